@@ -0,0 +1,12 @@
+-- Rotating refresh tokens. Each sessions row is one token family: every
+-- refresh marks the presented token as rotated and issues a successor.
+-- Presenting a token that was already rotated means it leaked, so the
+-- whole session is deactivated.
+CREATE TABLE IF NOT EXISTS session_refresh_tokens (
+    token_hash VARCHAR(64) PRIMARY KEY,
+    session_id VARCHAR(128) NOT NULL REFERENCES sessions(id) ON DELETE CASCADE,
+    issued_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    rotated_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_refresh_tokens_session_id ON session_refresh_tokens(session_id);
@@ -0,0 +1,14 @@
+-- Failed-login counters for brute-force protection. Kept in Postgres so
+-- lockouts survive restarts and are shared by every replica.
+--   scope = 'account' → key is users.id (or the raw identifier when no such user)
+--   scope = 'ip'      → key is the client IP
+CREATE TABLE IF NOT EXISTS login_throttles (
+    scope VARCHAR(10) NOT NULL,
+    key VARCHAR(255) NOT NULL,
+    failed_count INTEGER NOT NULL DEFAULT 0,
+    last_failed_at TIMESTAMPTZ,
+    locked_until TIMESTAMPTZ,
+    PRIMARY KEY (scope, key)
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_throttles_locked_until ON login_throttles(locked_until);
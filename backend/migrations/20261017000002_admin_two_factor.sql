@@ -0,0 +1,27 @@
+-- TOTP two-factor authentication for admin accounts.
+--   secret       → base32 RFC 6238 secret shown once during setup
+--   enabled_at   → NULL until the first code is verified, so an abandoned
+--                  setup never locks anyone out
+--   last_used_step → last accepted 30s step, rejects replay inside a window
+CREATE TABLE IF NOT EXISTS user_totp (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    secret VARCHAR(64) NOT NULL,
+    enabled_at TIMESTAMPTZ,
+    last_used_step BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- One-time recovery codes; only the SHA-256 of each code is stored.
+CREATE TABLE IF NOT EXISTS user_recovery_codes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash VARCHAR(64) NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_recovery_codes_user ON user_recovery_codes(user_id);
+
+-- Per-organization policy: every admin of the organization must enrol.
+-- Super admins have no organization and follow REQUIRE_SUPER_ADMIN_2FA.
+ALTER TABLE organizations ADD COLUMN IF NOT EXISTS require_admin_2fa BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,27 @@
+-- OpenID Connect single sign-on.
+
+-- Links an IdP account (issuer + sub) to a local user. `sub` is the only
+-- claim guaranteed stable, so it — not email — is what later sign-ins use.
+CREATE TABLE IF NOT EXISTS user_identities (
+    issuer VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    email VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_login_at TIMESTAMPTZ,
+    PRIMARY KEY (issuer, subject)
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_identities_user ON user_identities(user_id);
+
+-- In-flight authorization requests: state → PKCE verifier + nonce.
+-- Rows are consumed by the callback and swept after 10 minutes.
+CREATE TABLE IF NOT EXISTS oidc_login_states (
+    state VARCHAR(64) PRIMARY KEY,
+    code_verifier VARCHAR(128) NOT NULL,
+    nonce VARCHAR(64) NOT NULL,
+    return_to TEXT NOT NULL DEFAULT '/',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_oidc_login_states_created_at ON oidc_login_states(created_at);
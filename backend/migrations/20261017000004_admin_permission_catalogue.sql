@@ -0,0 +1,43 @@
+-- admin_roles.permissions is now enforced (see auth/permissions.rs).
+-- Rows written before that hold either nothing or free-form labels such as
+-- 'ViewDashboard'; give them their level's default catalogue so nobody
+-- loses access on deploy. Rows that already use catalogue keys are kept.
+UPDATE admin_roles
+SET permissions = ARRAY[
+        'activities.create', 'activities.update', 'activities.delete',
+        'participations.manual_complete',
+        'users.view', 'users.update', 'users.reset_password', 'users.unlock_login', 'users.delete',
+        'qr.scan',
+        'departments.view', 'departments.manage',
+        'organizations.view', 'organizations.requirements'
+    ],
+    updated_at = NOW()
+WHERE admin_level = 'organization_admin'
+  AND NOT permissions && ARRAY[
+        'activities.create', 'activities.update', 'activities.delete',
+        'participations.manual_complete',
+        'users.view', 'users.update', 'users.reset_password', 'users.unlock_login', 'users.delete',
+        'qr.scan',
+        'departments.view', 'departments.manage',
+        'organizations.view', 'organizations.manage', 'organizations.requirements'
+    ];
+
+UPDATE admin_roles
+SET permissions = ARRAY[
+        'activities.create', 'activities.update', 'activities.delete',
+        'participations.manual_complete',
+        'users.view',
+        'qr.scan',
+        'departments.view', 'departments.manage',
+        'organizations.view', 'organizations.requirements'
+    ],
+    updated_at = NOW()
+WHERE admin_level = 'regular_admin'
+  AND NOT permissions && ARRAY[
+        'activities.create', 'activities.update', 'activities.delete',
+        'participations.manual_complete',
+        'users.view', 'users.update', 'users.reset_password', 'users.unlock_login', 'users.delete',
+        'qr.scan',
+        'departments.view', 'departments.manage',
+        'organizations.view', 'organizations.manage', 'organizations.requirements'
+    ];
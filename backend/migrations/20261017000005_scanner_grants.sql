@@ -0,0 +1,28 @@
+-- Limited "scanner" access for check-in staff (typically student
+-- volunteers). A grant covers one activity, or every activity of one
+-- organization until `expires_at`. Holders may only scan QR codes and read
+-- the roster of covered activities; they get no admin claims.
+CREATE TABLE IF NOT EXISTS scanner_grants (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    activity_id UUID REFERENCES activities(id) ON DELETE CASCADE,
+    organization_id UUID REFERENCES organizations(id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ,
+    granted_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ,
+    CHECK ((activity_id IS NULL) <> (organization_id IS NULL)),
+    CHECK (organization_id IS NULL OR expires_at IS NOT NULL)
+);
+
+CREATE INDEX IF NOT EXISTS idx_scanner_grants_user ON scanner_grants(user_id);
+CREATE INDEX IF NOT EXISTS idx_scanner_grants_activity ON scanner_grants(activity_id);
+CREATE INDEX IF NOT EXISTS idx_scanner_grants_organization ON scanner_grants(organization_id);
+
+-- New catalogue permission; default for organization and regular admins.
+-- An admin whose permissions were deliberately emptied stays without it.
+UPDATE admin_roles
+SET permissions = array_append(permissions, 'scanners.manage'), updated_at = NOW()
+WHERE admin_level IN ('organization_admin', 'regular_admin')
+  AND cardinality(permissions) > 0
+  AND NOT ('scanners.manage' = ANY(permissions));
@@ -0,0 +1,18 @@
+-- audit_logs has existed since the initial schema but nothing wrote to it.
+-- Give id/log_date server-side defaults so inserts only carry the payload,
+-- and record which organization an entry belongs to so org admins can be
+-- shown their own trail. No FK on organization_id: entries must outlive
+-- the organization they describe.
+ALTER TABLE audit_logs ALTER COLUMN id ADD GENERATED BY DEFAULT AS IDENTITY;
+ALTER TABLE audit_logs ALTER COLUMN log_date SET DEFAULT CURRENT_DATE;
+ALTER TABLE audit_logs ADD COLUMN IF NOT EXISTS organization_id UUID;
+
+CREATE INDEX IF NOT EXISTS idx_audit_logs_org_timestamp ON audit_logs(organization_id, timestamp DESC);
+
+-- New catalogue permission; default for organization admins. An admin
+-- whose permissions were deliberately emptied stays without it.
+UPDATE admin_roles
+SET permissions = array_append(permissions, 'audit.view'), updated_at = NOW()
+WHERE admin_level = 'organization_admin'
+  AND cardinality(permissions) > 0
+  AND NOT ('audit.view' = ANY(permissions));
@@ -0,0 +1,7 @@
+-- Super-admin "act as user" sessions. A session with impersonator_id set
+-- belongs to the impersonated user but was opened by that admin;
+-- impersonator_session_id is the admin's own session, resumed on stop.
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS impersonator_id UUID REFERENCES users(id) ON DELETE CASCADE;
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS impersonator_session_id VARCHAR(128);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_impersonator ON sessions(impersonator_id) WHERE impersonator_id IS NOT NULL;
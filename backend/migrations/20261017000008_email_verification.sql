@@ -0,0 +1,7 @@
+-- Self-registered accounts stay unverified until the emailed link is
+-- opened; login is refused until then. Existing accounts predate the
+-- requirement and are treated as verified.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verification_sent_at TIMESTAMPTZ;
+
+UPDATE users SET email_verified_at = COALESCE(created_at, NOW()) WHERE email_verified_at IS NULL;
@@ -0,0 +1,5 @@
+-- email_queue becomes the outbox drained by the mail worker; it only ever
+-- looks at pending rows that are due.
+CREATE INDEX IF NOT EXISTS idx_email_queue_due
+    ON email_queue (scheduled_for)
+    WHERE status = 'pending';
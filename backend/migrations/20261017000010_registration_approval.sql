@@ -0,0 +1,21 @@
+-- Optional admin vetting of self-registrations. Organizations that turn on
+-- require_registration_approval get new students in `pending` until one of
+-- their admins approves (→ active) or rejects (→ rejected) them.
+ALTER TYPE user_status ADD VALUE IF NOT EXISTS 'pending';
+ALTER TYPE user_status ADD VALUE IF NOT EXISTS 'rejected';
+
+ALTER TABLE organizations
+    ADD COLUMN IF NOT EXISTS require_registration_approval BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS registration_reviewed_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS registration_reviewed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    ADD COLUMN IF NOT EXISTS registration_review_reason TEXT;
+
+-- New catalogue permission; default for organization admins. An admin
+-- whose permissions were deliberately emptied stays without it.
+UPDATE admin_roles
+SET permissions = array_append(permissions, 'users.approve'), updated_at = NOW()
+WHERE admin_level = 'organization_admin'
+  AND cardinality(permissions) > 0
+  AND NOT ('users.approve' = ANY(permissions));
@@ -0,0 +1,14 @@
+-- Queue for activities that have reached max_participants. Seats freed by a
+-- cancellation or a raised cap are handed to the lowest position first.
+-- Positions only need to be ordered, not contiguous.
+CREATE TABLE IF NOT EXISTS activity_waitlist (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    activity_id UUID NOT NULL REFERENCES activities(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE(activity_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_activity_waitlist_queue
+    ON activity_waitlist(activity_id, position, created_at);
@@ -0,0 +1,14 @@
+-- Students may withdraw from an activity before it starts. The
+-- participation row is deleted so the seat (and the UNIQUE slot) is free
+-- again; this table keeps the history and the reason for admins.
+CREATE TABLE IF NOT EXISTS participation_cancellations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    activity_id UUID NOT NULL REFERENCES activities(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason TEXT,
+    registered_at TIMESTAMPTZ,
+    cancelled_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_participation_cancellations_activity
+    ON participation_cancellations(activity_id, cancelled_at DESC);
@@ -0,0 +1,27 @@
+-- When an activity completes, registrations nobody checked in for become
+-- `no_show`, check-ins without a checkout are flagged, and the outcome is
+-- kept per activity.
+ALTER TABLE participations
+    ADD COLUMN IF NOT EXISTS no_show_marked_at TIMESTAMPTZ,
+    ADD COLUMN IF NOT EXISTS checkout_missing BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS idx_participations_user_no_show
+    ON participations(user_id, no_show_marked_at)
+    WHERE status = 'no_show';
+
+CREATE TABLE IF NOT EXISTS activity_attendance_summaries (
+    activity_id UUID PRIMARY KEY REFERENCES activities(id) ON DELETE CASCADE,
+    registered_count INTEGER NOT NULL,
+    attended_count INTEGER NOT NULL,
+    no_show_count INTEGER NOT NULL,
+    checkout_missing_count INTEGER NOT NULL,
+    finalized_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Per-organization penalty: a student with at least
+-- no_show_block_threshold no-shows in the last no_show_window_days can't
+-- register for no_show_block_days after the latest one. 0 disables it.
+ALTER TABLE organizations
+    ADD COLUMN IF NOT EXISTS no_show_block_threshold INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS no_show_window_days INTEGER NOT NULL DEFAULT 90,
+    ADD COLUMN IF NOT EXISTS no_show_block_days INTEGER NOT NULL DEFAULT 30;
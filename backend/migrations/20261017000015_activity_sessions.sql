@@ -0,0 +1,32 @@
+-- Multi-day / multi-session activities. An activity without sessions keeps
+-- the single check-in / check-out on `participations`; one with sessions
+-- records attendance per session and counts the participation as
+-- `completed` once `required_sessions` (NULL = all of them) have been
+-- attended, i.e. checked in and out.
+CREATE TABLE IF NOT EXISTS activity_sessions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    activity_id UUID NOT NULL REFERENCES activities(id) ON DELETE CASCADE,
+    title VARCHAR(255),
+    session_date DATE NOT NULL,
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (end_time > start_time),
+    UNIQUE(activity_id, session_date, start_time)
+);
+
+CREATE TABLE IF NOT EXISTS session_attendance (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    session_id UUID NOT NULL REFERENCES activity_sessions(id) ON DELETE CASCADE,
+    participation_id UUID NOT NULL REFERENCES participations(id) ON DELETE CASCADE,
+    checked_in_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    checked_out_at TIMESTAMPTZ,
+    UNIQUE(session_id, participation_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_session_attendance_participation
+    ON session_attendance(participation_id);
+
+ALTER TABLE activities
+    ADD COLUMN IF NOT EXISTS required_sessions INTEGER CHECK (required_sessions > 0);
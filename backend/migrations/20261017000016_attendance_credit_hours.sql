@@ -0,0 +1,29 @@
+-- Credit hours per participation. `fixed` activities credit `hours` to
+-- every completed participant (the old behaviour); `attendance` activities
+-- credit the time actually attended, rounded to credit_rounding_minutes,
+-- capped at `hours`, and nothing below credit_min_attendance_percent of
+-- `hours`. Admins can override the result with a reason.
+ALTER TABLE activities
+    ADD COLUMN IF NOT EXISTS hours_mode TEXT NOT NULL DEFAULT 'fixed'
+        CHECK (hours_mode IN ('fixed', 'attendance')),
+    ADD COLUMN IF NOT EXISTS credit_rounding TEXT NOT NULL DEFAULT 'down'
+        CHECK (credit_rounding IN ('down', 'nearest', 'up')),
+    ADD COLUMN IF NOT EXISTS credit_rounding_minutes INTEGER NOT NULL DEFAULT 30
+        CHECK (credit_rounding_minutes BETWEEN 1 AND 60),
+    ADD COLUMN IF NOT EXISTS credit_min_attendance_percent INTEGER NOT NULL DEFAULT 50
+        CHECK (credit_min_attendance_percent BETWEEN 0 AND 100);
+
+ALTER TABLE participations
+    ADD COLUMN IF NOT EXISTS credited_hours NUMERIC(6, 2),
+    ADD COLUMN IF NOT EXISTS credited_hours_override NUMERIC(6, 2),
+    ADD COLUMN IF NOT EXISTS credit_override_reason TEXT,
+    ADD COLUMN IF NOT EXISTS credit_overridden_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    ADD COLUMN IF NOT EXISTS credit_overridden_at TIMESTAMPTZ;
+
+-- Existing completions were all credited the fixed hours.
+UPDATE participations p
+SET credited_hours = a.hours
+FROM activities a
+WHERE a.id = p.activity_id
+  AND p.status IN ('checked_out', 'completed')
+  AND p.credited_hours IS NULL;
@@ -0,0 +1,22 @@
+-- New catalogue permissions guarding the admin activity list, the dashboard
+-- counts and the admin/permission listings, which used to check only that
+-- the caller was an admin. Granted by default to the levels that could see
+-- them before; an admin whose permissions were deliberately emptied stays
+-- without them.
+UPDATE admin_roles
+SET permissions = array_append(permissions, 'activities.view'), updated_at = NOW()
+WHERE admin_level IN ('organization_admin', 'regular_admin')
+  AND cardinality(permissions) > 0
+  AND NOT ('activities.view' = ANY(permissions));
+
+UPDATE admin_roles
+SET permissions = array_append(permissions, 'dashboard.view'), updated_at = NOW()
+WHERE admin_level IN ('organization_admin', 'regular_admin')
+  AND cardinality(permissions) > 0
+  AND NOT ('dashboard.view' = ANY(permissions));
+
+UPDATE admin_roles
+SET permissions = array_append(permissions, 'admins.view'), updated_at = NOW()
+WHERE admin_level = 'organization_admin'
+  AND cardinality(permissions) > 0
+  AND NOT ('admins.view' = ANY(permissions));
@@ -2,7 +2,7 @@ use axum::{
     extract::State,
     http::StatusCode,
     response::IntoResponse,
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
     Json, Router,
 };
 use sqlx::postgres::{PgPool, PgPoolOptions, PgConnectOptions};
@@ -11,7 +11,7 @@ use std::str::FromStr;
 use std::time::Duration;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-use axum::http::{HeaderValue, Method, header};
+use axum::http::{HeaderName, HeaderValue, Method, header};
 
 mod models;
 mod modules;
@@ -92,6 +92,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .expect("Failed to run migrations");
     tracing::info!("✅ Migrations executed successfully!");
 
+    auth::keys::init().expect("Invalid JWT signing keys");
+
     let frontend_urls = std::env::var("FRONTEND_URL")
         .unwrap_or_else(|_| "http://localhost:5173".to_string());
     
@@ -99,6 +101,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .split(',')
         .map(|s| s.trim().parse::<HeaderValue>().expect("Invalid FRONTEND_URL"))
         .collect();
+    let csrf_origins = auth::csrf::AllowedOrigins::new(frontend_urls.split(','));
+    let trusted_proxy = auth::client_ip::TrustedProxy::from_env();
 
     let cors = CorsLayer::new()
         .allow_origin(allowed_origins)
@@ -107,9 +111,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             header::CONTENT_TYPE,
             header::AUTHORIZATION,
             header::COOKIE,
+            HeaderName::from_static("x-api-key"),
         ])
         .allow_credentials(true);
 
+    let mailer = modules::mailer::Mailer::from_env().expect("Invalid mail configuration");
+    tokio::spawn(modules::mailer::outbox::run(pool.clone(), mailer));
+    tokio::spawn(activities::scheduler::run(pool.clone()));
+
     let app = Router::new()
         .route("/", get(|| async { "Trackivity Backend is running! 🚀" }))
         .route("/health", get(health_check))
@@ -117,14 +126,33 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/auth/login", post(auth::login_handler))
         .route("/auth/register", post(auth::register_handler))
         .route("/auth/logout", post(auth::logout_handler))
+        .route("/auth/refresh", post(auth::refresh_handler))
         .route("/auth/me", get(auth::me_handler))
+        .route("/auth/oidc/login", get(auth::oidc_login_handler))
+        .route("/auth/oidc/callback", get(auth::oidc_callback_handler))
+        .route("/auth/2fa/login", post(auth::two_factor_login_handler))
+        .route("/auth/2fa/setup", post(auth::two_factor_setup_handler))
+        .route("/auth/2fa/verify", post(auth::two_factor_verify_handler))
+        .route("/auth/impersonation/stop", post(auth::stop_impersonation_handler))
+        .route("/auth/sessions", get(auth::list_sessions_handler))
+        .route("/auth/sessions/revoke-others", post(auth::revoke_other_sessions_handler))
+        .route("/auth/sessions/{id}", delete(auth::revoke_session_handler))
+        .route("/auth/verify-email", post(auth::verify_email_handler))
+        .route("/auth/resend-verification", post(auth::resend_verification_handler))
+        .route("/auth/password-policy", get(auth::password_policy_handler))
+        .route("/.well-known/jwks.json", get(auth::jwks_handler))
         .route("/auth/forgot-password", post(auth::forgot_password_handler))
         .route("/auth/reset-password", post(auth::reset_password_handler))
         // ─── Activities ───────────────────────────────────
         .route("/activities/dashboard", get(activities::get_dashboard_activities))
         .route("/activities", get(activities::list_activities).post(activities::create_activity))
         .route("/activities/{id}", get(activities::get_activity).put(activities::update_activity).delete(activities::delete_activity))
-        .route("/activities/{id}/join", post(activities::join_activity))
+        .route("/activities/{id}/join", post(activities::join_activity).delete(activities::cancellation::cancel_registration))
+        .route("/activities/{id}/cancellations", get(activities::cancellation::list_cancellations))
+        .route("/activities/{id}/attendance-summary", get(activities::no_show::get_attendance_summary))
+        .route("/participations/{id}/credit", put(activities::credit::override_credit))
+        .route("/activities/{id}/waitlist", get(activities::waitlist::list_waitlist).post(activities::waitlist::join_waitlist).delete(activities::waitlist::leave_waitlist))
+        .route("/activities/{id}/waitlist/order", put(activities::waitlist::reorder_waitlist))
         .route("/activities/{id}/participations/manual-complete", post(activities::manual_complete_participations))
         .route("/activities/my/participations", get(activities::get_my_participations))
         // ─── Organizations ────────────────────────────────
@@ -133,6 +161,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/organizations/{id}", put(organizations::update_organization).delete(organizations::delete_organization))
         .route("/organizations/{id}/toggle-status", post(organizations::toggle_organization_status))
         .route("/organizations/{id}/requirements", get(organizations::get_activity_requirements).put(organizations::update_activity_requirements))
+        .route("/organizations/{id}/compliance", get(organizations::compliance::compliance_report))
         .route("/organizations/{id}/departments", get(organizations::get_departments))
         // ─── Departments ──────────────────────────────────
         .route("/departments", get(departments::list_departments).post(departments::create_department))
@@ -142,6 +171,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/users", get(users::list_users))
         .route("/users/me/profile", put(users::update_profile))
         .route("/users/me/password", post(users::change_password))
+        .route("/users/me/progress", get(users::get_my_progress))
         .route(
             "/users/{id}",
             get(users::get_user)
@@ -149,17 +179,34 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .delete(users::admin_delete_user),
         )
         .route("/users/{id}/reset-password", post(users::admin_reset_password))
+        .route("/users/{id}/unlock-login", post(users::admin_unlock_login))
         .route("/users/{id}/participations", get(users::admin_get_user_participations))
+        .route("/users/{id}/progress", get(users::admin_get_user_progress))
+        .route("/admin/registrations", get(users::list_pending_registrations))
+        .route("/admin/registrations/{id}/approve", post(users::approve_registration))
+        .route("/admin/registrations/{id}/reject", post(users::reject_registration))
         // ─── QR Code ──────────────────────────────────────────
         .route("/qr/generate", post(qr::handlers::generate_qr_handler))
         .route("/activities/{id}/checkin", post(qr::handlers::checkin_handler))
         .route("/activities/{id}/checkout", post(qr::handlers::checkout_handler))
+        .route("/activities/{id}/roster", get(qr::handlers::activity_roster_handler))
+        .route("/activities/{id}/sessions", get(activities::sessions::list_sessions).post(activities::sessions::create_session))
+        .route("/activities/{id}/sessions/{session_id}", put(activities::sessions::update_session).delete(activities::sessions::delete_session))
+        .route("/activities/{id}/sessions/{session_id}/roster", get(qr::handlers::session_roster_handler))
+        .route("/scanner-grants", get(qr::handlers::list_scanner_grants).post(qr::handlers::create_scanner_grant))
+        .route("/scanner-grants/mine", get(qr::handlers::my_scanner_grants))
+        .route("/scanner-grants/{id}", delete(qr::handlers::revoke_scanner_grant))
         // ─── Admins ───────────────────────────────────────
         .route("/admins", get(admins::handlers::list_admins).post(admins::handlers::create_admin))
         .route("/admins/{id}", put(admins::handlers::update_admin).delete(admins::handlers::delete_admin))
         .route("/admins/{id}/toggle-status", post(admins::handlers::toggle_admin_status))
         // ─── Admin Dashboard ──────────────────────────────
         .route("/admin/dashboard-stats", get(admins::handlers::get_dashboard_stats))
+        .route("/admin/permissions", get(admins::handlers::list_permissions))
+        .route("/admin/api-keys", get(modules::api_keys::handlers::list_api_keys).post(modules::api_keys::handlers::create_api_key))
+        .route("/admin/api-keys/{id}", delete(modules::api_keys::handlers::revoke_api_key))
+        .route("/admin/audit-logs", get(modules::audit::handlers::list_audit_logs))
+        .route("/admin/impersonate/{user_id}", post(auth::start_impersonation_handler))
         // ─── Organization Admins ──────────────────────────
         .route("/organization-admins", get(admins::handlers::list_organization_admins))
         // ─── Notifications ────────────────────────────────
@@ -168,6 +215,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/notifications/read-all", put(modules::notifications::handlers::mark_all_read))
         .route("/notifications/{id}/read", put(modules::notifications::handlers::mark_read))
         // ─── Middleware ───────────────────────────────────
+        .layer(axum::middleware::from_fn_with_state(csrf_origins, auth::csrf::verify_origin))
+        .layer(axum::middleware::from_fn_with_state(trusted_proxy, auth::client_ip::resolve_client_ip))
         .layer(cors)
         .layer(TraceLayer::new_for_http())
         .with_state(pool);
@@ -175,7 +224,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let addr = SocketAddr::from(([0, 0, 0, 0], 3000));
     tracing::info!("🚀 Listening on {}", addr);
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
 
     Ok(())
 }
@@ -13,6 +13,9 @@ pub enum UserStatus {
     Active,
     Inactive,
     Suspended,
+    /// Self-registered, waiting for an admin of the organization to approve.
+    Pending,
+    Rejected,
 }
 
 #[derive(Debug, Serialize, Deserialize, sqlx::Type, Clone, PartialEq)]
@@ -64,6 +67,11 @@ pub struct Organization {
     pub description: Option<String>,
     pub organization_type: OrganizationType,
     pub status: bool,
+    pub require_admin_2fa: bool,
+    pub require_registration_approval: bool,
+    pub no_show_block_threshold: i32,
+    pub no_show_window_days: i32,
+    pub no_show_block_days: i32,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
@@ -83,6 +91,7 @@ pub struct User {
     pub department_id: Option<Uuid>,
     pub deleted_at: Option<DateTime<Utc>>,
     pub last_login_at: Option<DateTime<Utc>>,
+    pub email_verified_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
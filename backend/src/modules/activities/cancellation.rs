@@ -0,0 +1,147 @@
+//! Students withdrawing their own registration.
+//!
+//! Allowed while the activity is published and at least
+//! `REGISTRATION_CANCEL_CUTOFF_HOURS` (default 24) before it starts, and
+//! only before check-in. The participation row is removed so the seat goes
+//! back to the pool — and straight to the waitlist, in the same
+//! transaction — while `participation_cancellations` keeps the history.
+
+use axum::{Json, extract::{State, Path}, http::{StatusCode, HeaderMap}};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::modules::auth::get_claims_from_headers;
+use crate::modules::auth::impersonation::forbid_while_impersonating;
+use crate::modules::auth::permissions::{require_permission, Permission};
+use super::handlers::assert_admin_can_manage_activity;
+use super::models::{CancelRegistrationInput, CancellationEntry};
+use super::waitlist;
+
+const DEFAULT_CUTOFF_HOURS: i32 = 24;
+const MAX_REASON_CHARS: usize = 500;
+
+fn cutoff_hours() -> i32 {
+    std::env::var("REGISTRATION_CANCEL_CUTOFF_HOURS")
+        .ok()
+        .and_then(|v| v.trim().parse::<i32>().ok())
+        .filter(|h| *h >= 0)
+        .unwrap_or(DEFAULT_CUTOFF_HOURS)
+}
+
+pub async fn cancel_registration(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(activity_id): Path<Uuid>,
+    payload: Option<Json<CancelRegistrationInput>>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    forbid_while_impersonating(&claims)?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
+
+    let reason = payload
+        .and_then(|Json(p)| p.reason)
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    if reason.as_ref().is_some_and(|r| r.chars().count() > MAX_REASON_CHARS) {
+        return Err((StatusCode::BAD_REQUEST, format!("เหตุผลต้องไม่เกิน {} ตัวอักษร", MAX_REASON_CHARS)));
+    }
+
+    let mut tx = pool.begin().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB tx error: {}", e)))?;
+
+    #[derive(sqlx::FromRow)]
+    struct ActivityRow {
+        status: String,
+        before_cutoff: bool,
+    }
+
+    // Lock the activity so the freed seat and the waitlist promotion can't
+    // race a concurrent join.
+    let activity = sqlx::query_as::<_, ActivityRow>(r#"
+        SELECT status::text AS status,
+               NOW() < ((start_date + COALESCE(start_time_only, TIME '00:00')) AT TIME ZONE 'Asia/Bangkok')
+                       - make_interval(hours => $2) AS before_cutoff
+        FROM activities
+        WHERE id = $1 AND deleted_at IS NULL
+        FOR UPDATE
+    "#)
+    .bind(activity_id)
+    .bind(cutoff_hours())
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .ok_or((StatusCode::NOT_FOUND, "Activity not found".to_string()))?;
+
+    if activity.status != "published" {
+        return Err((StatusCode::CONFLICT, "ไม่สามารถยกเลิกการลงทะเบียนได้ (กิจกรรมไม่ได้อยู่ในสถานะเผยแพร่)".to_string()));
+    }
+    if !activity.before_cutoff {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("ยกเลิกการลงทะเบียนได้ก่อนเริ่มกิจกรรมอย่างน้อย {} ชั่วโมงเท่านั้น", cutoff_hours()),
+        ));
+    }
+
+    let participation_status: Option<String> = sqlx::query_scalar(
+        "SELECT status::text FROM participations WHERE activity_id = $1 AND user_id = $2"
+    )
+    .bind(activity_id)
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    match participation_status.as_deref() {
+        None => return Err((StatusCode::NOT_FOUND, "ยังไม่ได้ลงทะเบียนกิจกรรมนี้".to_string())),
+        Some("registered") => {}
+        Some(_) => return Err((StatusCode::CONFLICT, "เช็คอินแล้ว ไม่สามารถยกเลิกการลงทะเบียนได้".to_string())),
+    }
+
+    sqlx::query(r#"
+        WITH removed AS (
+            DELETE FROM participations
+            WHERE activity_id = $1 AND user_id = $2
+            RETURNING registered_at
+        )
+        INSERT INTO participation_cancellations (activity_id, user_id, reason, registered_at)
+        SELECT $1, $2, $3, registered_at FROM removed
+    "#)
+    .bind(activity_id)
+    .bind(user_id)
+    .bind(&reason)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to cancel registration: {}", e)))?;
+
+    let promoted = waitlist::promote(&mut tx, activity_id).await?;
+
+    tx.commit().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB commit error: {}", e)))?;
+    waitlist::notify_promoted(&pool, activity_id, &promoted).await;
+
+    Ok(Json(serde_json::json!({ "message": "Registration cancelled" })))
+}
+
+pub async fn list_cancellations(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(activity_id): Path<Uuid>,
+) -> Result<Json<Vec<CancellationEntry>>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ActivitiesUpdate)?;
+    assert_admin_can_manage_activity(&pool, &claims, activity_id).await?;
+
+    let rows = sqlx::query_as::<_, CancellationEntry>(r#"
+        SELECT c.id, u.id AS user_id, u.student_id, u.first_name, u.last_name,
+               c.reason, c.registered_at, c.cancelled_at
+        FROM participation_cancellations c
+        JOIN users u ON u.id = c.user_id
+        WHERE c.activity_id = $1
+        ORDER BY c.cancelled_at DESC
+    "#)
+    .bind(activity_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+
+    Ok(Json(rows))
+}
@@ -0,0 +1,265 @@
+//! Credit hours for completed participations.
+//!
+//! `fixed` activities credit their `hours` to every completed participant.
+//! `attendance` activities credit the time between check-in and check-out
+//! (summed over sessions for multi-session activities), rounded to
+//! `credit_rounding_minutes` in the `credit_rounding` direction, capped at
+//! `hours`, and zero below `credit_min_attendance_percent` of `hours`.
+//!
+//! The result is stored in `participations.credited_hours` by [`recompute`]
+//! whenever a participation completes or the activity's rule changes. An
+//! admin override (`credited_hours_override`) always wins; read credited
+//! hours through [`EFFECTIVE_HOURS_SQL`].
+
+use axum::{Json, extract::{State, Path}, http::{StatusCode, HeaderMap}};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::modules::audit::service::{self as audit, AuditEntry};
+use crate::modules::auth::get_claims_from_headers;
+use crate::modules::auth::permissions::{require_permission, Permission};
+use super::handlers::assert_admin_can_manage_activity;
+use super::models::OverrideCreditInput;
+
+/// Credited hours of participation `p` in activity `a`, for SELECTs.
+pub const EFFECTIVE_HOURS_SQL: &str = "COALESCE(p.credited_hours_override, p.credited_hours, \
+    CASE WHEN p.status IN ('checked_out', 'completed') THEN a.hours ELSE 0 END)::float8";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HoursMode {
+    Fixed,
+    Attendance,
+}
+
+impl HoursMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HoursMode::Fixed => "fixed",
+            HoursMode::Attendance => "attendance",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CreditRounding {
+    Down,
+    Nearest,
+    Up,
+}
+
+impl CreditRounding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CreditRounding::Down => "down",
+            CreditRounding::Nearest => "nearest",
+            CreditRounding::Up => "up",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CreditRule {
+    pub hours: i16,
+    pub attendance_based: bool,
+    pub rounding: CreditRounding,
+    pub rounding_minutes: i64,
+    pub min_attendance_percent: i64,
+}
+
+impl CreditRule {
+    /// Hours credited for `attended_minutes` of presence.
+    pub fn credit(&self, attended_minutes: i64) -> f64 {
+        let max_minutes = self.hours.max(0) as i64 * 60;
+        if !self.attendance_based {
+            return self.hours.max(0) as f64;
+        }
+        let attended = attended_minutes.max(0);
+        if attended * 100 < max_minutes * self.min_attendance_percent {
+            return 0.0;
+        }
+        let step = self.rounding_minutes.max(1);
+        let steps = match self.rounding {
+            CreditRounding::Down => attended / step,
+            CreditRounding::Nearest => (attended + step / 2) / step,
+            CreditRounding::Up => (attended + step - 1) / step,
+        };
+        (steps * step).min(max_minutes) as f64 / 60.0
+    }
+}
+
+/// Recalculate `credited_hours` for the activity's participations —
+/// only `user_ids` when given. Participations that aren't completed get
+/// no credit.
+pub async fn recompute(
+    conn: &mut PgConnection,
+    activity_id: Uuid,
+    user_ids: Option<&[Uuid]>,
+) -> Result<(), (StatusCode, String)> {
+    #[derive(sqlx::FromRow)]
+    struct Row {
+        id: Uuid,
+        completed: bool,
+        attended_minutes: i64,
+        hours: i16,
+        hours_mode: String,
+        credit_rounding: String,
+        credit_rounding_minutes: i32,
+        credit_min_attendance_percent: i32,
+    }
+
+    let rows = sqlx::query_as::<_, Row>(r#"
+        SELECT p.id,
+               p.status IN ('checked_out', 'completed') AS completed,
+               COALESCE(
+                   CASE WHEN EXISTS (SELECT 1 FROM activity_sessions s WHERE s.activity_id = a.id)
+                        THEN (SELECT SUM(EXTRACT(EPOCH FROM sa.checked_out_at - sa.checked_in_at))
+                              FROM session_attendance sa
+                              WHERE sa.participation_id = p.id AND sa.checked_out_at IS NOT NULL)
+                        ELSE EXTRACT(EPOCH FROM p.checked_out_at - p.checked_in_at)
+                   END / 60,
+                   0
+               )::bigint AS attended_minutes,
+               a.hours, a.hours_mode, a.credit_rounding,
+               a.credit_rounding_minutes, a.credit_min_attendance_percent
+        FROM participations p
+        JOIN activities a ON a.id = p.activity_id
+        WHERE p.activity_id = $1 AND ($2::uuid[] IS NULL OR p.user_id = ANY($2))
+    "#)
+    .bind(activity_id)
+    .bind(user_ids)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to load attendance: {}", e)))?;
+
+    for row in rows {
+        let credited = row.completed.then(|| {
+            CreditRule {
+                hours: row.hours,
+                attendance_based: row.hours_mode == "attendance",
+                rounding: match row.credit_rounding.as_str() {
+                    "nearest" => CreditRounding::Nearest,
+                    "up" => CreditRounding::Up,
+                    _ => CreditRounding::Down,
+                },
+                rounding_minutes: row.credit_rounding_minutes as i64,
+                min_attendance_percent: row.credit_min_attendance_percent as i64,
+            }
+            .credit(row.attended_minutes)
+        });
+        sqlx::query("UPDATE participations SET credited_hours = $2::float8::numeric(6, 2) WHERE id = $1")
+            .bind(row.id)
+            .bind(credited)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to store credited hours: {}", e)))?;
+    }
+    Ok(())
+}
+
+/// Set or clear (`hours: null`) the admin override of one participation's
+/// credited hours. A reason is required either way.
+pub async fn override_credit(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(participation_id): Path<Uuid>,
+    Json(payload): Json<OverrideCreditInput>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ParticipationsManualComplete)?;
+
+    let reason = payload.reason.trim();
+    if reason.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "กรุณาระบุเหตุผล".to_string()));
+    }
+
+    let (activity_id, organizer_id, hours): (Uuid, Uuid, i16) = sqlx::query_as(r#"
+        SELECT a.id, a.organizer_id, a.hours
+        FROM participations p JOIN activities a ON a.id = p.activity_id
+        WHERE p.id = $1
+    "#)
+    .bind(participation_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::NOT_FOUND, "Participation not found".to_string()))?;
+    assert_admin_can_manage_activity(&pool, &claims, activity_id).await?;
+
+    if let Some(h) = payload.hours {
+        if !h.is_finite() || h < 0.0 || h > hours.max(0) as f64 {
+            return Err((StatusCode::BAD_REQUEST, format!("ชั่วโมงต้องอยู่ระหว่าง 0 ถึง {}", hours)));
+        }
+    }
+
+    let admin_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "participations", participation_id).await?;
+
+    sqlx::query(r#"
+        UPDATE participations
+        SET credited_hours_override = $2::float8::numeric(6, 2),
+            credit_override_reason = $3,
+            credit_overridden_by = $4,
+            credit_overridden_at = NOW()
+        WHERE id = $1
+    "#)
+    .bind(participation_id)
+    .bind(payload.hours)
+    .bind(reason)
+    .bind(admin_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to override credit: {}", e)))?;
+
+    let after = audit::snapshot(&mut *tx, "participations", participation_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("participation.credit_override", "participation", participation_id)
+            .organization(Some(organizer_id))
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
+    Ok(Json(serde_json::json!({ "message": "Credited hours updated" })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(rounding: CreditRounding) -> CreditRule {
+        CreditRule {
+            hours: 3,
+            attendance_based: true,
+            rounding,
+            rounding_minutes: 30,
+            min_attendance_percent: 50,
+        }
+    }
+
+    #[test]
+    fn attendance_credit_is_rounded_capped_and_thresholded() {
+        assert_eq!(rule(CreditRounding::Down).credit(100), 1.5);
+        assert_eq!(rule(CreditRounding::Nearest).credit(100), 1.5);
+        assert_eq!(rule(CreditRounding::Nearest).credit(106), 2.0);
+        assert_eq!(rule(CreditRounding::Up).credit(91), 2.0);
+        // Capped at the activity's hours.
+        assert_eq!(rule(CreditRounding::Up).credit(400), 3.0);
+        // Under 50% of 3 hours credits nothing.
+        assert_eq!(rule(CreditRounding::Up).credit(89), 0.0);
+
+        let fixed = CreditRule { attendance_based: false, ..rule(CreditRounding::Down) };
+        assert_eq!(fixed.credit(0), 3.0);
+    }
+}
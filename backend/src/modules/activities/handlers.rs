@@ -3,8 +3,12 @@ use serde::Deserialize;
 use sqlx::PgPool;
 use std::collections::{HashMap, HashSet};
 use crate::models::{ActivityStatus, AdminLevel};
+use crate::modules::audit::service::{self as audit, AuditEntry};
 use crate::modules::auth::get_claims_from_headers;
+use crate::modules::auth::impersonation::forbid_while_impersonating;
+use crate::modules::auth::permissions::{require_permission, Permission};
 use crate::modules::notifications::service::{NotificationService, NotificationType};
+use super::{credit, no_show, scheduler, waitlist};
 use super::models::{
     ActivityPublic, CreateActivityInput, CreateActivityResponse, DashboardResponse,
     ManualCompleteParticipationResult, ManualCompleteParticipationsInput,
@@ -24,7 +28,7 @@ pub struct ListActivitiesQuery {
 /// Super admin can touch anything; org / regular admin can only touch
 /// activities organised by their own organization. The check is one
 /// SELECT, so callers should run it before any UPDATE / DELETE.
-async fn assert_admin_can_manage_activity(
+pub(crate) async fn assert_admin_can_manage_activity(
     pool: &PgPool,
     claims: &crate::modules::auth::models::Claims,
     activity_id: Uuid,
@@ -63,7 +67,8 @@ const ACTIVITY_SELECT: &str = r#"
         a.id, a.title, a.description, a.location,
         a.activity_type::text AS activity_type,
         a.start_date, a.end_date, a.start_time_only, a.end_time_only,
-        a.hours, a.max_participants, a.registration_open,
+        a.hours, a.hours_mode, a.credit_rounding, a.credit_rounding_minutes, a.credit_min_attendance_percent,
+        a.max_participants, a.registration_open, a.required_sessions,
         a.status::text AS status,
         a.created_at, a.updated_at,
         a.organizer_id, o.name AS organizer_name,
@@ -91,7 +96,7 @@ pub async fn get_dashboard_activities(
     headers: HeaderMap,
 ) -> Result<Json<DashboardResponse>, (StatusCode, String)> {
     // Try to determine user's organization from their department
-    let user_org_id: Option<Uuid> = if let Ok(claims) = get_claims_from_headers(&headers) {
+    let user_org_id: Option<Uuid> = if let Ok(claims) = get_claims_from_headers(&pool, &headers).await {
         if let Some(dept_id) = claims.department_id {
             // Look up which organization this department belongs to
             sqlx::query_scalar::<_, Uuid>(
@@ -157,18 +162,17 @@ pub async fn list_activities(
     headers: HeaderMap,
     Query(params): Query<ListActivitiesQuery>,
 ) -> Result<Json<Vec<ActivityPublic>>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers);
-    let is_admin = claims.as_ref().map(|c| c.is_admin).unwrap_or(false);
-
-    if is_admin {
+    let claims = get_claims_from_headers(&pool, &headers).await;
+    if let Some(admin) = claims.as_ref().ok().filter(|c| c.is_admin) {
+        require_permission(admin, Permission::ActivitiesView)?;
         // Super admin sees everything; organization/regular admin only see
         // activities organized by their own organization. The frontend QR
         // scanner used to receive every activity (including drafts and other
         // orgs') and filter client-side; this scopes server-side instead.
-        let admin_org: Option<Uuid> = claims.as_ref().ok().and_then(|c| match c.admin_level {
+        let admin_org: Option<Uuid> = match admin.admin_level {
             Some(AdminLevel::SuperAdmin) => None,
-            _ => c.organization_id,
-        });
+            _ => admin.organization_id,
+        };
 
         let activities = sqlx::query_as::<_, ActivityPublic>(&format!(
             r#"
@@ -251,10 +255,8 @@ pub async fn create_activity(
     headers: HeaderMap,
     Json(payload): Json<CreateActivityInput>,
 ) -> Result<Json<CreateActivityResponse>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
-    }
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ActivitiesCreate)?;
     let user_id = Uuid::parse_str(&claims.sub)
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
 
@@ -275,6 +277,10 @@ pub async fn create_activity(
 
     let activity_id = Uuid::new_v4();
 
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
     sqlx::query(r#"
         INSERT INTO activities (
             id, title, description, location, activity_type,
@@ -302,10 +308,26 @@ pub async fn create_activity(
     .bind(ActivityStatus::Draft)
     .bind(payload.organizer_id)
     .bind(user_id)
-    .execute(&pool)
+    .execute(&mut *tx)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create activity: {}", e)))?;
 
+    let after = audit::snapshot(&mut *tx, "activities", activity_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("activity.create", "activity", activity_id)
+            .organization(Some(payload.organizer_id))
+            .after(after),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+    scheduler::wake();
+
     Ok(Json(CreateActivityResponse {
         activity_id,
         message: "Activity created successfully".to_string(),
@@ -318,7 +340,8 @@ pub async fn update_activity(
     Path(activity_id): Path<Uuid>,
     Json(payload): Json<UpdateActivityInput>,
 ) -> Result<Json<ActivityPublic>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ActivitiesUpdate)?;
     assert_admin_can_manage_activity(&pool, &claims, activity_id).await?;
 
     // Org / regular admin can't transfer the activity to another organization.
@@ -333,6 +356,29 @@ pub async fn update_activity(
         }
     }
 
+    if payload.required_sessions.is_some_and(|n| n < 0) {
+        return Err((StatusCode::BAD_REQUEST, "required_sessions must not be negative".to_string()));
+    }
+    // More than the activity has would leave nobody able to complete it.
+    if let Some(required) = payload.required_sessions.filter(|n| *n > 0) {
+        let session_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM activity_sessions WHERE activity_id = $1")
+            .bind(activity_id)
+            .fetch_one(&pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        if i64::from(required) > session_count {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("required_sessions exceeds the activity's {} sessions", session_count),
+            ));
+        }
+    }
+    if payload.credit_rounding_minutes.is_some_and(|m| !(1..=60).contains(&m))
+        || payload.credit_min_attendance_percent.is_some_and(|p| !(0..=100).contains(&p))
+    {
+        return Err((StatusCode::BAD_REQUEST, "Invalid credit hours rule".to_string()));
+    }
+
     // Build dynamic update
     let mut set_parts: Vec<String> = vec!["updated_at = NOW()".to_string()];
     let mut i = 1usize;
@@ -352,6 +398,11 @@ pub async fn update_activity(
     if payload.eligible_organizations.is_some() { i += 1; set_parts.push(format!("eligible_organizations = ${}", i)); }
     if payload.activity_type.is_some()        { i += 1; set_parts.push(format!("activity_type = ${}", i)); }
     if payload.hours.is_some()                { i += 1; set_parts.push(format!("hours = ${}", i)); }
+    if payload.required_sessions.is_some()    { i += 1; set_parts.push(format!("required_sessions = NULLIF(${}, 0)", i)); }
+    if payload.hours_mode.is_some()           { i += 1; set_parts.push(format!("hours_mode = ${}", i)); }
+    if payload.credit_rounding.is_some()      { i += 1; set_parts.push(format!("credit_rounding = ${}", i)); }
+    if payload.credit_rounding_minutes.is_some() { i += 1; set_parts.push(format!("credit_rounding_minutes = ${}", i)); }
+    if payload.credit_min_attendance_percent.is_some() { i += 1; set_parts.push(format!("credit_min_attendance_percent = ${}", i)); }
     let _ = i;
 
     let set_clause = set_parts.join(", ");
@@ -359,6 +410,18 @@ pub async fn update_activity(
 
     let just_published = matches!(payload.status, Some(crate::models::ActivityStatus::Published));
     let just_opened = payload.registration_open == Some(true);
+    let capacity_changed = payload.max_participants.is_some();
+    let just_completed = matches!(payload.status, Some(ActivityStatus::Completed));
+    let credit_rule_changed = payload.hours.is_some()
+        || payload.hours_mode.is_some()
+        || payload.credit_rounding.is_some()
+        || payload.credit_rounding_minutes.is_some()
+        || payload.credit_min_attendance_percent.is_some();
+
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "activities", activity_id).await?;
 
     let mut q = sqlx::query(&update_query).bind(activity_id);
     if let Some(v) = payload.title               { q = q.bind(v); }
@@ -376,11 +439,54 @@ pub async fn update_activity(
     if let Some(v) = payload.eligible_organizations { q = q.bind(v); }
     if let Some(v) = payload.activity_type       { q = q.bind(v); }
     if let Some(v) = payload.hours               { q = q.bind(v); }
+    if let Some(v) = payload.required_sessions   { q = q.bind(v); }
+    if let Some(v) = payload.hours_mode          { q = q.bind(v.as_str()); }
+    if let Some(v) = payload.credit_rounding     { q = q.bind(v.as_str()); }
+    if let Some(v) = payload.credit_rounding_minutes { q = q.bind(v); }
+    if let Some(v) = payload.credit_min_attendance_percent { q = q.bind(v); }
 
-    q.execute(&pool)
+    q.execute(&mut *tx)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update activity: {}", e)))?;
 
+    // A raised cap hands the new seats to the waitlist in the same transaction.
+    let promoted = if capacity_changed {
+        waitlist::promote(&mut tx, activity_id).await?
+    } else {
+        Vec::new()
+    };
+    if credit_rule_changed {
+        credit::recompute(&mut tx, activity_id, None).await?;
+    }
+    let finalized = if just_completed {
+        Some(no_show::finalize(&mut tx, activity_id)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to finalize attendance: {}", e)))?)
+    } else {
+        None
+    };
+
+    let after = audit::snapshot(&mut *tx, "activities", activity_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("activity.update", "activity", activity_id)
+            .organization(audit::uuid_field(&after, "organizer_id"))
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+    scheduler::wake();
+    waitlist::notify_promoted(&pool, activity_id, &promoted).await;
+    if let Some(finalized) = &finalized {
+        no_show::notify(&pool, finalized).await;
+    }
+
     // Return updated activity
     let activity = sqlx::query_as::<_, ActivityPublic>(&format!("{} WHERE a.id = $1", ACTIVITY_SELECT))
         .bind(activity_id)
@@ -431,15 +537,35 @@ pub async fn delete_activity(
     headers: HeaderMap,
     Path(activity_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ActivitiesDelete)?;
     assert_admin_can_manage_activity(&pool, &claims, activity_id).await?;
 
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "activities", activity_id).await?;
+
     sqlx::query("DELETE FROM activities WHERE id = $1")
         .bind(activity_id)
-        .execute(&pool)
+        .execute(&mut *tx)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete activity: {}", e)))?;
 
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("activity.delete", "activity", activity_id)
+            .organization(audit::uuid_field(&before, "organizer_id"))
+            .before(before),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
     Ok(Json(serde_json::json!({ "message": "Activity deleted successfully" })))
 }
 
@@ -448,7 +574,8 @@ pub async fn join_activity(
     headers: HeaderMap,
     Path(activity_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    forbid_while_impersonating(&claims)?;
     let user_id = Uuid::parse_str(&claims.sub)
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
 
@@ -485,6 +612,7 @@ pub async fn join_activity(
     if activity.end_date < chrono::Utc::now().date_naive() {
         return Err((StatusCode::CONFLICT, "กิจกรรมสิ้นสุดแล้ว ไม่สามารถลงทะเบียนได้".to_string()));
     }
+    no_show::ensure_not_blocked(&mut *tx, user_id).await?;
     if let Some(max) = activity.max_participants {
         let current: i64 = sqlx::query_scalar(
             "SELECT COUNT(*) FROM participations WHERE activity_id = $1 AND status::text != 'no_show'"
@@ -610,10 +738,8 @@ pub async fn manual_complete_participations(
     Path(activity_id): Path<Uuid>,
     Json(payload): Json<ManualCompleteParticipationsInput>,
 ) -> Result<Json<ManualCompleteParticipationsResponse>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
-    }
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ParticipationsManualComplete)?;
     assert_admin_can_manage_activity(&pool, &claims, activity_id).await?;
 
     let normalized_student_ids = normalize_manual_student_ids(&payload.student_ids);
@@ -644,6 +770,7 @@ pub async fn manual_complete_participations(
     #[derive(sqlx::FromRow)]
     struct ManualActivityRow {
         title: String,
+        organizer_id: Uuid,
         status: String,
         checked_in_at: chrono::DateTime<chrono::Utc>,
         checked_out_at: chrono::DateTime<chrono::Utc>,
@@ -653,6 +780,7 @@ pub async fn manual_complete_participations(
         r#"
         SELECT
             title,
+            organizer_id,
             status::text AS status,
             ((start_date + start_time_only) AT TIME ZONE 'Asia/Bangkok') AS checked_in_at,
             ((end_date + end_time_only) AT TIME ZONE 'Asia/Bangkok') AS checked_out_at
@@ -832,6 +960,7 @@ pub async fn manual_complete_participations(
     };
 
     let mut notify_user_ids = Vec::new();
+    let mut previous_statuses = serde_json::Map::new();
     for user in candidate_users {
         match existing_participations.get(&user.id) {
             Some(existing) if existing.status == "checked_out" || existing.status == "completed" => {
@@ -862,6 +991,7 @@ pub async fn manual_complete_participations(
                 .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
 
                 notify_user_ids.push(user.id);
+                previous_statuses.insert(user.id.to_string(), existing.status.clone().into());
                 results.push(manual_result(
                     user.student_id.clone(),
                     Some(&user),
@@ -903,6 +1033,28 @@ pub async fn manual_complete_participations(
         }
     }
 
+    if !notify_user_ids.is_empty() {
+        credit::recompute(&mut tx, activity_id, Some(&notify_user_ids)).await?;
+    }
+
+    // One entry per batch: prior statuses of the participations that were
+    // overwritten, and every user who ended up completed.
+    if !notify_user_ids.is_empty() {
+        audit::record(
+            &mut *tx,
+            &claims,
+            &headers,
+            AuditEntry::new("participation.manual_complete", "activity", activity_id)
+                .organization(Some(activity.organizer_id))
+                .before(Some(serde_json::json!({ "participation_statuses": previous_statuses })))
+                .after(Some(serde_json::json!({
+                    "completed_user_ids": notify_user_ids,
+                    "notes": note,
+                }))),
+        )
+        .await?;
+    }
+
     tx.commit()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB commit error: {}", e)))?;
@@ -925,7 +1077,7 @@ pub async fn get_my_participations(
     State(pool): State<PgPool>,
     headers: HeaderMap,
 ) -> Result<Json<Vec<serde_json::Value>>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
     let user_id = Uuid::parse_str(&claims.sub)
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
 
@@ -944,12 +1096,13 @@ pub async fn get_my_participations(
         start_date: chrono::NaiveDate,
         end_date: chrono::NaiveDate,
         hours: i16,
+        credited_hours: f64,
         organizer_name: String,
         activity_type: String,
         activity_level: Option<String>,
     }
 
-    let rows = sqlx::query_as::<_, ParticipationRow>(r#"
+    let rows = sqlx::query_as::<_, ParticipationRow>(&format!(r#"
         SELECT
             p.id, p.status::text AS status, p.registered_at, p.checked_in_at, p.checked_out_at, p.notes,
             a.id AS activity_id,
@@ -959,6 +1112,7 @@ pub async fn get_my_participations(
             a.start_date,
             a.end_date,
             a.hours,
+            {credited_hours} AS credited_hours,
             o.name AS organizer_name,
             a.activity_type::text AS activity_type,
             a.activity_level::text AS activity_level
@@ -967,7 +1121,7 @@ pub async fn get_my_participations(
         JOIN organizations o ON a.organizer_id = o.id
         WHERE p.user_id = $1
         ORDER BY p.registered_at DESC
-    "#)
+    "#, credited_hours = credit::EFFECTIVE_HOURS_SQL))
     .bind(user_id)
     .fetch_all(&pool)
     .await
@@ -981,6 +1135,7 @@ pub async fn get_my_participations(
             "checked_in_at": r.checked_in_at,
             "checked_out_at": r.checked_out_at,
             "notes": r.notes,
+            "credited_hours": r.credited_hours,
             "activity": {
                 "id": r.activity_id,
                 "title": r.activity_title,
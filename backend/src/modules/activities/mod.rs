@@ -1,5 +1,11 @@
+pub mod cancellation;
+pub mod credit;
 pub mod handlers;
 pub mod models;
+pub mod no_show;
+pub mod scheduler;
+pub mod sessions;
+pub mod waitlist;
 
 pub use handlers::*;
 pub use models::*;
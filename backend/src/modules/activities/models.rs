@@ -3,6 +3,7 @@ use sqlx::FromRow;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 use crate::models::{ActivityType, ActivityStatus};
+use super::credit::{CreditRounding, HoursMode};
 
 #[derive(Debug, Serialize, FromRow)]
 pub struct ActivityPublic {
@@ -16,8 +17,14 @@ pub struct ActivityPublic {
     pub start_time_only: Option<chrono::NaiveTime>,
     pub end_time_only: Option<chrono::NaiveTime>,
     pub hours: i16,
+    pub hours_mode: String,
+    pub credit_rounding: String,
+    pub credit_rounding_minutes: i32,
+    pub credit_min_attendance_percent: i32,
     pub max_participants: Option<i32>,
     pub registration_open: Option<bool>,
+    /// Sessions needed to complete a multi-session activity; `None` = all.
+    pub required_sessions: Option<i32>,
     pub status: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -65,6 +72,12 @@ pub struct UpdateActivityInput {
     pub eligible_organizations: Option<serde_json::Value>,
     pub activity_type: Option<ActivityType>,
     pub hours: Option<i16>,
+    /// 0 resets to "every session".
+    pub required_sessions: Option<i32>,
+    pub hours_mode: Option<HoursMode>,
+    pub credit_rounding: Option<CreditRounding>,
+    pub credit_rounding_minutes: Option<i32>,
+    pub credit_min_attendance_percent: Option<i32>,
 }
 
 #[derive(Debug, Serialize)]
@@ -128,3 +141,80 @@ pub struct ManualCompleteParticipationResult {
     pub status: String,
     pub message: String,
 }
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WaitlistEntry {
+    pub user_id: Uuid,
+    pub student_id: String,
+    pub first_name: String,
+    pub last_name: String,
+    /// 1-based place in the queue.
+    pub position: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JoinWaitlistResponse {
+    pub message: String,
+    pub position: i64,
+}
+
+/// The whole queue in its new order; every waiting student exactly once.
+#[derive(Debug, Deserialize)]
+pub struct ReorderWaitlistInput {
+    pub user_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelRegistrationInput {
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CancellationEntry {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub student_id: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub reason: Option<String>,
+    pub registered_at: Option<DateTime<Utc>>,
+    pub cancelled_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AttendanceSummary {
+    pub activity_id: Uuid,
+    pub registered_count: i32,
+    pub attended_count: i32,
+    pub no_show_count: i32,
+    pub checkout_missing_count: i32,
+    pub finalized_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ActivitySession {
+    pub id: Uuid,
+    pub activity_id: Uuid,
+    pub title: Option<String>,
+    pub session_date: chrono::NaiveDate,
+    pub start_time: chrono::NaiveTime,
+    pub end_time: chrono::NaiveTime,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ActivitySessionInput {
+    pub title: Option<String>,
+    pub session_date: chrono::NaiveDate,
+    pub start_time: chrono::NaiveTime,
+    pub end_time: chrono::NaiveTime,
+}
+
+/// `hours: null` removes the override and falls back to the computed value.
+#[derive(Debug, Deserialize)]
+pub struct OverrideCreditInput {
+    pub hours: Option<f64>,
+    pub reason: String,
+}
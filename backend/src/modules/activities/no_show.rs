@@ -0,0 +1,184 @@
+//! Closing out attendance when an activity completes.
+//!
+//! [`finalize`] runs in the transaction that moves the activity to
+//! `completed` — the status scheduler or an admin's `update_activity` —
+//! and is idempotent. It marks every still-`registered` participation as
+//! `no_show`, flags check-ins that never checked out, stores the
+//! per-activity summary and drops the now pointless waitlist. Affected
+//! students are told via [`notify`] after commit.
+//!
+//! Organizations may also block repeat no-shows from registering; see
+//! [`registration_blocked_until`].
+
+use axum::{Json, extract::{State, Path}, http::{StatusCode, HeaderMap}};
+use chrono::{DateTime, FixedOffset, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+use crate::modules::auth::get_claims_from_headers;
+use crate::modules::auth::permissions::{require_permission, Permission};
+use crate::modules::notifications::service::{NotificationService, NotificationType};
+use super::handlers::assert_admin_can_manage_activity;
+use super::models::AttendanceSummary;
+
+#[derive(Debug, Default)]
+pub struct Finalized {
+    pub activity_id: Uuid,
+    pub no_show: Vec<Uuid>,
+    pub checkout_missing: Vec<Uuid>,
+}
+
+pub async fn finalize(
+    tx: &mut Transaction<'_, Postgres>,
+    activity_id: Uuid,
+) -> Result<Finalized, sqlx::Error> {
+    let no_show = sqlx::query_scalar::<_, Uuid>(r#"
+        UPDATE participations
+        SET status = 'no_show'::participation_status, no_show_marked_at = NOW()
+        WHERE activity_id = $1 AND status = 'registered'::participation_status
+        RETURNING user_id
+    "#)
+    .bind(activity_id)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let checkout_missing = sqlx::query_scalar::<_, Uuid>(r#"
+        UPDATE participations
+        SET checkout_missing = TRUE
+        WHERE activity_id = $1 AND status = 'checked_in'::participation_status AND NOT checkout_missing
+        RETURNING user_id
+    "#)
+    .bind(activity_id)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    sqlx::query(r#"
+        INSERT INTO activity_attendance_summaries
+            (activity_id, registered_count, attended_count, no_show_count, checkout_missing_count, finalized_at)
+        SELECT $1,
+               COUNT(*),
+               COUNT(*) FILTER (WHERE status IN ('checked_in', 'checked_out', 'completed')),
+               COUNT(*) FILTER (WHERE status = 'no_show'),
+               COUNT(*) FILTER (WHERE checkout_missing),
+               NOW()
+        FROM participations WHERE activity_id = $1
+        ON CONFLICT (activity_id) DO UPDATE SET
+            registered_count = EXCLUDED.registered_count,
+            attended_count = EXCLUDED.attended_count,
+            no_show_count = EXCLUDED.no_show_count,
+            checkout_missing_count = EXCLUDED.checkout_missing_count,
+            finalized_at = EXCLUDED.finalized_at
+    "#)
+    .bind(activity_id)
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query("DELETE FROM activity_waitlist WHERE activity_id = $1")
+        .bind(activity_id)
+        .execute(&mut **tx)
+        .await?;
+
+    Ok(Finalized { activity_id, no_show, checkout_missing })
+}
+
+/// Best effort; failures are logged.
+pub async fn notify(pool: &PgPool, finalized: &Finalized) {
+    if finalized.no_show.is_empty() && finalized.checkout_missing.is_empty() {
+        return;
+    }
+    let title: String = sqlx::query_scalar("SELECT title FROM activities WHERE id = $1")
+        .bind(finalized.activity_id)
+        .fetch_optional(pool)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    let link = format!("/student/activities/{}", finalized.activity_id);
+
+    if let Err(e) = NotificationService::send_bulk(
+        pool,
+        &finalized.no_show,
+        "ไม่ได้เข้าร่วมกิจกรรม",
+        &format!("คุณลงทะเบียนกิจกรรม '{}' แต่ไม่ได้เช็คอิน ระบบบันทึกว่าไม่มาเข้าร่วม", title),
+        NotificationType::Warning,
+        Some(&link),
+    ).await {
+        tracing::error!("Failed to notify no-shows for {}: {}", finalized.activity_id, e);
+    }
+    if let Err(e) = NotificationService::send_bulk(
+        pool,
+        &finalized.checkout_missing,
+        "ยังไม่ได้เช็คเอาท์",
+        &format!("กิจกรรม '{}' สิ้นสุดแล้วแต่คุณยังไม่ได้เช็คเอาท์ กรุณาติดต่อผู้จัดกิจกรรม", title),
+        NotificationType::Warning,
+        Some(&link),
+    ).await {
+        tracing::error!("Failed to notify missing checkouts for {}: {}", finalized.activity_id, e);
+    }
+}
+
+/// End of the student's registration block under their organization's
+/// no-show policy, if one is in force right now.
+pub async fn registration_blocked_until(
+    executor: impl sqlx::PgExecutor<'_>,
+    user_id: Uuid,
+) -> Result<Option<DateTime<Utc>>, (StatusCode, String)> {
+    let until: Option<DateTime<Utc>> = sqlx::query_scalar(r#"
+        SELECT MAX(p.no_show_marked_at) + make_interval(days => o.no_show_block_days)
+        FROM users u
+        JOIN departments d ON d.id = u.department_id
+        JOIN organizations o ON o.id = d.organization_id
+        JOIN participations p ON p.user_id = u.id
+        WHERE u.id = $1
+          AND o.no_show_block_threshold > 0
+          AND p.status = 'no_show'::participation_status
+          AND p.no_show_marked_at >= NOW() - make_interval(days => o.no_show_window_days)
+        GROUP BY o.no_show_block_threshold, o.no_show_block_days
+        HAVING COUNT(*) >= o.no_show_block_threshold
+    "#)
+    .bind(user_id)
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+    .flatten();
+    Ok(until.filter(|t| *t > Utc::now()))
+}
+
+/// 403 while the student is blocked by the no-show policy.
+pub async fn ensure_not_blocked(
+    executor: impl sqlx::PgExecutor<'_>,
+    user_id: Uuid,
+) -> Result<(), (StatusCode, String)> {
+    match registration_blocked_until(executor, user_id).await? {
+        Some(until) => {
+            let bangkok = FixedOffset::east_opt(7 * 3600).expect("valid offset");
+            Err((
+                StatusCode::FORBIDDEN,
+                format!(
+                    "ระงับการลงทะเบียนถึงวันที่ {} เนื่องจากไม่มาเข้าร่วมกิจกรรมหลายครั้ง",
+                    until.with_timezone(&bangkok).format("%d/%m/%Y %H:%M")
+                ),
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+pub async fn get_attendance_summary(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(activity_id): Path<Uuid>,
+) -> Result<Json<AttendanceSummary>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ActivitiesUpdate)?;
+    assert_admin_can_manage_activity(&pool, &claims, activity_id).await?;
+
+    sqlx::query_as::<_, AttendanceSummary>(
+        "SELECT * FROM activity_attendance_summaries WHERE activity_id = $1"
+    )
+    .bind(activity_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map(Json)
+    .ok_or((StatusCode::NOT_FOUND, "Attendance has not been finalized yet".to_string()))
+}
@@ -0,0 +1,192 @@
+//! Background status lifecycle for activities.
+//!
+//! Each tick, in one transaction:
+//! - closes registration `REGISTRATION_CLOSE_MINUTES_BEFORE_START` (default
+//!   0) before the activity starts,
+//! - moves `published` activities to `ongoing` at their start time,
+//! - moves `published` / `ongoing` activities to `completed` once they end
+//!   and closes out their attendance (see `no_show`).
+//!
+//! Start and end are `start_date + start_time_only` and
+//! `end_date + end_time_only` in Asia/Bangkok; a missing start time means
+//! midnight, a missing end time means the end of the day. Draft and
+//! cancelled activities are never touched.
+//!
+//! Rather than polling, the scheduler sleeps until the next of those moments
+//! (at most `MAX_SLEEP`), so Neon can suspend between them. Creating or
+//! editing an activity calls [`wake`] to re-plan.
+//!
+//! Replicas coordinate through a transaction-scoped advisory lock: whoever
+//! gets it runs the tick, the others skip it. Transaction scope keeps this
+//! correct behind Neon's transaction-mode pooler, where session locks
+//! would leak across clients.
+
+use std::time::Duration;
+use sqlx::PgPool;
+use tokio::sync::Notify;
+use uuid::Uuid;
+use super::no_show::{self, Finalized};
+
+/// Safety net for transitions nobody woke us for.
+const MAX_SLEEP: Duration = Duration::from_secs(30 * 60);
+/// Floor between ticks, so a transition another replica is still holding
+/// the lock for doesn't turn into a busy loop.
+const MIN_SLEEP: Duration = Duration::from_secs(5);
+/// Pause before retrying after a failed tick.
+const ERROR_BACKOFF: Duration = Duration::from_secs(60);
+/// Arbitrary, but must stay unique among the app's advisory locks.
+const ADVISORY_LOCK_KEY: i64 = 0x7472_6b5f_6163_7473; // "trk_acts"
+
+const STARTS_AT: &str = "((start_date + COALESCE(start_time_only, TIME '00:00')) AT TIME ZONE 'Asia/Bangkok')";
+const ENDS_AT: &str = "((end_date + COALESCE(end_time_only, TIME '24:00')) AT TIME ZONE 'Asia/Bangkok')";
+
+static WAKE: Notify = Notify::const_new();
+
+/// Re-plan the next tick after an activity's status, times or registration
+/// flag changed.
+pub fn wake() {
+    WAKE.notify_one();
+}
+
+fn close_minutes_before_start() -> i32 {
+    std::env::var("REGISTRATION_CLOSE_MINUTES_BEFORE_START")
+        .ok()
+        .and_then(|v| v.trim().parse::<i32>().ok())
+        .filter(|m| *m >= 0)
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Default)]
+struct TickOutcome {
+    registration_closed: u64,
+    started: u64,
+    completed: Vec<Finalized>,
+}
+
+/// `None` when another replica holds the lock.
+async fn tick(pool: &PgPool) -> Result<Option<TickOutcome>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let locked: bool = sqlx::query_scalar("SELECT pg_try_advisory_xact_lock($1)")
+        .bind(ADVISORY_LOCK_KEY)
+        .fetch_one(&mut *tx)
+        .await?;
+    if !locked {
+        return Ok(None);
+    }
+
+    let registration_closed = sqlx::query(&format!(
+        r#"
+        UPDATE activities SET registration_open = FALSE, updated_at = NOW()
+        WHERE deleted_at IS NULL
+          AND registration_open IS TRUE
+          AND status IN ('published'::activity_status, 'ongoing'::activity_status)
+          AND NOW() >= {} - make_interval(mins => $1)
+        "#,
+        STARTS_AT
+    ))
+    .bind(close_minutes_before_start())
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    let completed_ids = sqlx::query_scalar::<_, Uuid>(&format!(
+        r#"
+        UPDATE activities SET status = 'completed'::activity_status, updated_at = NOW()
+        WHERE deleted_at IS NULL
+          AND status IN ('published'::activity_status, 'ongoing'::activity_status)
+          AND NOW() >= {}
+        RETURNING id
+        "#,
+        ENDS_AT
+    ))
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut completed = Vec::with_capacity(completed_ids.len());
+    for activity_id in completed_ids {
+        completed.push(no_show::finalize(&mut tx, activity_id).await?);
+    }
+
+    let started = sqlx::query(&format!(
+        r#"
+        UPDATE activities SET status = 'ongoing'::activity_status, updated_at = NOW()
+        WHERE deleted_at IS NULL
+          AND status = 'published'::activity_status
+          AND NOW() >= {}
+        "#,
+        STARTS_AT
+    ))
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    tx.commit().await?;
+    Ok(Some(TickOutcome { registration_closed, started, completed }))
+}
+
+/// Time until the earliest pending transition, clamped to
+/// `MIN_SLEEP..=MAX_SLEEP`. Overdue rows (another replica is mid-tick)
+/// count as due now.
+async fn next_tick_in(pool: &PgPool) -> Result<Duration, sqlx::Error> {
+    let secs: Option<f64> = sqlx::query_scalar(&format!(
+        r#"
+        SELECT EXTRACT(EPOCH FROM MIN(due) - NOW())::float8
+        FROM (
+            SELECT {starts} - make_interval(mins => $1) AS due FROM activities
+            WHERE deleted_at IS NULL AND registration_open IS TRUE
+              AND status IN ('published'::activity_status, 'ongoing'::activity_status)
+            UNION ALL
+            SELECT {starts} FROM activities
+            WHERE deleted_at IS NULL AND status = 'published'::activity_status
+            UNION ALL
+            SELECT {ends} FROM activities
+            WHERE deleted_at IS NULL
+              AND status IN ('published'::activity_status, 'ongoing'::activity_status)
+        ) upcoming
+        "#,
+        starts = STARTS_AT,
+        ends = ENDS_AT,
+    ))
+    .bind(close_minutes_before_start())
+    .fetch_one(pool)
+    .await?;
+    Ok(secs.map_or(MAX_SLEEP, |s| Duration::from_secs_f64(s.max(0.0)).clamp(MIN_SLEEP, MAX_SLEEP)))
+}
+
+pub async fn run(pool: PgPool) {
+    tracing::info!("Activity status scheduler started");
+    loop {
+        let result = tick(&pool).await;
+        let failed = result.is_err();
+        match result {
+            Ok(Some(outcome)) => {
+                if outcome.registration_closed + outcome.started > 0 || !outcome.completed.is_empty() {
+                    tracing::info!(
+                        "Activity scheduler: {} registration closed, {} started, {} completed",
+                        outcome.registration_closed,
+                        outcome.started,
+                        outcome.completed.len()
+                    );
+                }
+                for finalized in &outcome.completed {
+                    no_show::notify(&pool, finalized).await;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => tracing::error!("Activity scheduler tick failed: {}", e),
+        }
+        // A failed tick would otherwise retry at the next (likely overdue)
+        // deadline immediately, hammering a database that is already failing.
+        let sleep_for = if failed {
+            ERROR_BACKOFF
+        } else {
+            next_tick_in(&pool).await.unwrap_or_else(|e| {
+                tracing::error!("Activity scheduler failed to plan the next tick: {}", e);
+                ERROR_BACKOFF
+            })
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(sleep_for) => {}
+            _ = WAKE.notified() => {}
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Sessions of multi-day activities (date + time window). Scanning during a
+//! session records attendance for it; see `qr::handlers::scan_session`.
+
+use axum::{Json, extract::{State, Path}, http::{StatusCode, HeaderMap}};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::modules::audit::service::{self as audit, AuditEntry};
+use crate::modules::auth::get_claims_from_headers;
+use crate::modules::auth::permissions::{require_permission, Permission};
+use super::handlers::assert_admin_can_manage_activity;
+use super::models::{ActivitySession, ActivitySessionInput};
+
+const SESSION_SELECT: &str = r#"
+    SELECT id, activity_id, title, session_date, start_time, end_time, created_at, updated_at
+    FROM activity_sessions
+"#;
+
+/// The session must sit inside the activity's date range.
+async fn validate(
+    pool: &PgPool,
+    activity_id: Uuid,
+    input: &ActivitySessionInput,
+) -> Result<(), (StatusCode, String)> {
+    if input.end_time <= input.start_time {
+        return Err((StatusCode::BAD_REQUEST, "เวลาสิ้นสุดต้องอยู่หลังเวลาเริ่ม".to_string()));
+    }
+    let (start_date, end_date): (chrono::NaiveDate, chrono::NaiveDate) = sqlx::query_as(
+        "SELECT start_date, end_date FROM activities WHERE id = $1 AND deleted_at IS NULL"
+    )
+    .bind(activity_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::NOT_FOUND, "Activity not found".to_string()))?;
+    if input.session_date < start_date || input.session_date > end_date {
+        return Err((StatusCode::BAD_REQUEST, "วันที่ของรอบต้องอยู่ในช่วงวันจัดกิจกรรม".to_string()));
+    }
+    Ok(())
+}
+
+fn map_write_error(e: sqlx::Error) -> (StatusCode, String) {
+    match e {
+        sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+            (StatusCode::CONFLICT, "มีรอบที่เริ่มวันและเวลาเดียวกันอยู่แล้ว".to_string())
+        }
+        e => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to save session: {}", e)),
+    }
+}
+
+/// Open to any signed-in user so students can see the schedule.
+pub async fn list_sessions(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(activity_id): Path<Uuid>,
+) -> Result<Json<Vec<ActivitySession>>, (StatusCode, String)> {
+    get_claims_from_headers(&pool, &headers).await?;
+
+    let sessions = sqlx::query_as::<_, ActivitySession>(&format!(
+        "{} WHERE activity_id = $1 ORDER BY session_date, start_time",
+        SESSION_SELECT
+    ))
+    .bind(activity_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(sessions))
+}
+
+pub async fn create_session(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(activity_id): Path<Uuid>,
+    Json(payload): Json<ActivitySessionInput>,
+) -> Result<Json<ActivitySession>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ActivitiesUpdate)?;
+    assert_admin_can_manage_activity(&pool, &claims, activity_id).await?;
+    validate(&pool, activity_id, &payload).await?;
+
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let session_id: Uuid = sqlx::query_scalar(r#"
+        INSERT INTO activity_sessions (activity_id, title, session_date, start_time, end_time)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+    "#)
+    .bind(activity_id)
+    .bind(payload.title.as_deref().map(str::trim).filter(|t| !t.is_empty()))
+    .bind(payload.session_date)
+    .bind(payload.start_time)
+    .bind(payload.end_time)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(map_write_error)?;
+
+    let after = audit::snapshot(&mut *tx, "activity_sessions", session_id).await?;
+    let organizer_id = organizer_of(&mut *tx, activity_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("activity.session_create", "activity_session", session_id)
+            .organization(organizer_id)
+            .after(after),
+    )
+    .await?;
+
+    let session = sqlx::query_as::<_, ActivitySession>(&format!("{} WHERE id = $1", SESSION_SELECT))
+        .bind(session_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
+    Ok(Json(session))
+}
+
+pub async fn update_session(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path((activity_id, session_id)): Path<(Uuid, Uuid)>,
+    Json(payload): Json<ActivitySessionInput>,
+) -> Result<Json<ActivitySession>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ActivitiesUpdate)?;
+    assert_admin_can_manage_activity(&pool, &claims, activity_id).await?;
+    validate(&pool, activity_id, &payload).await?;
+
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "activity_sessions", session_id).await?;
+
+    let updated = sqlx::query(r#"
+        UPDATE activity_sessions
+        SET title = $3, session_date = $4, start_time = $5, end_time = $6, updated_at = NOW()
+        WHERE id = $1 AND activity_id = $2
+    "#)
+    .bind(session_id)
+    .bind(activity_id)
+    .bind(payload.title.as_deref().map(str::trim).filter(|t| !t.is_empty()))
+    .bind(payload.session_date)
+    .bind(payload.start_time)
+    .bind(payload.end_time)
+    .execute(&mut *tx)
+    .await
+    .map_err(map_write_error)?
+    .rows_affected();
+    if updated == 0 {
+        return Err((StatusCode::NOT_FOUND, "Session not found".to_string()));
+    }
+
+    let after = audit::snapshot(&mut *tx, "activity_sessions", session_id).await?;
+    let organizer_id = organizer_of(&mut *tx, activity_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("activity.session_update", "activity_session", session_id)
+            .organization(organizer_id)
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
+    let session = sqlx::query_as::<_, ActivitySession>(&format!("{} WHERE id = $1", SESSION_SELECT))
+        .bind(session_id)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
+    Ok(Json(session))
+}
+
+/// Deleting a session also deletes its attendance records.
+pub async fn delete_session(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path((activity_id, session_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ActivitiesUpdate)?;
+    assert_admin_can_manage_activity(&pool, &claims, activity_id).await?;
+
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "activity_sessions", session_id).await?;
+
+    let deleted = sqlx::query("DELETE FROM activity_sessions WHERE id = $1 AND activity_id = $2")
+        .bind(session_id)
+        .bind(activity_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete session: {}", e)))?
+        .rows_affected();
+    if deleted == 0 {
+        return Err((StatusCode::NOT_FOUND, "Session not found".to_string()));
+    }
+    let below_required: bool = sqlx::query_scalar(
+        r#"
+        SELECT COALESCE(a.required_sessions, 0) > (SELECT COUNT(*) FROM activity_sessions s WHERE s.activity_id = a.id)
+        FROM activities a WHERE a.id = $1
+        "#,
+    )
+    .bind(activity_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if below_required {
+        return Err((
+            StatusCode::CONFLICT,
+            "Lower required_sessions before deleting this session".to_string(),
+        ));
+    }
+
+    let organizer_id = organizer_of(&mut *tx, activity_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("activity.session_delete", "activity_session", session_id)
+            .organization(organizer_id)
+            .before(before),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
+    Ok(Json(serde_json::json!({ "message": "Session deleted successfully" })))
+}
+
+async fn organizer_of(
+    executor: impl sqlx::PgExecutor<'_>,
+    activity_id: Uuid,
+) -> Result<Option<Uuid>, (StatusCode, String)> {
+    sqlx::query_scalar("SELECT organizer_id FROM activities WHERE id = $1")
+        .bind(activity_id)
+        .fetch_optional(executor)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
@@ -0,0 +1,355 @@
+//! Waitlist for full activities.
+//!
+//! Students queue with `POST /activities/{id}/waitlist` once `join_activity`
+//! answers "full". Whenever seats free up — a cancelled registration or an
+//! admin raising `max_participants` — the caller runs [`promote`] inside
+//! its own transaction so the seat and the queue change together, then
+//! [`notify_promoted`] after commit.
+
+use std::collections::HashSet;
+use axum::{Json, extract::{State, Path}, http::{StatusCode, HeaderMap}};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+use crate::modules::audit::service::{self as audit, AuditEntry};
+use crate::modules::auth::get_claims_from_headers;
+use crate::modules::auth::impersonation::forbid_while_impersonating;
+use crate::modules::auth::permissions::{require_permission, Permission};
+use crate::modules::notifications::service::{NotificationService, NotificationType};
+use super::handlers::assert_admin_can_manage_activity;
+use super::no_show;
+use super::models::{JoinWaitlistResponse, ReorderWaitlistInput, WaitlistEntry};
+
+#[derive(sqlx::FromRow)]
+struct CapacityRow {
+    status: String,
+    registration_open: bool,
+    max_participants: Option<i32>,
+    end_date: chrono::NaiveDate,
+    taken: i64,
+}
+
+/// Lock the activity row and count the seats taken. Callers that change
+/// capacity or the queue go through here so they serialize with joins.
+async fn lock_capacity(
+    tx: &mut Transaction<'_, Postgres>,
+    activity_id: Uuid,
+) -> Result<Option<CapacityRow>, (StatusCode, String)> {
+    sqlx::query_as::<_, CapacityRow>(r#"
+        SELECT a.status::text AS status, a.registration_open, a.max_participants, a.end_date,
+               (SELECT COUNT(*) FROM participations p
+                WHERE p.activity_id = a.id AND p.status::text != 'no_show') AS taken
+        FROM activities a
+        WHERE a.id = $1 AND a.deleted_at IS NULL
+        FOR UPDATE OF a
+    "#)
+    .bind(activity_id)
+    .fetch_optional(&mut **tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))
+}
+
+/// Move waiting students into free seats, lowest position first. Does
+/// nothing unless the activity is still published and hasn't ended.
+/// Students under a no-show registration block keep their place in the
+/// queue and are passed over; entries for students who already hold a
+/// seat are dropped. Returns only the user IDs actually registered, for
+/// [`notify_promoted`].
+pub async fn promote(
+    tx: &mut Transaction<'_, Postgres>,
+    activity_id: Uuid,
+) -> Result<Vec<Uuid>, (StatusCode, String)> {
+    let Some(activity) = lock_capacity(tx, activity_id).await? else {
+        return Ok(Vec::new());
+    };
+    if activity.status != "published" || activity.end_date < chrono::Utc::now().date_naive() {
+        return Ok(Vec::new());
+    }
+    let free: Option<i64> = activity.max_participants.map(|max| (max as i64 - activity.taken).max(0));
+    if free == Some(0) {
+        return Ok(Vec::new());
+    }
+
+    sqlx::query(r#"
+        DELETE FROM activity_waitlist w
+        WHERE w.activity_id = $1
+          AND EXISTS (SELECT 1 FROM participations p WHERE p.activity_id = w.activity_id AND p.user_id = w.user_id)
+    "#)
+    .bind(activity_id)
+    .execute(&mut **tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to promote waitlist: {}", e)))?;
+
+    let queue = sqlx::query_scalar::<_, Uuid>(
+        "SELECT user_id FROM activity_waitlist WHERE activity_id = $1 ORDER BY position, created_at"
+    )
+    .bind(activity_id)
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to promote waitlist: {}", e)))?;
+
+    let mut candidates = Vec::new();
+    for user_id in queue {
+        if free.is_some_and(|free| candidates.len() as i64 >= free) {
+            break;
+        }
+        if no_show::registration_blocked_until(&mut **tx, user_id).await?.is_none() {
+            candidates.push(user_id);
+        }
+    }
+    if candidates.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let promoted = sqlx::query_scalar::<_, Uuid>(r#"
+        INSERT INTO participations (user_id, activity_id, status, registered_at)
+        SELECT u, $1, 'registered'::participation_status, NOW()
+        FROM UNNEST($2::uuid[]) AS u
+        ON CONFLICT (user_id, activity_id) DO NOTHING
+        RETURNING user_id
+    "#)
+    .bind(activity_id)
+    .bind(&candidates)
+    .fetch_all(&mut **tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to promote waitlist: {}", e)))?;
+
+    sqlx::query("DELETE FROM activity_waitlist WHERE activity_id = $1 AND user_id = ANY($2)")
+        .bind(activity_id)
+        .bind(&promoted)
+        .execute(&mut **tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to promote waitlist: {}", e)))?;
+    Ok(promoted)
+}
+
+/// Tell promoted students they now have a seat. Best effort: a failed
+/// notification is logged, the registration stands.
+pub async fn notify_promoted(pool: &PgPool, activity_id: Uuid, user_ids: &[Uuid]) {
+    if user_ids.is_empty() {
+        return;
+    }
+    let title: String = sqlx::query_scalar("SELECT title FROM activities WHERE id = $1")
+        .bind(activity_id)
+        .fetch_optional(pool)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    let link = format!("/student/activities/{}", activity_id);
+    for user_id in user_ids {
+        if let Err(e) = NotificationService::send(
+            pool,
+            *user_id,
+            "ได้รับที่นั่งจากรายชื่อสำรอง",
+            &format!("คุณได้รับการลงทะเบียนกิจกรรม '{}' จากรายชื่อสำรองแล้ว", title),
+            NotificationType::Success,
+            Some(&link),
+        ).await {
+            tracing::error!("Failed to notify waitlist promotion for {}: {}", user_id, e);
+        }
+    }
+}
+
+pub async fn join_waitlist(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(activity_id): Path<Uuid>,
+) -> Result<Json<JoinWaitlistResponse>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    forbid_while_impersonating(&claims)?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
+
+    let mut tx = pool.begin().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB tx error: {}", e)))?;
+    let activity = lock_capacity(&mut tx, activity_id)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "Activity not found".to_string()))?;
+
+    if activity.status != "published" {
+        return Err((StatusCode::CONFLICT, "ไม่สามารถลงทะเบียนได้ (กิจกรรมยังไม่เผยแพร่หรือถูกยกเลิก)".to_string()));
+    }
+    if !activity.registration_open {
+        return Err((StatusCode::CONFLICT, "การลงทะเบียนปิดแล้ว".to_string()));
+    }
+    if activity.end_date < chrono::Utc::now().date_naive() {
+        return Err((StatusCode::CONFLICT, "กิจกรรมสิ้นสุดแล้ว ไม่สามารถลงทะเบียนได้".to_string()));
+    }
+    no_show::ensure_not_blocked(&mut *tx, user_id).await?;
+    if activity.max_participants.is_none_or(|max| activity.taken < max as i64) {
+        return Err((StatusCode::CONFLICT, "กิจกรรมยังมีที่ว่าง กรุณาลงทะเบียนโดยตรง".to_string()));
+    }
+
+    let already_registered: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM participations WHERE activity_id = $1 AND user_id = $2)"
+    )
+    .bind(activity_id)
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?;
+    if already_registered {
+        return Err((StatusCode::CONFLICT, "ลงทะเบียนกิจกรรมนี้แล้ว".to_string()));
+    }
+
+    let result = sqlx::query(r#"
+        INSERT INTO activity_waitlist (activity_id, user_id, position)
+        SELECT $1, $2, COALESCE(MAX(position), 0) + 1
+        FROM activity_waitlist WHERE activity_id = $1
+    "#)
+    .bind(activity_id)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await;
+    match result {
+        Ok(_) => {}
+        Err(sqlx::Error::Database(db_err)) if db_err.is_unique_violation() => {
+            return Err((StatusCode::CONFLICT, "อยู่ในรายชื่อสำรองของกิจกรรมนี้แล้ว".to_string()));
+        }
+        Err(e) => {
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to join waitlist: {}", e)));
+        }
+    }
+
+    let position = queue_position(&mut tx, activity_id, user_id).await?;
+    tx.commit().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB commit error: {}", e)))?;
+
+    Ok(Json(JoinWaitlistResponse {
+        message: "Added to the waitlist".to_string(),
+        position,
+    }))
+}
+
+async fn queue_position(
+    tx: &mut Transaction<'_, Postgres>,
+    activity_id: Uuid,
+    user_id: Uuid,
+) -> Result<i64, (StatusCode, String)> {
+    sqlx::query_scalar(r#"
+        SELECT COUNT(*) FROM activity_waitlist w
+        JOIN activity_waitlist me ON me.activity_id = w.activity_id AND me.user_id = $2
+        WHERE w.activity_id = $1 AND (w.position, w.created_at) <= (me.position, me.created_at)
+    "#)
+    .bind(activity_id)
+    .bind(user_id)
+    .fetch_one(&mut **tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))
+}
+
+pub async fn leave_waitlist(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(activity_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    forbid_while_impersonating(&claims)?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
+
+    let removed = sqlx::query("DELETE FROM activity_waitlist WHERE activity_id = $1 AND user_id = $2")
+        .bind(activity_id)
+        .bind(user_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))?
+        .rows_affected();
+    if removed == 0 {
+        return Err((StatusCode::NOT_FOUND, "ไม่ได้อยู่ในรายชื่อสำรองของกิจกรรมนี้".to_string()));
+    }
+    Ok(Json(serde_json::json!({ "message": "Removed from the waitlist" })))
+}
+
+async fn fetch_queue(
+    executor: impl sqlx::PgExecutor<'_>,
+    activity_id: Uuid,
+) -> Result<Vec<WaitlistEntry>, (StatusCode, String)> {
+    sqlx::query_as::<_, WaitlistEntry>(r#"
+        SELECT u.id AS user_id, u.student_id, u.first_name, u.last_name,
+               ROW_NUMBER() OVER (ORDER BY w.position, w.created_at) AS position,
+               w.created_at
+        FROM activity_waitlist w
+        JOIN users u ON u.id = w.user_id
+        WHERE w.activity_id = $1
+        ORDER BY w.position, w.created_at
+    "#)
+    .bind(activity_id)
+    .fetch_all(executor)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("DB error: {}", e)))
+}
+
+pub async fn list_waitlist(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(activity_id): Path<Uuid>,
+) -> Result<Json<Vec<WaitlistEntry>>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ActivitiesUpdate)?;
+    assert_admin_can_manage_activity(&pool, &claims, activity_id).await?;
+
+    Ok(Json(fetch_queue(&pool, activity_id).await?))
+}
+
+pub async fn reorder_waitlist(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(activity_id): Path<Uuid>,
+    Json(payload): Json<ReorderWaitlistInput>,
+) -> Result<Json<Vec<WaitlistEntry>>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ActivitiesUpdate)?;
+    assert_admin_can_manage_activity(&pool, &claims, activity_id).await?;
+
+    let mut tx = pool.begin().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    lock_capacity(&mut tx, activity_id)
+        .await?
+        .ok_or((StatusCode::NOT_FOUND, "Activity not found".to_string()))?;
+
+    let before = fetch_queue(&mut *tx, activity_id).await?;
+    let current: HashSet<Uuid> = before.iter().map(|e| e.user_id).collect();
+    let requested: HashSet<Uuid> = payload.user_ids.iter().copied().collect();
+    if requested.len() != payload.user_ids.len() || requested != current {
+        return Err((
+            StatusCode::CONFLICT,
+            "รายชื่อสำรองมีการเปลี่ยนแปลง กรุณาโหลดใหม่แล้วลองอีกครั้ง".to_string(),
+        ));
+    }
+
+    sqlx::query(r#"
+        UPDATE activity_waitlist w
+        SET position = o.ordinality
+        FROM UNNEST($2::uuid[]) WITH ORDINALITY AS o(user_id, ordinality)
+        WHERE w.activity_id = $1 AND w.user_id = o.user_id
+    "#)
+    .bind(activity_id)
+    .bind(&payload.user_ids)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to reorder waitlist: {}", e)))?;
+
+    let organizer_id: Option<Uuid> = sqlx::query_scalar("SELECT organizer_id FROM activities WHERE id = $1")
+        .bind(activity_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let order = |entries: &[WaitlistEntry]| -> Vec<Uuid> { entries.iter().map(|e| e.user_id).collect() };
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("activity.waitlist_reorder", "activity", activity_id)
+            .organization(organizer_id)
+            .before(Some(serde_json::json!({ "order": order(&before) })))
+            .after(Some(serde_json::json!({ "order": payload.user_ids }))),
+    )
+    .await?;
+
+    let after = fetch_queue(&mut *tx, activity_id).await?;
+    tx.commit().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
+    Ok(Json(after))
+}
@@ -5,16 +5,38 @@ use chrono::Utc;
 use argon2::{Argon2, PasswordHasher, password_hash::{rand_core::OsRng, SaltString}};
 use axum::http::HeaderMap;
 
-use crate::modules::auth::get_claims_from_headers;
+use crate::modules::audit::service::{self as audit, AuditEntry};
+use crate::modules::auth::{get_claims_from_headers, password_policy};
+use crate::modules::auth::permissions::{self, require_permission, Permission, PermissionInfo};
 use crate::models::AdminLevel;
 use super::models::*;
 use rand::Rng;
 
+/// Audit snapshot of an admin: the `admin_roles` row with the account it
+/// belongs to nested under `user`.
+async fn admin_snapshot(
+    executor: impl sqlx::PgExecutor<'_>,
+    admin_id: Uuid,
+) -> Result<Option<serde_json::Value>, (StatusCode, String)> {
+    sqlx::query_scalar::<_, serde_json::Value>(
+        r#"
+        SELECT to_jsonb(ar) || jsonb_build_object('user', to_jsonb(u) - 'password_hash')
+        FROM admin_roles ar
+        JOIN users u ON u.id = ar.user_id
+        WHERE ar.id = $1
+        "#,
+    )
+    .bind(admin_id)
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to snapshot admin: {}", e)))
+}
+
 pub async fn list_admins(
     State(pool): State<PgPool>,
     headers: HeaderMap,
 ) -> Result<Json<AdminsListResponse>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
     if !matches!(claims.admin_level, Some(AdminLevel::SuperAdmin)) {
         return Err((StatusCode::FORBIDDEN, "Super admin access required".to_string()));
     }
@@ -38,6 +60,7 @@ pub async fn list_admins(
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to fetch admins: {}", e)))?;
 
     let admins = rows.into_iter().map(|r| {
+        let permission_count = r.permissions.as_ref().map_or(0, |p| p.len());
         AdminResponseItem {
             id: r.id,
             user_id: r.user_id,
@@ -72,7 +95,7 @@ pub async fn list_admins(
             }),
             full_name: Some(format!("{} {}", r.first_name, r.last_name)),
             created_at_formatted: Some(r.created_at.to_rfc3339()),
-            permission_count: Some(0),
+            permission_count: Some(permission_count),
             days_since_last_login: None,
             assigned_departments: vec![],
             department_count: Some(0),
@@ -86,7 +109,8 @@ pub async fn list_organization_admins(
     State(pool): State<PgPool>,
     headers: HeaderMap,
 ) -> Result<Json<Vec<AdminResponseItem>>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::AdminsView)?;
     if !matches!(
         claims.admin_level,
         Some(AdminLevel::SuperAdmin) | Some(AdminLevel::OrganizationAdmin)
@@ -114,6 +138,7 @@ pub async fn list_organization_admins(
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to fetch org admins: {}", e)))?;
 
     let admins = rows.into_iter().map(|r| {
+        let permission_count = r.permissions.as_ref().map_or(0, |p| p.len());
         AdminResponseItem {
             id: r.id,
             user_id: r.user_id,
@@ -148,7 +173,7 @@ pub async fn list_organization_admins(
             }),
             full_name: Some(format!("{} {}", r.first_name, r.last_name)),
             created_at_formatted: Some(r.created_at.to_rfc3339()),
-            permission_count: Some(0),
+            permission_count: Some(permission_count),
             days_since_last_login: None,
             assigned_departments: vec![],
             department_count: Some(0),
@@ -163,7 +188,7 @@ pub async fn create_admin(
     headers: HeaderMap,
     Json(payload): Json<CreateAdminInput>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
     if !matches!(claims.admin_level, Some(AdminLevel::SuperAdmin)) {
         return Err((StatusCode::FORBIDDEN, "Super admin access required".to_string()));
     }
@@ -172,12 +197,7 @@ pub async fn create_admin(
         StatusCode::BAD_REQUEST,
         "Password is required".to_string(),
     ))?;
-    if pw.len() < 8 {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "Password must be at least 8 characters".to_string(),
-        ));
-    }
+    password_policy::enforce(&pw, &[&payload.email]).await?;
     let salt = SaltString::generate(&mut OsRng);
     let password_hash = Argon2::default()
         .hash_password(pw.as_bytes(), &salt)
@@ -186,7 +206,9 @@ pub async fn create_admin(
 
     let student_id = format!("A{}", rand::random::<u32>() % 900000000 + 100000000);
     let org_id: Option<Uuid> = payload.organization_id.and_then(|id| Uuid::parse_str(&id).ok());
-    let permissions = payload.permissions.unwrap_or_else(|| vec!["ViewDashboard".to_string()]);
+    let admin_level: AdminLevel = serde_json::from_value(serde_json::json!(payload.admin_level))
+        .map_err(|_| (StatusCode::BAD_REQUEST, format!("Invalid admin level: {}", payload.admin_level)))?;
+    let permissions = permissions::resolve_for_level(&admin_level, payload.permissions)?;
 
     // Wrap user + admin_role inserts in a transaction so a failure on the
     // second statement doesn't leave an orphan user account behind.
@@ -195,8 +217,8 @@ pub async fn create_admin(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     let row = sqlx::query(
-        "INSERT INTO users (student_id, email, password_hash, prefix, first_name, last_name, status)
-         VALUES ($1, $2, $3, $4, $5, $6, 'active') RETURNING id"
+        "INSERT INTO users (student_id, email, password_hash, prefix, first_name, last_name, status, email_verified_at)
+         VALUES ($1, $2, $3, $4, $5, $6, 'active', NOW()) RETURNING id"
     )
     .bind(&student_id)
     .bind(&payload.email)
@@ -211,18 +233,29 @@ pub async fn create_admin(
     use sqlx::Row;
     let user_id: Uuid = row.try_get("id").map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    sqlx::query(
+    let admin_id: Uuid = sqlx::query_scalar(
         "INSERT INTO admin_roles (user_id, admin_level, organization_id, permissions, is_enabled)
-         VALUES ($1, $2::admin_level, $3, $4, true)"
+         VALUES ($1, $2::admin_level, $3, $4, true) RETURNING id"
     )
     .bind(&user_id)
     .bind(&payload.admin_level)
     .bind(&org_id)
     .bind(&permissions)
-    .execute(&mut *tx)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    let after = admin_snapshot(&mut *tx, admin_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("admin.create", "admin", admin_id)
+            .organization(org_id)
+            .after(after),
+    )
+    .await?;
+
     tx.commit()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
@@ -236,7 +269,7 @@ pub async fn update_admin(
     Path(admin_id): Path<Uuid>,
     Json(payload): Json<UpdateAdminInput>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
     if !matches!(claims.admin_level, Some(AdminLevel::SuperAdmin)) {
         return Err((StatusCode::FORBIDDEN, "Super admin access required".to_string()));
     }
@@ -260,6 +293,7 @@ pub async fn update_admin(
     let mut tx = pool.begin()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = admin_snapshot(&mut *tx, admin_id).await?;
 
     sqlx::query(
         "UPDATE users SET first_name = $1, last_name = $2, email = $3, prefix = COALESCE($4, prefix), department_id = $5, updated_at = NOW() WHERE id = $6"
@@ -283,6 +317,33 @@ pub async fn update_admin(
             .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     }
 
+    if let Some(requested) = payload.permissions {
+        let level = sqlx::query_scalar::<_, AdminLevel>("SELECT admin_level FROM admin_roles WHERE id = $1")
+            .bind(admin_id)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        let permissions = permissions::resolve_for_level(&level, Some(requested))?;
+        sqlx::query("UPDATE admin_roles SET permissions = $1, updated_at = NOW() WHERE id = $2")
+            .bind(&permissions)
+            .bind(admin_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    let after = admin_snapshot(&mut *tx, admin_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("admin.update", "admin", admin_id)
+            .organization(audit::uuid_field(&after, "organization_id"))
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
     tx.commit()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
@@ -295,7 +356,7 @@ pub async fn delete_admin(
     headers: HeaderMap,
     Path(admin_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
     if !matches!(claims.admin_level, Some(AdminLevel::SuperAdmin)) {
         return Err((StatusCode::FORBIDDEN, "Super admin access required".to_string()));
     }
@@ -312,10 +373,29 @@ pub async fn delete_admin(
         .try_get("user_id")
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = admin_snapshot(&mut *tx, admin_id).await?;
+
     // Actually, deleting users will cascade delete admin_roles, assuming FK CASCADE
     sqlx::query("DELETE FROM users WHERE id = $1")
         .bind(user_id)
-        .execute(&pool)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("admin.delete", "admin", admin_id)
+            .organization(audit::uuid_field(&before, "organization_id"))
+            .before(before),
+    )
+    .await?;
+
+    tx.commit()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
@@ -328,15 +408,36 @@ pub async fn toggle_admin_status(
     Path(admin_id): Path<Uuid>,
     Json(payload): Json<ToggleStatusInput>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
     if !matches!(claims.admin_level, Some(AdminLevel::SuperAdmin)) {
         return Err((StatusCode::FORBIDDEN, "Super admin access required".to_string()));
     }
 
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "admin_roles", admin_id).await?;
+
     sqlx::query("UPDATE admin_roles SET is_enabled = $1, updated_at = NOW() WHERE id = $2")
         .bind(payload.is_active)
         .bind(admin_id)
-        .execute(&pool)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let after = audit::snapshot(&mut *tx, "admin_roles", admin_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("admin.toggle_status", "admin", admin_id)
+            .organization(audit::uuid_field(&after, "organization_id"))
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
+    tx.commit()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
@@ -356,10 +457,8 @@ pub async fn get_dashboard_stats(
     State(pool): State<PgPool>,
     headers: HeaderMap,
 ) -> Result<Json<DashboardStats>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
-    }
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::DashboardView)?;
 
     let scope_org_id: Option<Uuid> = match claims.admin_level {
         Some(AdminLevel::SuperAdmin) => None,
@@ -462,3 +561,13 @@ pub async fn get_dashboard_stats(
         scope,
     }))
 }
+
+/// Permission catalogue for the admin UI's checkboxes.
+pub async fn list_permissions(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<PermissionInfo>>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::AdminsView)?;
+    Ok(Json(permissions::catalogue()))
+}
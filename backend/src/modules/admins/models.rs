@@ -107,6 +107,8 @@ pub struct UpdateAdminInput {
     pub prefix: Option<String>,
     pub department_id: Option<String>,
     pub organization_id: Option<String>,
+    /// Replaces the admin's permission list when present.
+    pub permissions: Option<Vec<String>>,
 }
 
 #[derive(Debug, Deserialize)]
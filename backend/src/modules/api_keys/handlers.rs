@@ -0,0 +1,206 @@
+use axum::{Json, extract::{Path, State}, http::{StatusCode, HeaderMap}};
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::models::AdminLevel;
+use crate::modules::audit::service::{self as audit, AuditEntry};
+use crate::modules::auth::get_claims_from_headers;
+use crate::modules::auth::permissions::Permission;
+use super::models::{ApiKeyItem, CreateApiKeyInput, CreatedApiKey};
+use super::service::{self, forbid_api_key};
+
+const DEFAULT_EXPIRY_DAYS: i64 = 90;
+const MAX_EXPIRY_DAYS: i64 = 365;
+
+const API_KEY_COLUMNS: &str = r#"
+    k.id, k.user_id, u.first_name || ' ' || u.last_name AS owner_name,
+    k.organization_id, o.name AS organization_name,
+    k.name, k.prefix, k.permissions, k.expires_at, k.last_used_at,
+    host(k.last_used_ip) AS last_used_ip, k.revoked_at, k.created_at
+"#;
+
+/// The caller's own keys; super admins see every key.
+pub async fn list_api_keys(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ApiKeyItem>>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    forbid_api_key(&claims)?;
+    if !claims.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
+    }
+    let owner: Option<Uuid> = match claims.admin_level {
+        Some(AdminLevel::SuperAdmin) => None,
+        _ => Some(Uuid::parse_str(&claims.sub).map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?),
+    };
+
+    let keys = sqlx::query_as::<_, ApiKeyItem>(&format!(
+        r#"
+        SELECT {}
+        FROM api_keys k
+        JOIN users u ON u.id = k.user_id
+        LEFT JOIN organizations o ON o.id = k.organization_id
+        WHERE ($1::uuid IS NULL OR k.user_id = $1)
+        ORDER BY k.revoked_at IS NOT NULL, k.created_at DESC
+        "#,
+        API_KEY_COLUMNS
+    ))
+    .bind(owner)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to fetch API keys: {}", e)))?;
+
+    Ok(Json(keys))
+}
+
+/// Issue a key for the caller. The secret is in the response only.
+pub async fn create_api_key(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateApiKeyInput>,
+) -> Result<Json<CreatedApiKey>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    forbid_api_key(&claims)?;
+    let level = match (&claims.admin_level, claims.is_admin) {
+        (Some(level), true) => level.clone(),
+        _ => return Err((StatusCode::FORBIDDEN, "Admin access required".to_string())),
+    };
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
+
+    let name = payload.name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err((StatusCode::BAD_REQUEST, "Name must be 1-100 characters".to_string()));
+    }
+
+    let organization_id = match level {
+        AdminLevel::SuperAdmin => payload.organization_id.ok_or((
+            StatusCode::BAD_REQUEST,
+            "organization_id is required for super admin keys".to_string(),
+        ))?,
+        _ => claims.organization_id.ok_or((
+            StatusCode::FORBIDDEN,
+            "Admin is not assigned to any organization".to_string(),
+        ))?,
+    };
+
+    if payload.permissions.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Choose at least one permission".to_string()));
+    }
+    let grantable = service::grantable(&level, &claims.permissions);
+    let mut permissions: Vec<String> = Vec::with_capacity(payload.permissions.len());
+    for key in payload.permissions {
+        if Permission::from_key(&key).is_none() {
+            return Err((StatusCode::BAD_REQUEST, format!("Unknown permission: {}", key)));
+        }
+        if !grantable.contains(&key.as_str()) {
+            return Err((StatusCode::FORBIDDEN, format!("Cannot delegate permission you do not hold: {}", key)));
+        }
+        if !permissions.contains(&key) {
+            permissions.push(key);
+        }
+    }
+
+    let days = payload.expires_in_days.unwrap_or(DEFAULT_EXPIRY_DAYS);
+    if !(1..=MAX_EXPIRY_DAYS).contains(&days) {
+        return Err((StatusCode::BAD_REQUEST, format!("expires_in_days must be 1-{}", MAX_EXPIRY_DAYS)));
+    }
+    let expires_at = Utc::now() + Duration::days(days);
+    let (key, prefix, hash) = service::generate();
+
+    let mut tx = pool.begin().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO api_keys (user_id, organization_id, name, prefix, key_hash, permissions, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(organization_id)
+    .bind(name)
+    .bind(&prefix)
+    .bind(&hash)
+    .bind(&permissions)
+    .bind(expires_at)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        if let Some(db_err) = e.as_database_error() {
+            if db_err.is_foreign_key_violation() {
+                return (StatusCode::BAD_REQUEST, "Organization not found".to_string());
+            }
+        }
+        (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create API key: {}", e))
+    })?;
+
+    // Not a row snapshot: the row holds the key hash.
+    let after = serde_json::json!({
+        "name": name,
+        "prefix": prefix,
+        "permissions": permissions,
+        "expires_at": expires_at,
+    });
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("api_key.create", "api_key", id)
+            .organization(Some(organization_id))
+            .after(Some(after)),
+    )
+    .await?;
+
+    tx.commit().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
+    Ok(Json(CreatedApiKey { id, key, prefix, expires_at }))
+}
+
+/// Revoke one of the caller's keys (any key for super admins). Idempotent.
+pub async fn revoke_api_key(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(key_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    forbid_api_key(&claims)?;
+    if !claims.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
+    }
+    let owner: Option<Uuid> = match claims.admin_level {
+        Some(AdminLevel::SuperAdmin) => None,
+        _ => Some(Uuid::parse_str(&claims.sub).map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?),
+    };
+
+    let mut tx = pool.begin().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let organization_id: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        UPDATE api_keys SET revoked_at = COALESCE(revoked_at, NOW())
+        WHERE id = $1 AND ($2::uuid IS NULL OR user_id = $2)
+        RETURNING organization_id
+        "#,
+    )
+    .bind(key_id)
+    .bind(owner)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to revoke API key: {}", e)))?;
+    let Some(organization_id) = organization_id else {
+        return Err((StatusCode::NOT_FOUND, "API key not found".to_string()));
+    };
+
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("api_key.revoke", "api_key", key_id).organization(Some(organization_id)),
+    )
+    .await?;
+    tx.commit().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
+    Ok(Json(serde_json::json!({ "message": "API key revoked" })))
+}
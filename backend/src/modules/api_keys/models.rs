@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyInput {
+    pub name: String,
+    /// Keys from the permission catalogue; each must be held by the issuer.
+    pub permissions: Vec<String>,
+    /// Defaults to 90 days, at most 365.
+    pub expires_in_days: Option<i64>,
+    /// Required for super admins, who have no organization of their own.
+    pub organization_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ApiKeyItem {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub owner_name: Option<String>,
+    pub organization_id: Uuid,
+    pub organization_name: Option<String>,
+    pub name: String,
+    pub prefix: String,
+    pub permissions: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub last_used_ip: Option<String>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Returned once at creation; `key` is never shown again.
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    pub id: Uuid,
+    pub key: String,
+    pub prefix: String,
+    pub expires_at: DateTime<Utc>,
+}
@@ -0,0 +1,215 @@
+//! Authentication with personal API keys.
+//!
+//! A key looks like `trk_<prefix>_<secret>`. It is presented as `X-API-Key`
+//! or as a Bearer token and resolves to `Claims` for its issuing admin, but
+//! always as an organization-scoped admin of the key's organization holding
+//! only the key's permissions that the issuer still has. Revoking the
+//! issuer's admin role therefore disables their keys too.
+
+use axum::http::{HeaderMap, StatusCode};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::models::AdminLevel;
+use crate::modules::auth::handlers::client_ip;
+use crate::modules::auth::models::Claims;
+use crate::modules::auth::permissions::Permission;
+
+pub const KEY_PREFIX: &str = "trk_";
+pub const API_KEY_HEADER: &str = "X-API-Key";
+/// `last_used_at` is refreshed at most this often to spare a write per call.
+const LAST_USED_RESOLUTION_SECS: i64 = 60;
+
+/// Returns `(key, prefix, hash)`. Hand the key to the admin once, store the
+/// prefix and hash.
+pub fn generate() -> (String, String, String) {
+    let prefix: String = rand::random::<[u8; 6]>().iter().map(|b| format!("{:02x}", b)).collect();
+    let secret = URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>());
+    let key = format!("{}{}_{}", KEY_PREFIX, prefix, secret);
+    let hash = hash_key(&key);
+    (key, prefix, hash)
+}
+
+pub fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// The API key in the request, if any. Bearer values that don't carry the
+/// key prefix are left for the JWT path.
+pub fn presented_key(headers: &HeaderMap) -> Option<&str> {
+    if let Some(value) = headers.get(API_KEY_HEADER).and_then(|v| v.to_str().ok()) {
+        return Some(value.trim());
+    }
+    headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|v| v.starts_with(KEY_PREFIX))
+}
+
+#[derive(sqlx::FromRow)]
+struct KeyRow {
+    id: Uuid,
+    organization_id: Uuid,
+    permissions: Vec<String>,
+    last_used_at: Option<DateTime<Utc>>,
+    user_id: Uuid,
+    student_id: String,
+    email: String,
+    first_name: String,
+    last_name: String,
+    department_id: Option<Uuid>,
+    admin_level: AdminLevel,
+    admin_organization_id: Option<Uuid>,
+    admin_permissions: Vec<String>,
+}
+
+/// What the issuer may delegate: everything an org-scoped admin can hold
+/// for super admins, otherwise their own grant.
+pub fn grantable(level: &AdminLevel, admin_permissions: &[String]) -> Vec<&'static str> {
+    Permission::ALL
+        .into_iter()
+        .filter(|p| !p.super_admin_only())
+        .filter(|p| matches!(level, AdminLevel::SuperAdmin) || admin_permissions.iter().any(|k| k == p.key()))
+        .map(|p| p.key())
+        .collect()
+}
+
+pub async fn authenticate(pool: &PgPool, key: &str, headers: &HeaderMap) -> Result<Claims, (StatusCode, String)> {
+    let invalid = || (StatusCode::UNAUTHORIZED, "Invalid or expired API key".to_string());
+    if !key.starts_with(KEY_PREFIX) {
+        return Err(invalid());
+    }
+
+    let row = sqlx::query_as::<_, KeyRow>(
+        r#"
+        SELECT
+            k.id, k.organization_id, k.permissions, k.last_used_at,
+            u.id AS user_id, u.student_id, u.email, u.first_name, u.last_name, u.department_id,
+            ar.admin_level, ar.organization_id AS admin_organization_id, ar.permissions AS admin_permissions
+        FROM api_keys k
+        JOIN users u ON u.id = k.user_id AND u.deleted_at IS NULL AND u.status = 'active'::user_status
+        JOIN admin_roles ar ON ar.user_id = u.id AND ar.is_enabled = TRUE
+        WHERE k.key_hash = $1 AND k.revoked_at IS NULL AND k.expires_at > NOW()
+        "#,
+    )
+    .bind(hash_key(key))
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or_else(invalid)?;
+
+    let is_super = matches!(row.admin_level, AdminLevel::SuperAdmin);
+    // An issuer moved to another organization no longer speaks for this one.
+    if !is_super && row.admin_organization_id != Some(row.organization_id) {
+        return Err(invalid());
+    }
+
+    let stale = row
+        .last_used_at
+        .is_none_or(|t| (Utc::now() - t).num_seconds() >= LAST_USED_RESOLUTION_SECS);
+    if stale {
+        sqlx::query("UPDATE api_keys SET last_used_at = NOW(), last_used_ip = $2::inet WHERE id = $1")
+            .bind(row.id)
+            .bind(client_ip(headers))
+            .execute(pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    Ok(key_claims(row))
+}
+
+/// Claims the key acts with: an org-scoped admin holding the key's
+/// permissions, narrowed to what the issuer can still grant. Handlers gate
+/// on those permissions, never on `is_admin` alone.
+fn key_claims(row: KeyRow) -> Claims {
+    let is_super = matches!(row.admin_level, AdminLevel::SuperAdmin);
+    let grantable = grantable(&row.admin_level, &row.admin_permissions);
+    let permissions: Vec<String> = row
+        .permissions
+        .into_iter()
+        .filter(|p| grantable.contains(&p.as_str()))
+        .collect();
+
+    let now = Utc::now().timestamp() as usize;
+    Claims {
+        sub: row.user_id.to_string(),
+        session_id: format!("api_key:{}", row.id),
+        exp: now + 60,
+        iat: now,
+        student_id: row.student_id,
+        email: row.email,
+        first_name: row.first_name,
+        last_name: row.last_name,
+        department_id: row.department_id,
+        is_admin: true,
+        admin_level: Some(if is_super { AdminLevel::OrganizationAdmin } else { row.admin_level }),
+        organization_id: Some(row.organization_id),
+        permissions,
+        impersonator_id: None,
+        api_key_id: Some(row.id),
+    }
+}
+
+/// API keys act on data, not on credentials: they can't mint more keys,
+/// change passwords or manage 2FA.
+pub fn forbid_api_key(claims: &Claims) -> Result<(), (StatusCode, String)> {
+    if claims.api_key_id.is_some() {
+        return Err((StatusCode::FORBIDDEN, "Not available with an API key".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_keys_are_recognised_by_prefix() {
+        let (key, prefix, hash) = generate();
+        assert!(key.starts_with(&format!("{}{}_", KEY_PREFIX, prefix)));
+        assert_eq!(hash, hash_key(&key));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("Authorization", format!("Bearer {}", key).parse().unwrap());
+        assert_eq!(presented_key(&headers), Some(key.as_str()));
+        headers.insert("Authorization", "Bearer eyJhbGciOi.jwt".parse().unwrap());
+        assert_eq!(presented_key(&headers), None);
+        headers.insert(API_KEY_HEADER, key.parse().unwrap());
+        assert_eq!(presented_key(&headers), Some(key.as_str()));
+    }
+
+    #[test]
+    fn key_claims_only_carry_the_chosen_permissions() {
+        use crate::modules::auth::permissions::{require_permission, Permission};
+
+        let row = KeyRow {
+            id: Uuid::new_v4(),
+            organization_id: Uuid::new_v4(),
+            permissions: vec!["qr.scan".into(), "organizations.manage".into()],
+            last_used_at: None,
+            user_id: Uuid::new_v4(),
+            student_id: "A1".into(),
+            email: "a@example.com".into(),
+            first_name: "A".into(),
+            last_name: "B".into(),
+            department_id: None,
+            admin_level: AdminLevel::SuperAdmin,
+            admin_organization_id: None,
+            admin_permissions: vec![],
+        };
+        let claims = key_claims(row);
+        assert_eq!(claims.permissions, vec!["qr.scan".to_string()]);
+        assert!(require_permission(&claims, Permission::QrScan).is_ok());
+        for p in [Permission::ActivitiesView, Permission::AdminsView, Permission::DashboardView, Permission::UsersView] {
+            assert!(require_permission(&claims, p).is_err());
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use axum::{Json, extract::{Query, State}, http::{StatusCode, HeaderMap}};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::models::AdminLevel;
+use crate::modules::auth::get_claims_from_headers;
+use crate::modules::auth::permissions::{require_permission, Permission};
+use super::models::{AuditLogItem, AuditLogListResponse, ListAuditLogsQuery};
+
+const AUDIT_LOG_FILTER: &str = r#"
+    WHERE ($1::uuid IS NULL OR l.organization_id = $1)
+      AND ($2::text IS NULL OR l.action = $2)
+      AND ($3::text IS NULL OR l.entity_type = $3)
+      AND ($4::uuid IS NULL OR l.entity_id = $4)
+      AND ($5::uuid IS NULL OR l.user_id = $5)
+      AND ($6::date IS NULL OR l.log_date >= $6)
+      AND ($7::date IS NULL OR l.log_date <= $7)
+"#;
+
+/// Newest-first audit trail. Super admins see everything (optionally
+/// narrowed by `organization_id`); other admins only see entries recorded
+/// against their own organization.
+pub async fn list_audit_logs(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Query(params): Query<ListAuditLogsQuery>,
+) -> Result<Json<AuditLogListResponse>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::AuditView)?;
+
+    let scope_org_id: Option<Uuid> = match claims.admin_level {
+        Some(AdminLevel::SuperAdmin) => params.organization_id,
+        _ => Some(claims.organization_id.ok_or((
+            StatusCode::FORBIDDEN,
+            "Admin is not assigned to any organization".to_string(),
+        ))?),
+    };
+
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(50).clamp(1, 200);
+    let offset = (page - 1) * per_page;
+
+    let action = params.action.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    let entity_type = params.entity_type.as_deref().map(str::trim).filter(|s| !s.is_empty());
+
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM audit_logs l {}", AUDIT_LOG_FILTER))
+        .bind(scope_org_id)
+        .bind(action)
+        .bind(entity_type)
+        .bind(params.entity_id)
+        .bind(params.user_id)
+        .bind(params.from)
+        .bind(params.to)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to count audit logs: {}", e)))?;
+
+    let logs = sqlx::query_as::<_, AuditLogItem>(&format!(
+        r#"
+        SELECT
+            l.id, l.user_id,
+            u.student_id AS actor_student_id,
+            u.first_name || ' ' || u.last_name AS actor_name,
+            l.session_id, l.action, l.entity_type, l.entity_id, l.organization_id,
+            l.old_values, l.new_values,
+            host(l.ip_address) AS ip_address, l.user_agent, l.timestamp
+        FROM audit_logs l
+        LEFT JOIN users u ON u.id = l.user_id
+        {}
+        ORDER BY l.timestamp DESC, l.id DESC
+        LIMIT $8 OFFSET $9
+        "#,
+        AUDIT_LOG_FILTER
+    ))
+    .bind(scope_org_id)
+    .bind(action)
+    .bind(entity_type)
+    .bind(params.entity_id)
+    .bind(params.user_id)
+    .bind(params.from)
+    .bind(params.to)
+    .bind(per_page)
+    .bind(offset)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to fetch audit logs: {}", e)))?;
+
+    Ok(Json(AuditLogListResponse { logs, total }))
+}
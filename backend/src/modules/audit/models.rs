@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, NaiveDate, Utc};
+
+#[derive(Debug, Deserialize)]
+pub struct ListAuditLogsQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// Exact action, e.g. `user.delete`.
+    pub action: Option<String>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<Uuid>,
+    /// The admin who performed the action.
+    pub user_id: Option<Uuid>,
+    /// Super admins only; org admins are always pinned to their own org.
+    pub organization_id: Option<Uuid>,
+    /// Inclusive date range on `log_date`.
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AuditLogItem {
+    pub id: i64,
+    pub user_id: Option<Uuid>,
+    pub actor_student_id: Option<String>,
+    pub actor_name: Option<String>,
+    pub session_id: Option<String>,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: Option<Uuid>,
+    pub organization_id: Option<Uuid>,
+    pub old_values: Option<serde_json::Value>,
+    pub new_values: Option<serde_json::Value>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditLogListResponse {
+    pub logs: Vec<AuditLogItem>,
+    pub total: i64,
+}
@@ -0,0 +1,124 @@
+//! Write side of `audit_logs`.
+//!
+//! A privileged handler takes a `snapshot` of the row before and after its
+//! change and hands both to `record` on the same transaction as the change
+//! itself, so a mutation can't commit without its log entry.
+
+use axum::http::{HeaderMap, StatusCode};
+use serde_json::Value;
+use sqlx::PgExecutor;
+use uuid::Uuid;
+use crate::modules::auth::handlers::{client_ip, user_agent};
+use crate::modules::auth::models::Claims;
+
+/// One row of `audit_logs`. `action` is `<entity>.<verb>`, e.g.
+/// `activity.update`; `organization_id` decides which org admins see it.
+#[derive(Debug)]
+pub struct AuditEntry {
+    pub action: &'static str,
+    pub entity_type: &'static str,
+    pub entity_id: Option<Uuid>,
+    pub organization_id: Option<Uuid>,
+    pub old_values: Option<Value>,
+    pub new_values: Option<Value>,
+}
+
+impl AuditEntry {
+    pub fn new(action: &'static str, entity_type: &'static str, entity_id: Uuid) -> Self {
+        Self {
+            action,
+            entity_type,
+            entity_id: Some(entity_id),
+            organization_id: None,
+            old_values: None,
+            new_values: None,
+        }
+    }
+
+    pub fn organization(mut self, organization_id: Option<Uuid>) -> Self {
+        self.organization_id = organization_id;
+        self
+    }
+
+    pub fn before(mut self, old_values: Option<Value>) -> Self {
+        self.old_values = old_values;
+        self
+    }
+
+    pub fn after(mut self, new_values: Option<Value>) -> Self {
+        self.new_values = new_values;
+        self
+    }
+}
+
+/// The row `id` of `table` as JSON, minus credentials. `table` is always a
+/// literal from the calling handler, never user input.
+pub async fn snapshot<'e>(
+    executor: impl PgExecutor<'e>,
+    table: &'static str,
+    id: Uuid,
+) -> Result<Option<Value>, (StatusCode, String)> {
+    sqlx::query_scalar::<_, Value>(&format!(
+        "SELECT to_jsonb(t) - 'password_hash' FROM {} t WHERE id = $1",
+        table
+    ))
+    .bind(id)
+    .fetch_optional(executor)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to snapshot {}: {}", table, e)))
+}
+
+/// Read a UUID field (typically the owning organization) out of a snapshot.
+pub fn uuid_field(snapshot: &Option<Value>, field: &str) -> Option<Uuid> {
+    snapshot
+        .as_ref()
+        .and_then(|v| v.get(field))
+        .and_then(Value::as_str)
+        .and_then(|s| Uuid::parse_str(s).ok())
+}
+
+pub async fn record<'e>(
+    executor: impl PgExecutor<'e>,
+    claims: &Claims,
+    headers: &HeaderMap,
+    entry: AuditEntry,
+) -> Result<(), (StatusCode, String)> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_logs (
+            user_id, session_id, action, entity_type, entity_id, organization_id,
+            old_values, new_values, ip_address, user_agent
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9::inet, $10)
+        "#,
+    )
+    // During impersonation the real actor is the admin, not `sub`.
+    .bind(claims.impersonator_id.or_else(|| Uuid::parse_str(&claims.sub).ok()))
+    .bind(&claims.session_id)
+    .bind(entry.action)
+    .bind(entry.entity_type)
+    .bind(entry.entity_id)
+    .bind(entry.organization_id)
+    .bind(entry.old_values)
+    .bind(entry.new_values)
+    .bind(client_ip(headers))
+    .bind(user_agent(headers))
+    .execute(executor)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to write audit log: {}", e)))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uuid_field_reads_string_uuids_only() {
+        let id = Uuid::new_v4();
+        let snap = Some(serde_json::json!({ "organizer_id": id.to_string(), "hours": 3 }));
+        assert_eq!(uuid_field(&snap, "organizer_id"), Some(id));
+        assert_eq!(uuid_field(&snap, "hours"), None);
+        assert_eq!(uuid_field(&None, "organizer_id"), None);
+    }
+}
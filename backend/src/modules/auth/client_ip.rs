@@ -0,0 +1,144 @@
+//! Which address a request came from, for the login throttle, sessions,
+//! the audit log and API-key usage.
+//!
+//! Forwarding headers are client-controlled unless a proxy we trust
+//! overwrites them, so by default only the socket peer counts. Behind a
+//! proxy, set:
+//! - `TRUSTED_PROXY_HEADER` — the header the proxy sets, e.g.
+//!   `CF-Connecting-IP` behind Cloudflare or `X-Forwarded-For`.
+//! - `TRUSTED_PROXY_HOPS` — for `X-Forwarded-For` only: how many proxies
+//!   we run in front of the backend (default 1). The client is the entry
+//!   that many places from the right; anything further left was sent by the
+//!   client itself.
+//!
+//! The origin must then only accept connections from that proxy, or a
+//! client can talk to it directly and send the header itself.
+//!
+//! [`resolve_client_ip`] works the address out once per request and stamps
+//! it on [`RESOLVED_IP_HEADER`], replacing any copy the client sent;
+//! `client_ip` reads it back from there.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+pub const RESOLVED_IP_HEADER: &str = "x-trackivity-client-ip";
+
+#[derive(Debug, Clone)]
+pub struct TrustedProxy {
+    header: Option<HeaderName>,
+    hops: usize,
+}
+
+impl TrustedProxy {
+    pub fn from_env() -> Arc<Self> {
+        let header = std::env::var("TRUSTED_PROXY_HEADER")
+            .ok()
+            .map(|v| v.trim().to_ascii_lowercase())
+            .filter(|v| !v.is_empty())
+            .and_then(|v| match HeaderName::from_bytes(v.as_bytes()) {
+                Ok(name) => Some(name),
+                Err(_) => {
+                    tracing::error!("Ignoring invalid TRUSTED_PROXY_HEADER {:?}", v);
+                    None
+                }
+            });
+        let hops = std::env::var("TRUSTED_PROXY_HOPS")
+            .ok()
+            .and_then(|v| v.trim().parse().ok())
+            .unwrap_or(1usize)
+            .max(1);
+        Arc::new(Self { header, hops })
+    }
+
+    /// The client address: the trusted header when configured and usable,
+    /// otherwise the socket peer.
+    fn resolve(&self, headers: &HeaderMap, peer: Option<IpAddr>) -> Option<IpAddr> {
+        let Some(name) = &self.header else {
+            return peer;
+        };
+        let values: Vec<&str> = headers
+            .get_all(name)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .collect();
+        let candidate = if name == "x-forwarded-for" {
+            values.len().checked_sub(self.hops).map(|i| values[i])
+        } else {
+            values.last().copied()
+        };
+        candidate.and_then(|ip| ip.parse().ok()).or(peer)
+    }
+}
+
+pub async fn resolve_client_ip(
+    State(proxy): State<Arc<TrustedProxy>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let resolved = proxy.resolve(req.headers(), peer);
+    let headers = req.headers_mut();
+    headers.remove(RESOLVED_IP_HEADER);
+    if let Some(value) = resolved.and_then(|ip| HeaderValue::from_str(&ip.to_string()).ok()) {
+        headers.insert(RESOLVED_IP_HEADER, value);
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.append(*name, HeaderValue::from_static(value));
+        }
+        map
+    }
+
+    fn proxy(header: Option<&'static str>, hops: usize) -> TrustedProxy {
+        TrustedProxy { header: header.map(HeaderName::from_static), hops }
+    }
+
+    #[test]
+    fn forwarding_headers_are_ignored_unless_trusted() {
+        let peer = Some("192.0.2.10".parse().unwrap());
+        let spoofed = headers(&[("x-forwarded-for", "203.0.113.9"), ("cf-connecting-ip", "198.51.100.7")]);
+        assert_eq!(proxy(None, 1).resolve(&spoofed, peer), peer);
+    }
+
+    #[test]
+    fn forwarded_for_takes_the_entry_our_proxies_appended() {
+        let peer = Some("10.0.0.2".parse().unwrap());
+        // Client sent "1.1.1.1"; our proxy appended the real address.
+        let h = headers(&[("x-forwarded-for", "1.1.1.1, 203.0.113.9")]);
+        assert_eq!(proxy(Some("x-forwarded-for"), 1).resolve(&h, peer), Some("203.0.113.9".parse().unwrap()));
+
+        let h = headers(&[("x-forwarded-for", "1.1.1.1"), ("x-forwarded-for", "203.0.113.9, 10.0.0.1")]);
+        assert_eq!(proxy(Some("x-forwarded-for"), 2).resolve(&h, peer), Some("203.0.113.9".parse().unwrap()));
+
+        // Fewer entries than hops or garbage: fall back to the peer.
+        assert_eq!(proxy(Some("x-forwarded-for"), 4).resolve(&h, peer), peer);
+        let h = headers(&[("x-forwarded-for", "not-an-ip")]);
+        assert_eq!(proxy(Some("x-forwarded-for"), 1).resolve(&h, peer), peer);
+    }
+
+    #[test]
+    fn single_value_header_is_used_as_sent_by_the_proxy() {
+        let h = headers(&[("cf-connecting-ip", "198.51.100.7"), ("x-forwarded-for", "1.1.1.1")]);
+        assert_eq!(proxy(Some("cf-connecting-ip"), 1).resolve(&h, None), Some("198.51.100.7".parse().unwrap()));
+        assert_eq!(proxy(Some("cf-connecting-ip"), 1).resolve(&HeaderMap::new(), None), None);
+    }
+}
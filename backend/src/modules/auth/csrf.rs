@@ -0,0 +1,122 @@
+//! CSRF protection for cookie-authenticated requests.
+//!
+//! The browser attaches `session_token` / `refresh_token` to cross-site
+//! requests as well, so a state-changing request that authenticates by
+//! cookie must prove it came from the frontend: its `Origin` (or, failing
+//! that, `Referer`) has to match one of the `FRONTEND_URL` origins.
+//! Requests carrying a Bearer token or an API key are exempt — a cross-site
+//! page can't set those headers without passing the CORS preflight.
+
+use std::sync::Arc;
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use crate::modules::api_keys::service::API_KEY_HEADER;
+use super::handlers::read_cookie;
+use super::tokens::{ACCESS_COOKIE, REFRESH_COOKIE};
+
+/// Normalized (`scheme://host[:port]`, lowercase) frontend origins.
+#[derive(Debug, Clone)]
+pub struct AllowedOrigins(Vec<String>);
+
+impl AllowedOrigins {
+    pub fn new<'a>(urls: impl IntoIterator<Item = &'a str>) -> Arc<Self> {
+        Arc::new(Self(urls.into_iter().filter_map(origin_of).collect()))
+    }
+
+    fn contains(&self, origin: &str) -> bool {
+        self.0.iter().any(|o| o == origin)
+    }
+}
+
+/// `scheme://host[:port]` of a URL, lowercased; `None` for `null` or
+/// anything without a scheme.
+fn origin_of(url: &str) -> Option<String> {
+    let url = url.trim();
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    if scheme.is_empty() || authority.is_empty() {
+        return None;
+    }
+    Some(format!("{}://{}", scheme, authority).to_ascii_lowercase())
+}
+
+fn is_safe_method(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+/// Whether the request would be authenticated by cookie alone.
+fn cookie_authenticated(headers: &HeaderMap) -> bool {
+    let has_bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("Bearer "));
+    if has_bearer || headers.contains_key(API_KEY_HEADER) {
+        return false;
+    }
+    read_cookie(headers, ACCESS_COOKIE).is_some() || read_cookie(headers, REFRESH_COOKIE).is_some()
+}
+
+fn request_origin(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::ORIGIN)
+        .or_else(|| headers.get(header::REFERER))
+        .and_then(|v| v.to_str().ok())
+        .and_then(origin_of)
+}
+
+fn check(allowed: &AllowedOrigins, method: &Method, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    if is_safe_method(method) || !cookie_authenticated(headers) {
+        return Ok(());
+    }
+    match request_origin(headers) {
+        Some(origin) if allowed.contains(&origin) => Ok(()),
+        Some(origin) => {
+            tracing::warn!("Rejected cross-site request from origin {}", origin);
+            Err((StatusCode::FORBIDDEN, "Cross-site request rejected".to_string()))
+        }
+        None => Err((StatusCode::FORBIDDEN, "Missing Origin header".to_string())),
+    }
+}
+
+pub async fn verify_origin(State(allowed): State<Arc<AllowedOrigins>>, req: Request, next: Next) -> Response {
+    if let Err(e) = check(&allowed, req.method(), req.headers()) {
+        return e.into_response();
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut map = HeaderMap::new();
+        for (name, value) in pairs {
+            map.insert(*name, HeaderValue::from_static(value));
+        }
+        map
+    }
+
+    #[test]
+    fn cookie_mutations_need_a_frontend_origin() {
+        let allowed = AllowedOrigins::new(["https://app.example.ac.th/", "http://localhost:5173"]);
+        let cookie = ("cookie", "session_token=abc");
+
+        assert!(check(&allowed, &Method::POST, &headers(&[cookie, ("origin", "https://APP.example.ac.th")])).is_ok());
+        assert!(check(&allowed, &Method::DELETE, &headers(&[cookie, ("referer", "http://localhost:5173/admin/users?x=1")])).is_ok());
+        assert!(check(&allowed, &Method::POST, &headers(&[cookie, ("origin", "https://evil.example")])).is_err());
+        assert!(check(&allowed, &Method::PUT, &headers(&[cookie])).is_err());
+        assert!(check(&allowed, &Method::POST, &headers(&[cookie, ("origin", "null")])).is_err());
+
+        // Safe methods, header-authenticated and anonymous requests pass.
+        assert!(check(&allowed, &Method::GET, &headers(&[cookie, ("origin", "https://evil.example")])).is_ok());
+        assert!(check(&allowed, &Method::POST, &headers(&[cookie, ("authorization", "Bearer t")])).is_ok());
+        assert!(check(&allowed, &Method::POST, &headers(&[cookie, ("x-api-key", "trk_x")])).is_ok());
+        assert!(check(&allowed, &Method::POST, &headers(&[("origin", "https://evil.example")])).is_ok());
+    }
+}
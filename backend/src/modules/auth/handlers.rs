@@ -1,31 +1,47 @@
-use axum::{Json, extract::State, http::StatusCode, http::HeaderMap};
-use axum::response::{IntoResponse, Response};
-use axum::http::header::{SET_COOKIE, COOKIE};
-use sqlx::PgPool;
-use crate::models::{User, AdminRole, UserStatus};
-use super::models::{AuthInput, AuthResponse, RegisterInput, RegisterResponse, UserResponse, Claims, ForgotPasswordInput, ResetPasswordInput};
+use axum::{Json, extract::{Path, Query, State}, http::StatusCode, http::HeaderMap};
+use axum::response::{AppendHeaders, IntoResponse, Redirect, Response};
+use axum::http::header::{CACHE_CONTROL, SET_COOKIE, COOKIE, RETRY_AFTER};
+use sqlx::{PgConnection, PgPool};
+use crate::models::{User, AdminLevel, AdminRole, UserStatus};
+use crate::modules::audit::service::{self as audit, AuditEntry};
+use crate::modules::api_keys::service as api_keys;
+use crate::modules::mailer::{outbox, EmailTemplate};
+use super::models::{AuthInput, AuthResponse, RegisterInput, RegisterResponse, UserResponse, Claims, ForgotPasswordInput, ResetPasswordInput, SessionInfo, TwoFactorChallengeResponse, TwoFactorLoginInput, TwoFactorCodeInput, TwoFactorSetupResponse, TwoFactorEnabledResponse, OidcLoginQuery, OidcCallbackQuery, VerifyEmailInput, ResendVerificationInput};
 use argon2::{
     Argon2, PasswordHash, PasswordVerifier,
     password_hash::{rand_core::OsRng, PasswordHasher, SaltString}
 };
-use jsonwebtoken::{encode, decode, Header, Validation, EncodingKey, DecodingKey};
 use chrono::{Utc, Duration};
 use uuid::Uuid;
+use super::{email_verification, impersonation, keys, oidc, password_policy, session, throttle, totp};
+use super::tokens::{self, ACCESS_COOKIE, REFRESH_COOKIE};
 
 // ─── Helpers ───────────────────────────────────────────────────────────────
 
 pub fn verify_token(token: &str) -> Result<Claims, StatusCode> {
-    let secret = std::env::var("JWT_SECRET").map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    let validation = Validation::default();
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &validation,
-    ).map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let token_data = keys::verify::<Claims>(keys::KeyUse::Session, token)
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
     Ok(token_data.claims)
 }
 
-pub fn get_claims_from_headers(headers: &HeaderMap) -> Result<Claims, (StatusCode, String)> {
+/// Decode the caller's JWT (Bearer header first, then the `session_token`
+/// cookie) and confirm its session has not been revoked server-side. An API
+/// key, when presented, is used instead.
+pub async fn get_claims_from_headers(
+    pool: &PgPool,
+    headers: &HeaderMap,
+) -> Result<Claims, (StatusCode, String)> {
+    if let Some(key) = api_keys::presented_key(headers) {
+        return api_keys::authenticate(pool, key, headers).await;
+    }
+    let claims = decode_claims_from_headers(headers)?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID in token".to_string()))?;
+    session::ensure_session_active(pool, &claims.session_id, user_id).await?;
+    Ok(claims)
+}
+
+fn decode_claims_from_headers(headers: &HeaderMap) -> Result<Claims, (StatusCode, String)> {
     if let Some(auth_header) = headers.get("Authorization") {
         let auth_str = auth_header.to_str()
             .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid Authorization header".to_string()))?;
@@ -36,126 +52,1032 @@ pub fn get_claims_from_headers(headers: &HeaderMap) -> Result<Claims, (StatusCod
         }
     }
 
-    if let Some(cookie_header) = headers.get(COOKIE) {
-        let cookie_str = cookie_header.to_str()
-            .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid cookie".to_string()))?;
-        for part in cookie_str.split(';') {
-            let part = part.trim();
-            if let Some(value) = part.strip_prefix("session_token=") {
-                return verify_token(value)
-                    .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid or expired session".to_string()));
+    if let Some(value) = read_cookie(headers, ACCESS_COOKIE) {
+        return verify_token(value)
+            .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid or expired session".to_string()));
+    }
+
+    Err((StatusCode::UNAUTHORIZED, "Missing authentication".to_string()))
+}
+
+pub(crate) fn read_cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    let cookie_str = headers.get(COOKIE)?.to_str().ok()?;
+    cookie_str.split(';').find_map(|part| {
+        part.trim()
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('='))
+    })
+}
+
+/// Client IP as worked out by the `client_ip::resolve_client_ip` middleware;
+/// `None` only when neither a trusted header nor the socket peer is known.
+pub fn client_ip(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(super::client_ip::RESOLVED_IP_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+pub fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(axum::http::header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// The refresh cookie is scoped to `/auth` so it only travels with the
+/// refresh and logout calls, never with ordinary API traffic.
+fn build_cookie(name: &str, value: &str, path: &str, max_age: i64) -> String {
+    let is_production = std::env::var("NODE_ENV").unwrap_or_default() == "production";
+    let secure = if is_production { "; Secure" } else { "" };
+    let domain = std::env::var("COOKIE_DOMAIN").unwrap_or_default();
+    let domain_part = if domain.is_empty() { String::new() } else { format!("; Domain={}", domain) };
+    format!(
+        "{}={}; HttpOnly{}; SameSite=Lax; Path={}; Max-Age={}{}",
+        name, value, secure, path, max_age.max(0), domain_part
+    )
+}
+
+fn auth_cookies(
+    access_token: &str,
+    access_expires_at: chrono::DateTime<Utc>,
+    refresh_token: &str,
+    session_expires_at: chrono::DateTime<Utc>,
+) -> AuthCookies {
+    let now = Utc::now();
+    AppendHeaders([
+        (SET_COOKIE, build_cookie(ACCESS_COOKIE, access_token, "/", (access_expires_at - now).num_seconds())),
+        (SET_COOKIE, build_cookie(REFRESH_COOKIE, refresh_token, "/auth", (session_expires_at - now).num_seconds())),
+    ])
+}
+
+type AuthCookies = AppendHeaders<[(axum::http::HeaderName, String); 2]>;
+
+/// Create a session for an already-authenticated user and answer with the
+/// access/refresh cookies and an `AuthResponse`. Shared by every login path
+/// so they all get the same session bookkeeping and 2FA policy.
+async fn start_session(
+    pool: &PgPool,
+    headers: &HeaderMap,
+    user: User,
+    remember_me: bool,
+    login_method: &str,
+) -> Result<Response, (StatusCode, String)> {
+    let (cookies, body) = create_session(pool, headers, user, remember_me, login_method).await?;
+    Ok((StatusCode::OK, cookies, Json(body)).into_response())
+}
+
+async fn create_session(
+    pool: &PgPool,
+    headers: &HeaderMap,
+    user: User,
+    remember_me: bool,
+    login_method: &str,
+) -> Result<(AuthCookies, AuthResponse), (StatusCode, String)> {
+    let admin_role = sqlx::query_as::<_, AdminRole>("SELECT * FROM admin_roles WHERE user_id = $1 AND is_enabled = TRUE")
+        .bind(user.id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let (admin_role, two_factor_setup_required) = totp::effective_admin_role(pool, user.id, admin_role).await?;
+
+    let session_id = Uuid::new_v4().to_string();
+    let expiration_duration = if remember_me { Duration::days(30) } else { Duration::days(7) };
+    let expires_at = Utc::now().checked_add_signed(expiration_duration).expect("valid timestamp");
+    let (refresh_token, refresh_hash) = tokens::generate_refresh_token();
+
+    // Session insert and last_login update are independent — run them
+    // concurrently to save one round-trip on every login. The first refresh
+    // token rides along in the same statement via a data-modifying CTE.
+    let session_insert = sqlx::query(
+        r#"
+        WITH s AS (
+            INSERT INTO sessions (id, user_id, expires_at, created_at, last_accessed, is_active, login_method, ip_address, user_agent)
+            VALUES ($1, $2, $3, NOW(), NOW(), TRUE, $4, $5::inet, $6)
+            RETURNING id
+        )
+        INSERT INTO session_refresh_tokens (token_hash, session_id)
+        SELECT $7, id FROM s
+        "#,
+    )
+    .bind(&session_id)
+    .bind(user.id)
+    .bind(expires_at)
+    .bind(login_method)
+    .bind(client_ip(headers))
+    .bind(user_agent(headers))
+    .bind(&refresh_hash)
+    .execute(pool);
+
+    let last_login_update = sqlx::query("UPDATE users SET last_login_at = NOW() WHERE id = $1")
+        .bind(user.id)
+        .execute(pool);
+
+    let (session_res, last_login_res) = tokio::join!(session_insert, last_login_update);
+    session_res.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create session: {}", e)))?;
+    last_login_res.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update user stats: {}", e)))?;
+
+    let access_expires_at = tokens::access_token_expiry(Utc::now(), expires_at);
+    let claims = tokens::claims_for_user(&user, admin_role.as_ref(), &session_id, access_expires_at);
+    let token = tokens::sign_access_token(&claims)?;
+    let cookies = auth_cookies(&token, access_expires_at, &refresh_token, expires_at);
+
+    let body = AuthResponse {
+        token,
+        access_expires_at,
+        user: UserResponse {
+            id: user.id,
+            student_id: user.student_id,
+            email: user.email,
+            first_name: user.first_name,
+            last_name: user.last_name,
+            prefix: user.prefix,
+            admin_role,
+            organization_id: None,
+            organization_name: None,
+            department_id: user.department_id,
+            department_name: None,
+            session_id,
+            expires_at,
+            two_factor_setup_required,
+            impersonator_id: None,
+        }
+    };
+
+    Ok((cookies, body))
+}
+
+/// An argon2 hash of a random password, with the same parameters as real
+/// ones, for `login_handler` to verify against when no account matches.
+fn dummy_password_hash() -> &'static str {
+    static HASH: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+    HASH.get_or_init(|| {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(oidc::random_token().as_bytes(), &salt)
+            .expect("argon2 hashing with default parameters")
+            .to_string()
+    })
+}
+
+// ─── Handlers ──────────────────────────────────────────────────────────────
+
+pub async fn login_handler(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Json(payload): Json<AuthInput>,
+) -> Result<Response, (StatusCode, String)> {
+    let user_query_result = if let Some(email) = &payload.email {
+        sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = $1")
+            .bind(email)
+            .fetch_optional(&pool)
+            .await
+    } else if let Some(student_id) = &payload.student_id {
+        sqlx::query_as::<_, User>("SELECT * FROM users WHERE student_id = $1")
+            .bind(student_id)
+            .fetch_optional(&pool)
+            .await
+    } else {
+        return Err((StatusCode::BAD_REQUEST, "Email or Student ID required".to_string()));
+    };
+
+    let user = user_query_result
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Throttle by user id when the account exists so alternating between
+    // email and student_id doesn't get a fresh allowance; otherwise by the
+    // identifier typed, so probing unknown accounts is throttled the same way.
+    let account_key = match &user {
+        Some(u) => u.id.to_string(),
+        None => payload.email.as_deref().or(payload.student_id.as_deref())
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase(),
+    };
+    let ip = client_ip(&headers);
+    let mut throttle_keys = vec![(throttle::SCOPE_ACCOUNT, account_key.as_str())];
+    if let Some(ip) = ip.as_deref() {
+        throttle_keys.push((throttle::SCOPE_IP, ip));
+    }
+
+    if let Some(until) = throttle::locked_until(&pool, &throttle_keys)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    {
+        let retry_after = (until - Utc::now()).num_seconds().max(1).to_string();
+        return Ok((
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, retry_after)],
+            throttle::lockout_message(until),
+        ).into_response());
+    }
+
+    // Unknown accounts still pay for one argon2 verification so the response
+    // time doesn't reveal which student IDs exist.
+    let stored_hash = match &user {
+        Some(u) => u.password_hash.as_str(),
+        None => dummy_password_hash(),
+    };
+    let parsed_hash = PasswordHash::new(stored_hash)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Invalid password hash stored".to_string()))?;
+    let password_ok = Argon2::default().verify_password(payload.password.as_bytes(), &parsed_hash).is_ok()
+        && user.is_some();
+
+    let user = match user {
+        Some(u) if password_ok => u,
+        _ => {
+            for (scope, key) in &throttle_keys {
+                if let Err(e) = throttle::record_failure(&pool, scope, key).await {
+                    tracing::error!("Failed to record login failure for {} {}: {}", scope, key, e);
+                }
+            }
+            return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
+        }
+    };
+
+    match user.status {
+        UserStatus::Active | UserStatus::Pending => {},
+        UserStatus::Rejected => {
+            let reason: Option<String> = sqlx::query_scalar("SELECT registration_review_reason FROM users WHERE id = $1")
+                .bind(user.id)
+                .fetch_one(&pool)
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            return Ok((
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({
+                    "code": REGISTRATION_REJECTED_CODE,
+                    "message": "Your registration was rejected",
+                    "reason": reason,
+                })),
+            ).into_response());
+        }
+        _ => return Err((StatusCode::FORBIDDEN, "Account is not active".to_string())),
+    }
+    if user.email_verified_at.is_none() {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "code": email_verification::NOT_VERIFIED_CODE,
+                "message": "Please verify your email address before signing in",
+            })),
+        ).into_response());
+    }
+    // Checked after verification so the student first does what they can.
+    if user.status == UserStatus::Pending {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            Json(serde_json::json!({
+                "code": REGISTRATION_PENDING_CODE,
+                "message": "Your registration is waiting for administrator approval",
+            })),
+        ).into_response());
+    }
+
+    throttle::clear(&pool, throttle::SCOPE_ACCOUNT, &account_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let remember_me = payload.remember_me.unwrap_or(false);
+    if totp::is_enabled(&pool, user.id).await? {
+        let (challenge_token, expires_at) = totp::sign_challenge(user.id, remember_me, "password")?;
+        return Ok(Json(TwoFactorChallengeResponse {
+            two_factor_required: true,
+            challenge_token,
+            expires_at,
+        }).into_response());
+    }
+
+    start_session(&pool, &headers, user, remember_me, "password").await
+}
+
+/// How long after a refresh token was rotated a second presentation is
+/// treated as a benign race (two tabs refreshing at once) rather than theft.
+const REFRESH_REUSE_GRACE_SECS: i64 = 10;
+
+/// How long a rotated refresh token is kept so replaying it still revokes
+/// the session. Older rotated rows are pruned on the next refresh; a replay
+/// after that is simply an unknown token.
+const REFRESH_REUSE_DETECTION_HOURS: i32 = 24;
+
+/// Codes `/auth/login` answers with while a registration awaits or has
+/// failed admin approval.
+const REGISTRATION_PENDING_CODE: &str = "registration_pending";
+const REGISTRATION_REJECTED_CODE: &str = "registration_rejected";
+
+/// Exchange the refresh cookie for a new access token and a new refresh
+/// token. The refresh token only ever travels in its HttpOnly cookie, so
+/// script on the page can't read it. Replaying an already-rotated token
+/// revokes the whole session, since only a leaked copy could still be
+/// presenting it.
+pub async fn refresh_handler(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, String)> {
+    let presented = read_cookie(&headers, REFRESH_COOKIE)
+        .ok_or((StatusCode::UNAUTHORIZED, "Missing refresh token".to_string()))?;
+    let presented_hash = tokens::hash_refresh_token(presented);
+
+    #[derive(sqlx::FromRow)]
+    struct RefreshRow {
+        session_id: String,
+        rotated_at: Option<chrono::DateTime<Utc>>,
+        user_id: Uuid,
+        is_active: bool,
+        expires_at: chrono::DateTime<Utc>,
+        impersonator_id: Option<Uuid>,
+    }
+
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let row = sqlx::query_as::<_, RefreshRow>(r#"
+        SELECT rt.session_id, rt.rotated_at, s.user_id, s.is_active, s.expires_at, s.impersonator_id
+        FROM session_refresh_tokens rt
+        JOIN sessions s ON s.id = rt.session_id
+        WHERE rt.token_hash = $1
+        FOR UPDATE OF rt
+    "#)
+    .bind(&presented_hash)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::UNAUTHORIZED, "Invalid refresh token".to_string()))?;
+
+    if let Some(rotated_at) = row.rotated_at {
+        if Utc::now() - rotated_at <= Duration::seconds(REFRESH_REUSE_GRACE_SECS) {
+            return Err((StatusCode::CONFLICT, "Refresh token already rotated".to_string()));
+        }
+        tracing::warn!(
+            "Refresh token reuse detected for session {} (user {}) — revoking session",
+            row.session_id,
+            row.user_id
+        );
+        sqlx::query("UPDATE sessions SET is_active = FALSE WHERE id = $1")
+            .bind(&row.session_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        tx.commit()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        session::invalidate_session(&row.session_id);
+        return Err((StatusCode::UNAUTHORIZED, "Refresh token reuse detected".to_string()));
+    }
+
+    if !row.is_active || row.expires_at <= Utc::now() {
+        return Err((StatusCode::UNAUTHORIZED, "Session has been revoked or expired".to_string()));
+    }
+
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL")
+        .bind(row.user_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::UNAUTHORIZED, "User not found".to_string()))?;
+    if user.status != UserStatus::Active {
+        return Err((StatusCode::FORBIDDEN, "Account is not active".to_string()));
+    }
+
+    // Re-read the admin role so a demotion (or a newly enforced 2FA policy)
+    // takes effect at the next refresh rather than at the next login.
+    let admin_role = sqlx::query_as::<_, AdminRole>("SELECT * FROM admin_roles WHERE user_id = $1 AND is_enabled = TRUE")
+        .bind(user.id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let (admin_role, _) = totp::effective_admin_role(&pool, user.id, admin_role).await?;
+    // Impersonation sessions never carry admin claims, whoever the target is.
+    let admin_role = admin_role.filter(|_| row.impersonator_id.is_none());
+
+    let (refresh_token, refresh_hash) = tokens::generate_refresh_token();
+
+    sqlx::query("UPDATE session_refresh_tokens SET rotated_at = NOW() WHERE token_hash = $1")
+        .bind(&presented_hash)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query("INSERT INTO session_refresh_tokens (token_hash, session_id) VALUES ($1, $2)")
+        .bind(&refresh_hash)
+        .bind(&row.session_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query(r#"
+        DELETE FROM session_refresh_tokens
+        WHERE session_id = $1 AND rotated_at < NOW() - make_interval(hours => $2)
+    "#)
+    .bind(&row.session_id)
+    .bind(REFRESH_REUSE_DETECTION_HOURS)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let access_expires_at = tokens::access_token_expiry(Utc::now(), row.expires_at);
+    let mut claims = tokens::claims_for_user(&user, admin_role.as_ref(), &row.session_id, access_expires_at);
+    claims.impersonator_id = row.impersonator_id;
+    let token = tokens::sign_access_token(&claims)?;
+    let cookies = auth_cookies(&token, access_expires_at, &refresh_token, row.expires_at);
+
+    let body = serde_json::json!({
+        "token": token,
+        "access_expires_at": access_expires_at,
+        "expires_at": row.expires_at,
+    });
+    Ok((StatusCode::OK, cookies, Json(body)).into_response())
+}
+
+pub async fn logout_handler(
+    headers: HeaderMap,
+    State(pool): State<PgPool>,
+) -> Result<Response, (StatusCode, String)> {
+    if let Ok(claims) = get_claims_from_headers(&pool, &headers).await {
+        let _ = sqlx::query("UPDATE sessions SET is_active = FALSE WHERE id = $1")
+            .bind(&claims.session_id)
+            .execute(&pool)
+            .await;
+        session::invalidate_session(&claims.session_id);
+        if claims.impersonator_id.is_some() {
+            if let Err(e) = record_impersonation_stop(&pool, &claims, &headers).await {
+                tracing::error!("Failed to audit impersonation stop: {:?}", e);
+            }
+        }
+    }
+    let response = (
+        StatusCode::OK,
+        AppendHeaders([
+            (SET_COOKIE, build_cookie(ACCESS_COOKIE, "", "/", 0)),
+            (SET_COOKIE, build_cookie(REFRESH_COOKIE, "", "/auth", 0)),
+        ]),
+        Json(serde_json::json!({ "message": "Logged out successfully" })),
+    ).into_response();
+    Ok(response)
+}
+
+// ─── Two-factor authentication ────────────────────────────────────────────
+
+/// Second login step: trade the challenge from `login_handler` plus a TOTP
+/// or recovery code for a session. Wrong codes count toward the same
+/// account lockout as wrong passwords.
+pub async fn two_factor_login_handler(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Json(payload): Json<TwoFactorLoginInput>,
+) -> Result<Response, (StatusCode, String)> {
+    let challenge = totp::verify_challenge(&payload.challenge_token)?;
+    let user_id = Uuid::parse_str(&challenge.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid two-factor challenge".to_string()))?;
+
+    let account_key = user_id.to_string();
+    let ip = client_ip(&headers);
+    let mut throttle_keys = vec![(throttle::SCOPE_ACCOUNT, account_key.as_str())];
+    if let Some(ip) = ip.as_deref() {
+        throttle_keys.push((throttle::SCOPE_IP, ip));
+    }
+    if let Some(until) = throttle::locked_until(&pool, &throttle_keys)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    {
+        let retry_after = (until - Utc::now()).num_seconds().max(1).to_string();
+        return Ok((
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, retry_after)],
+            throttle::lockout_message(until),
+        ).into_response());
+    }
+
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL")
+        .bind(user_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::UNAUTHORIZED, "Invalid two-factor challenge".to_string()))?;
+    if user.status != UserStatus::Active {
+        return Err((StatusCode::FORBIDDEN, "Account is not active".to_string()));
+    }
+
+    let ok = totp::verify_and_consume(
+        &pool,
+        user.id,
+        payload.code.as_deref(),
+        payload.recovery_code.as_deref(),
+    ).await?;
+    if !ok {
+        for (scope, key) in &throttle_keys {
+            if let Err(e) = throttle::record_failure(&pool, scope, key).await {
+                tracing::error!("Failed to record 2FA failure for {} {}: {}", scope, key, e);
             }
         }
+        return Err((StatusCode::UNAUTHORIZED, "Invalid two-factor code".to_string()));
+    }
+
+    throttle::clear(&pool, throttle::SCOPE_ACCOUNT, &account_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    start_session(&pool, &headers, user, challenge.remember_me, &challenge.login_method).await
+}
+
+/// Start (or restart) TOTP enrollment for the calling admin. Nothing is
+/// enforced until the first code is confirmed at `/auth/2fa/verify`.
+pub async fn two_factor_setup_handler(
+    headers: HeaderMap,
+    State(pool): State<PgPool>,
+) -> Result<Json<TwoFactorSetupResponse>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    impersonation::forbid_while_impersonating(&claims)?;
+    api_keys::forbid_api_key(&claims)?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID in token".to_string()))?;
+
+    // Checked against the table rather than claims: an admin held back by
+    // the 2FA policy has no admin claims until enrollment completes.
+    let is_admin = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM admin_roles WHERE user_id = $1 AND is_enabled = TRUE)",
+    )
+    .bind(user_id)
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !is_admin {
+        return Err((StatusCode::FORBIDDEN, "Two-factor authentication is available to admins only".to_string()));
+    }
+
+    let secret = totp::generate_secret();
+    let result = sqlx::query(r#"
+        INSERT INTO user_totp (user_id, secret)
+        VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE
+            SET secret = EXCLUDED.secret, last_used_step = NULL, created_at = NOW()
+            WHERE user_totp.enabled_at IS NULL
+    "#)
+    .bind(user_id)
+    .bind(&secret)
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if result.rows_affected() == 0 {
+        return Err((StatusCode::CONFLICT, "Two-factor authentication is already enabled".to_string()));
+    }
+
+    Ok(Json(TwoFactorSetupResponse {
+        otpauth_uri: totp::otpauth_uri(&secret, &claims.email),
+        secret,
+    }))
+}
+
+/// Confirm enrollment with a first code. Enables 2FA, returns fresh
+/// recovery codes (shown once) and re-issues the access token so admin
+/// claims withheld by the 2FA policy apply immediately.
+pub async fn two_factor_verify_handler(
+    headers: HeaderMap,
+    State(pool): State<PgPool>,
+    Json(payload): Json<TwoFactorCodeInput>,
+) -> Result<Response, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    impersonation::forbid_while_impersonating(&claims)?;
+    api_keys::forbid_api_key(&claims)?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID in token".to_string()))?;
+
+    let secret = sqlx::query_scalar::<_, String>("SELECT secret FROM user_totp WHERE user_id = $1 AND enabled_at IS NULL")
+        .bind(user_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::BAD_REQUEST, "No pending two-factor setup".to_string()))?;
+
+    let step = totp::verify_code(&secret, &payload.code, Utc::now().timestamp())
+        .ok_or((StatusCode::BAD_REQUEST, "Invalid two-factor code".to_string()))?;
+
+    let recovery_codes = totp::generate_recovery_codes();
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let enabled = sqlx::query("UPDATE user_totp SET enabled_at = NOW(), last_used_step = $2 WHERE user_id = $1 AND enabled_at IS NULL")
+        .bind(user_id)
+        .bind(step)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if enabled.rows_affected() == 0 {
+        return Err((StatusCode::CONFLICT, "Two-factor authentication is already enabled".to_string()));
+    }
+
+    sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let hashes: Vec<String> = recovery_codes.iter().map(|c| totp::hash_recovery_code(c)).collect();
+    sqlx::query("INSERT INTO user_recovery_codes (user_id, code_hash) SELECT $1, UNNEST($2::text[])")
+        .bind(user_id)
+        .bind(&hashes)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let admin_role = sqlx::query_as::<_, AdminRole>("SELECT * FROM admin_roles WHERE user_id = $1 AND is_enabled = TRUE")
+        .bind(user_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let session_expires_at = sqlx::query_scalar::<_, chrono::DateTime<Utc>>("SELECT expires_at FROM sessions WHERE id = $1")
+        .bind(&claims.session_id)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let access_expires_at = tokens::access_token_expiry(Utc::now(), session_expires_at);
+    let new_claims = tokens::claims_for_user(&user, admin_role.as_ref(), &claims.session_id, access_expires_at);
+    let token = tokens::sign_access_token(&new_claims)?;
+    let cookie = build_cookie(ACCESS_COOKIE, &token, "/", (access_expires_at - Utc::now()).num_seconds());
+
+    let body = TwoFactorEnabledResponse { recovery_codes, token, access_expires_at };
+    Ok((StatusCode::OK, [(SET_COOKIE, cookie)], Json(body)).into_response())
+}
+
+// ─── OpenID Connect ───────────────────────────────────────────────────────
+
+/// First entry of `FRONTEND_URL`, where browser-facing SSO redirects land.
+fn frontend_url() -> String {
+    std::env::var("FRONTEND_URL")
+        .unwrap_or_else(|_| "http://localhost:5173".to_string())
+        .split(',')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// Start SSO: remember state/nonce/PKCE verifier and send the browser to
+/// the IdP's authorization endpoint.
+pub async fn oidc_login_handler(
+    State(pool): State<PgPool>,
+    Query(query): Query<OidcLoginQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let config = oidc::OidcConfig::from_env()
+        .ok_or((StatusCode::NOT_FOUND, "Single sign-on is not configured".to_string()))?;
+    let discovery = oidc::discovery(&config).await?;
+
+    let state = oidc::random_token();
+    let nonce = oidc::random_token();
+    let code_verifier = oidc::random_token();
+
+    sqlx::query("DELETE FROM oidc_login_states WHERE created_at < NOW() - INTERVAL '10 minutes'")
+        .execute(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    sqlx::query("INSERT INTO oidc_login_states (state, code_verifier, nonce, return_to) VALUES ($1, $2, $3, $4)")
+        .bind(&state)
+        .bind(&code_verifier)
+        .bind(&nonce)
+        .bind(oidc::sanitize_return_to(query.return_to.as_deref()))
+        .execute(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let url = oidc::authorization_url(&config, &discovery, &state, &nonce, &code_verifier)?;
+    Ok(Redirect::to(&url).into_response())
+}
+
+/// IdP redirect target. Failures are sent back to the frontend login page
+/// as `?sso_error=...` since this is a browser navigation, not an API call.
+pub async fn oidc_callback_handler(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Response {
+    match oidc_callback(&pool, &headers, query).await {
+        Ok(response) => response,
+        Err((status, message)) => {
+            tracing::warn!("OIDC sign-in failed ({}): {}", status, message);
+            let reason = match status {
+                StatusCode::NOT_FOUND => "no_account",
+                StatusCode::FORBIDDEN => "inactive",
+                StatusCode::CONFLICT => "ambiguous_account",
+                _ => "failed",
+            };
+            Redirect::to(&format!("{}/login?sso_error={}", frontend_url(), reason)).into_response()
+        }
+    }
+}
+
+async fn oidc_callback(
+    pool: &PgPool,
+    headers: &HeaderMap,
+    query: OidcCallbackQuery,
+) -> Result<Response, (StatusCode, String)> {
+    let config = oidc::OidcConfig::from_env()
+        .ok_or((StatusCode::NOT_FOUND, "Single sign-on is not configured".to_string()))?;
+    if let Some(error) = query.error {
+        return Err((StatusCode::BAD_REQUEST, format!("IdP returned error: {}", error)));
+    }
+    let (Some(code), Some(state)) = (query.code, query.state) else {
+        return Err((StatusCode::BAD_REQUEST, "Missing code or state".to_string()));
+    };
+
+    #[derive(sqlx::FromRow)]
+    struct StateRow {
+        code_verifier: String,
+        nonce: String,
+        return_to: String,
+    }
+    // Deleting on read makes each state single-use.
+    let pending = sqlx::query_as::<_, StateRow>(r#"
+        DELETE FROM oidc_login_states
+        WHERE state = $1 AND created_at > NOW() - INTERVAL '10 minutes'
+        RETURNING code_verifier, nonce, return_to
+    "#)
+    .bind(&state)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::BAD_REQUEST, "Unknown or expired login state".to_string()))?;
+
+    let claims = oidc::exchange_code(&config, &code, &pending.code_verifier, &pending.nonce).await?;
+    let identity = oidc::identity_from_claims(&config, &claims)
+        .ok_or((StatusCode::UNAUTHORIZED, "ID token has no subject".to_string()))?;
+
+    let user = resolve_oidc_user(pool, &config, &identity).await?;
+    if user.status != UserStatus::Active {
+        return Err((StatusCode::FORBIDDEN, "Account is not active".to_string()));
+    }
+
+    // SSO replaces the password step only; accounts with TOTP still finish
+    // at /auth/2fa/login, from the login page's code step. The challenge
+    // travels in the fragment so it never reaches a server log.
+    if totp::is_enabled(pool, user.id).await? {
+        let (challenge_token, _) = totp::sign_challenge(user.id, false, "oidc")?;
+        let mut url = reqwest::Url::parse(&format!("{}/login", frontend_url()))
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Invalid FRONTEND_URL: {}", e)))?;
+        if pending.return_to != "/" {
+            url.query_pairs_mut().append_pair("redirectTo", &pending.return_to);
+        }
+        url.set_fragment(Some(&format!("challenge_token={}", challenge_token)));
+        return Ok(Redirect::to(url.as_str()).into_response());
     }
 
-    Err((StatusCode::UNAUTHORIZED, "Missing authentication".to_string()))
+    let (cookies, _) = create_session(pool, headers, user, false, "oidc").await?;
+    Ok((cookies, Redirect::to(&format!("{}{}", frontend_url(), pending.return_to))).into_response())
+}
+
+/// Find the local account for an IdP identity: an existing link first, then
+/// a match on student number or verified email (which is then linked), and
+/// finally — if `OIDC_AUTO_PROVISION` is on — a newly created student.
+async fn resolve_oidc_user(
+    pool: &PgPool,
+    config: &oidc::OidcConfig,
+    identity: &oidc::Identity,
+) -> Result<User, (StatusCode, String)> {
+    let linked = sqlx::query_as::<_, User>(r#"
+        SELECT u.* FROM user_identities i
+        JOIN users u ON u.id = i.user_id
+        WHERE i.issuer = $1 AND i.subject = $2 AND u.deleted_at IS NULL
+    "#)
+    .bind(&config.issuer)
+    .bind(&identity.subject)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let user = match linked {
+        Some(user) => user,
+        None => {
+            let mut matches = sqlx::query_as::<_, User>(r#"
+                SELECT * FROM users
+                WHERE deleted_at IS NULL
+                  AND (($1::text IS NOT NULL AND student_id = $1) OR ($2::text IS NOT NULL AND LOWER(email) = $2))
+                LIMIT 2
+            "#)
+            .bind(&identity.student_id)
+            .bind(&identity.email)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+            if matches.len() > 1 {
+                return Err((StatusCode::CONFLICT, "IdP identity matches more than one account".to_string()));
+            }
+            match matches.pop() {
+                Some(user) => user,
+                None if config.auto_provision => provision_oidc_user(pool, identity).await?,
+                None => return Err((StatusCode::NOT_FOUND, "No account matches this university identity".to_string())),
+            }
+        }
+    };
+
+    sqlx::query(r#"
+        INSERT INTO user_identities (issuer, subject, user_id, email, last_login_at)
+        VALUES ($1, $2, $3, $4, NOW())
+        ON CONFLICT (issuer, subject) DO UPDATE SET email = EXCLUDED.email, last_login_at = NOW()
+    "#)
+    .bind(&config.issuer)
+    .bind(&identity.subject)
+    .bind(user.id)
+    .bind(&identity.email)
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(user)
 }
 
-fn build_cookie(token: &str, remember_me: bool, is_production: bool) -> String {
-    let max_age = if remember_me { 30 * 24 * 60 * 60 } else { 7 * 24 * 60 * 60 };
-    let secure = if is_production { "; Secure" } else { "" };
-    let domain = std::env::var("COOKIE_DOMAIN").unwrap_or_default();
-    let domain_part = if domain.is_empty() { String::new() } else { format!("; Domain={}", domain) };
-    format!(
-        "session_token={}; HttpOnly{}; SameSite=Lax; Path=/; Max-Age={}{}",
-        token, secure, max_age, domain_part
+async fn provision_oidc_user(pool: &PgPool, identity: &oidc::Identity) -> Result<User, (StatusCode, String)> {
+    let (Some(student_id), Some(email), Some(first_name)) =
+        (&identity.student_id, &identity.email, &identity.first_name)
+    else {
+        return Err((StatusCode::NOT_FOUND, "IdP did not supply student number, email and name".to_string()));
+    };
+
+    // Only an unambiguous match is used; otherwise the student picks their
+    // department later from their profile.
+    let department_id = match &identity.department {
+        Some(dept) => {
+            let ids = sqlx::query_scalar::<_, Uuid>(
+                "SELECT id FROM departments WHERE status = TRUE AND (code = $1 OR name = $1) LIMIT 2",
+            )
+            .bind(dept)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            if ids.len() == 1 { Some(ids[0]) } else { None }
+        }
+        None => None,
+    };
+
+    // The IdP vouches for the identity, not for membership: organizations
+    // that vet registrations vet SSO sign-ups the same way.
+    let pending_approval = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT o.require_registration_approval
+        FROM departments d
+        JOIN organizations o ON o.id = d.organization_id
+        WHERE d.id = $1
+        "#,
     )
+    .bind(department_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .unwrap_or(false);
+    let status = if pending_approval { UserStatus::Pending } else { UserStatus::Active };
+
+    // SSO users never type this password; "forgot password" can set a real one.
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = Argon2::default()
+        .hash_password(oidc::random_token().as_bytes(), &salt)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to hash password: {}", e)))?
+        .to_string();
+
+    sqlx::query_as::<_, User>(r#"
+        INSERT INTO users (student_id, email, password_hash, first_name, last_name, status, department_id, email_verified_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, NOW())
+        RETURNING *
+    "#)
+    .bind(student_id)
+    .bind(email)
+    .bind(&password_hash)
+    .bind(first_name)
+    .bind(identity.last_name.clone().unwrap_or_default())
+    .bind(&status)
+    .bind(department_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| match e {
+        sqlx::Error::Database(ref db_err) if db_err.is_unique_violation() => {
+            (StatusCode::CONFLICT, "Student ID or Email already exists".to_string())
+        }
+        e => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to provision user: {}", e)),
+    })
 }
 
-// ─── Handlers ──────────────────────────────────────────────────────────────
+// ─── Impersonation ───────────────────────────────────────────────────────
 
-pub async fn login_handler(
+/// Super admin only: open a time-boxed session as `user_id` and switch the
+/// caller's cookies to it. The admin's own session stays alive and is
+/// resumed by `/auth/impersonation/stop`. Other admins can't be targeted.
+pub async fn start_impersonation_handler(
+    headers: HeaderMap,
     State(pool): State<PgPool>,
-    Json(payload): Json<AuthInput>,
+    Path(user_id): Path<Uuid>,
 ) -> Result<Response, (StatusCode, String)> {
-    let user_query_result = if let Some(email) = &payload.email {
-        sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = $1")
-            .bind(email)
-            .fetch_optional(&pool)
-            .await
-    } else if let Some(student_id) = &payload.student_id {
-        sqlx::query_as::<_, User>("SELECT * FROM users WHERE student_id = $1")
-            .bind(student_id)
-            .fetch_optional(&pool)
-            .await
-    } else {
-        return Err((StatusCode::BAD_REQUEST, "Email or Student ID required".to_string()));
-    };
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    if !matches!(claims.admin_level, Some(AdminLevel::SuperAdmin)) {
+        return Err((StatusCode::FORBIDDEN, "Super admin access required".to_string()));
+    }
+    let admin_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID in token".to_string()))?;
+    if admin_id == user_id {
+        return Err((StatusCode::BAD_REQUEST, "Cannot impersonate yourself".to_string()));
+    }
 
-    let user = user_query_result
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL")
+        .bind(user_id)
+        .fetch_optional(&pool)
+        .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
-        .ok_or((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()))?;
-
-    match user.status {
-        UserStatus::Active => {},
-        _ => return Err((StatusCode::FORBIDDEN, "Account is not active".to_string())),
+        .ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))?;
+    if user.status != UserStatus::Active {
+        return Err((StatusCode::CONFLICT, "Cannot impersonate an inactive account".to_string()));
     }
 
-    let parsed_hash = PasswordHash::new(&user.password_hash)
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Invalid password hash stored".to_string()))?;
-    if Argon2::default().verify_password(payload.password.as_bytes(), &parsed_hash).is_err() {
-        return Err((StatusCode::UNAUTHORIZED, "Invalid credentials".to_string()));
+    let target_is_admin: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM admin_roles WHERE user_id = $1 AND is_enabled = TRUE)",
+    )
+    .bind(user_id)
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if target_is_admin {
+        return Err((StatusCode::FORBIDDEN, "Cannot impersonate another admin".to_string()));
     }
 
-    let admin_role = sqlx::query_as::<_, AdminRole>("SELECT * FROM admin_roles WHERE user_id = $1 AND is_enabled = TRUE")
-        .bind(user.id)
+    let target_org: Option<Uuid> = sqlx::query_scalar("SELECT organization_id FROM departments WHERE id = $1")
+        .bind(user.department_id)
         .fetch_optional(&pool)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    let admin_level = admin_role.as_ref().map(|r| r.admin_level.clone());
     let session_id = Uuid::new_v4().to_string();
-    let remember_me = payload.remember_me.unwrap_or(false);
-    let expiration_duration = if remember_me { Duration::days(30) } else { Duration::days(7) };
-    let expires_at = Utc::now().checked_add_signed(expiration_duration).expect("valid timestamp");
+    let expires_at = Utc::now() + Duration::minutes(impersonation::IMPERSONATION_MINUTES);
+    let (refresh_token, refresh_hash) = tokens::generate_refresh_token();
 
-    // Session insert and last_login update are independent — run them
-    // concurrently to save one round-trip on every login.
-    let session_insert = sqlx::query(
-        "INSERT INTO sessions (id, user_id, expires_at, created_at, last_accessed, is_active, login_method) VALUES ($1, $2, $3, NOW(), NOW(), TRUE, 'password')",
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    sqlx::query(
+        r#"
+        WITH s AS (
+            INSERT INTO sessions (
+                id, user_id, expires_at, created_at, last_accessed, is_active, login_method,
+                ip_address, user_agent, impersonator_id, impersonator_session_id
+            )
+            VALUES ($1, $2, $3, NOW(), NOW(), TRUE, $4, $5::inet, $6, $7, $8)
+            RETURNING id
+        )
+        INSERT INTO session_refresh_tokens (token_hash, session_id)
+        SELECT $9, id FROM s
+        "#,
     )
     .bind(&session_id)
     .bind(user.id)
     .bind(expires_at)
-    .execute(&pool);
-
-    let last_login_update = sqlx::query("UPDATE users SET last_login_at = NOW() WHERE id = $1")
-        .bind(user.id)
-        .execute(&pool);
-
-    let (session_res, last_login_res) = tokio::join!(session_insert, last_login_update);
-    session_res.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create session: {}", e)))?;
-    last_login_res.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update user stats: {}", e)))?;
+    .bind(impersonation::LOGIN_METHOD)
+    .bind(client_ip(&headers))
+    .bind(user_agent(&headers))
+    .bind(admin_id)
+    .bind(&claims.session_id)
+    .bind(&refresh_hash)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create session: {}", e)))?;
 
-    let secret = std::env::var("JWT_SECRET")
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "JWT_SECRET not set".to_string()))?;
-
-    let is_admin = admin_role.is_some();
-    let organization_id = admin_role.as_ref().and_then(|r| r.organization_id);
-
-    let claims = Claims {
-        sub: user.id.to_string(),
-        student_id: user.student_id.clone(),
-        email: user.email.clone(),
-        admin_level: admin_level.clone(),
-        session_id: session_id.clone(),
-        exp: expires_at.timestamp() as usize,
-        iat: Utc::now().timestamp() as usize,
-        first_name: user.first_name.clone(),
-        last_name: user.last_name.clone(),
-        department_id: user.department_id,
-        is_admin,
-        organization_id,
-    };
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("impersonation.start", "user", user.id)
+            .organization(target_org)
+            .after(Some(serde_json::json!({
+                "session_id": session_id,
+                "expires_at": expires_at,
+            }))),
+    )
+    .await?;
 
-    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Token creation failed: {}", e)))?;
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    let is_production = std::env::var("NODE_ENV").unwrap_or_default() == "production";
-    let cookie = build_cookie(&token, remember_me, is_production);
+    let access_expires_at = tokens::access_token_expiry(Utc::now(), expires_at);
+    let mut user_claims = tokens::claims_for_user(&user, None, &session_id, access_expires_at);
+    user_claims.impersonator_id = Some(admin_id);
+    let token = tokens::sign_access_token(&user_claims)?;
+    let cookies = auth_cookies(&token, access_expires_at, &refresh_token, expires_at);
 
     let body = AuthResponse {
-        token: token.clone(),
+        token,
+        access_expires_at,
         user: UserResponse {
             id: user.id,
             student_id: user.student_id,
@@ -163,37 +1085,209 @@ pub async fn login_handler(
             first_name: user.first_name,
             last_name: user.last_name,
             prefix: user.prefix,
-            admin_role,
-            organization_id: None,
+            admin_role: None,
+            organization_id: target_org,
             organization_name: None,
             department_id: user.department_id,
             department_name: None,
             session_id,
             expires_at,
-        }
+            two_factor_setup_required: false,
+            impersonator_id: Some(admin_id),
+        },
     };
+    Ok((StatusCode::OK, cookies, Json(body)).into_response())
+}
 
-    let response = (StatusCode::OK, [(SET_COOKIE, cookie)], Json(body)).into_response();
-    Ok(response)
+async fn record_impersonation_stop(
+    pool: &PgPool,
+    claims: &Claims,
+    headers: &HeaderMap,
+) -> Result<(), (StatusCode, String)> {
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID in token".to_string()))?;
+    let target_org: Option<Uuid> = sqlx::query_scalar(
+        "SELECT d.organization_id FROM users u JOIN departments d ON d.id = u.department_id WHERE u.id = $1",
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    audit::record(
+        pool,
+        claims,
+        headers,
+        AuditEntry::new("impersonation.stop", "user", user_id)
+            .organization(target_org)
+            .before(Some(serde_json::json!({ "session_id": claims.session_id }))),
+    )
+    .await
 }
 
-pub async fn logout_handler(
+/// End the current impersonation session and, when the admin's own
+/// session is still valid, switch the cookies back to it. Otherwise the
+/// cookies are cleared and the admin signs in again.
+pub async fn stop_impersonation_handler(
     headers: HeaderMap,
     State(pool): State<PgPool>,
 ) -> Result<Response, (StatusCode, String)> {
-    if let Ok(claims) = get_claims_from_headers(&headers) {
-        let _ = sqlx::query("UPDATE sessions SET is_active = FALSE WHERE id = $1")
-            .bind(&claims.session_id)
-            .execute(&pool)
-            .await;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    let admin_id = claims.impersonator_id
+        .ok_or((StatusCode::BAD_REQUEST, "Not impersonating".to_string()))?;
+
+    let admin_session_id: Option<String> = sqlx::query_scalar(
+        "UPDATE sessions SET is_active = FALSE WHERE id = $1 RETURNING impersonator_session_id",
+    )
+    .bind(&claims.session_id)
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    session::invalidate_session(&claims.session_id);
+    record_impersonation_stop(&pool, &claims, &headers).await?;
+
+    let admin_session_expires_at: Option<chrono::DateTime<Utc>> = sqlx::query_scalar(
+        "SELECT expires_at FROM sessions WHERE id = $1 AND user_id = $2 AND is_active = TRUE AND expires_at > NOW()",
+    )
+    .bind(&admin_session_id)
+    .bind(admin_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let admin = sqlx::query_as::<_, User>(
+        "SELECT * FROM users WHERE id = $1 AND deleted_at IS NULL AND status = 'active'",
+    )
+    .bind(admin_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let (Some(admin_session_id), Some(session_expires_at), Some(admin)) =
+        (admin_session_id, admin_session_expires_at, admin)
+    else {
+        return Ok((
+            StatusCode::OK,
+            AppendHeaders([
+                (SET_COOKIE, build_cookie(ACCESS_COOKIE, "", "/", 0)),
+                (SET_COOKIE, build_cookie(REFRESH_COOKIE, "", "/auth", 0)),
+            ]),
+            Json(serde_json::json!({ "message": "Impersonation ended; please sign in again" })),
+        ).into_response());
+    };
+
+    let admin_role = sqlx::query_as::<_, AdminRole>("SELECT * FROM admin_roles WHERE user_id = $1 AND is_enabled = TRUE")
+        .bind(admin.id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let (admin_role, _) = totp::effective_admin_role(&pool, admin.id, admin_role).await?;
+
+    // The admin's refresh cookie was replaced when impersonation started,
+    // so the resumed session gets a fresh refresh token.
+    let (refresh_token, refresh_hash) = tokens::generate_refresh_token();
+    sqlx::query("INSERT INTO session_refresh_tokens (token_hash, session_id) VALUES ($1, $2)")
+        .bind(&refresh_hash)
+        .bind(&admin_session_id)
+        .execute(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let access_expires_at = tokens::access_token_expiry(Utc::now(), session_expires_at);
+    let admin_claims = tokens::claims_for_user(&admin, admin_role.as_ref(), &admin_session_id, access_expires_at);
+    let token = tokens::sign_access_token(&admin_claims)?;
+    let cookies = auth_cookies(&token, access_expires_at, &refresh_token, session_expires_at);
+
+    let body = serde_json::json!({
+        "token": token,
+        "access_expires_at": access_expires_at,
+        "expires_at": session_expires_at,
+    });
+    Ok((StatusCode::OK, cookies, Json(body)).into_response())
+}
+
+/// List the caller's active sessions, newest activity first, flagging the
+/// one this request was made with.
+pub async fn list_sessions_handler(
+    headers: HeaderMap,
+    State(pool): State<PgPool>,
+) -> Result<Json<Vec<SessionInfo>>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID in token".to_string()))?;
+
+    let sessions = sqlx::query_as::<_, SessionInfo>(r#"
+        SELECT id, host(ip_address) AS ip_address, user_agent, login_method,
+               created_at, last_accessed, expires_at, (id = $2) AS is_current
+        FROM sessions
+        WHERE user_id = $1 AND is_active = TRUE AND expires_at > NOW()
+        ORDER BY last_accessed DESC
+    "#)
+    .bind(user_id)
+    .bind(&claims.session_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(sessions))
+}
+
+/// Revoke one of the caller's own sessions. Revoking the current session
+/// is allowed and behaves like logout minus the cookie clearing.
+pub async fn revoke_session_handler(
+    headers: HeaderMap,
+    State(pool): State<PgPool>,
+    Path(session_id): Path<String>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    impersonation::forbid_while_impersonating(&claims)?;
+    api_keys::forbid_api_key(&claims)?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID in token".to_string()))?;
+
+    let result = sqlx::query(
+        "UPDATE sessions SET is_active = FALSE WHERE id = $1 AND user_id = $2 AND is_active = TRUE",
+    )
+    .bind(&session_id)
+    .bind(user_id)
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err((StatusCode::NOT_FOUND, "Session not found".to_string()));
     }
-    let clear_cookie = "session_token=; HttpOnly; SameSite=Lax; Path=/; Max-Age=0";
-    let response = (
-        StatusCode::OK,
-        [(SET_COOKIE, clear_cookie)],
-        Json(serde_json::json!({ "message": "Logged out successfully" })),
-    ).into_response();
-    Ok(response)
+    session::invalidate_session(&session_id);
+
+    Ok(Json(serde_json::json!({ "message": "Session revoked" })))
+}
+
+/// Revoke every session of the caller except the one making the request.
+pub async fn revoke_other_sessions_handler(
+    headers: HeaderMap,
+    State(pool): State<PgPool>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    impersonation::forbid_while_impersonating(&claims)?;
+    api_keys::forbid_api_key(&claims)?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID in token".to_string()))?;
+
+    let revoked: Vec<String> = sqlx::query_scalar(
+        "UPDATE sessions SET is_active = FALSE WHERE user_id = $1 AND id <> $2 AND is_active = TRUE RETURNING id",
+    )
+    .bind(user_id)
+    .bind(&claims.session_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    for id in &revoked {
+        session::invalidate_session(id);
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "Other sessions revoked",
+        "revoked": revoked.len()
+    })))
 }
 
 /// Flat row produced by the single LEFT-JOIN query in `me_handler`.
@@ -256,6 +1350,8 @@ fn me_row_to_response(row: MeRow, session_id: String, expires_at: chrono::DateTi
         department_name: row.department_name,
         session_id,
         expires_at,
+        two_factor_setup_required: false,
+        impersonator_id: None,
     }
 }
 
@@ -263,7 +1359,7 @@ pub async fn me_handler(
     headers: HeaderMap,
     State(pool): State<PgPool>,
 ) -> Result<Json<UserResponse>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
     let user_id = Uuid::parse_str(&claims.sub)
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID in token".to_string()))?;
 
@@ -301,13 +1397,40 @@ pub async fn me_handler(
     let expires_at = chrono::DateTime::from_timestamp(claims.exp as i64, 0)
         .unwrap_or_else(Utc::now);
 
-    Ok(Json(me_row_to_response(row, claims.session_id, expires_at)))
+    // An enabled admin_roles row without admin claims means the session was
+    // issued under a 2FA policy the admin hasn't satisfied yet.
+    let two_factor_setup_required = row.ar_id.is_some() && !claims.is_admin;
+    let mut response = me_row_to_response(row, claims.session_id, expires_at);
+    response.two_factor_setup_required = two_factor_setup_required;
+    response.impersonator_id = claims.impersonator_id;
+    Ok(Json(response))
+}
+
+/// Queue a fresh verification link. Runs on the caller's transaction, which
+/// has already stamped `email_verification_sent_at`, so the mail is only
+/// sent if that write commits.
+async fn send_verification_email(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    email: &str,
+) -> Result<(), (StatusCode, String)> {
+    let (token, _) = email_verification::sign_token(user_id, email)?;
+    let link = format!("{}/verify-email?token={}", frontend_url(), token);
+    outbox::enqueue(
+        &mut *conn,
+        email,
+        EmailTemplate::VerifyEmail { link: &link, expires_hours: email_verification::TOKEN_TTL_HOURS },
+    )
+    .await?;
+    Ok(())
 }
 
 pub async fn register_handler(
     State(pool): State<PgPool>,
     Json(payload): Json<RegisterInput>,
 ) -> Result<Json<RegisterResponse>, (StatusCode, String)> {
+    password_policy::enforce(&payload.password, &[&payload.student_id, &payload.email]).await?;
+
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
     let password_hash = argon2.hash_password(payload.password.as_bytes(), &salt)
@@ -316,10 +1439,28 @@ pub async fn register_handler(
 
     let user_id = Uuid::new_v4();
 
+    let pending_approval = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT o.require_registration_approval
+        FROM departments d
+        JOIN organizations o ON o.id = d.organization_id
+        WHERE d.id = $1
+        "#,
+    )
+    .bind(payload.department_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .unwrap_or(false);
+    let status = if pending_approval { UserStatus::Pending } else { UserStatus::Active };
+
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
     let result = sqlx::query(
         r#"
-        INSERT INTO users (id, student_id, email, password_hash, prefix, first_name, last_name, phone, status, department_id)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, 'active'::user_status, $9)
+        INSERT INTO users (id, student_id, email, password_hash, prefix, first_name, last_name, phone, status, department_id, email_verification_sent_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, NOW())
         "#,
     )
     .bind(user_id)
@@ -330,15 +1471,29 @@ pub async fn register_handler(
     .bind(&payload.first_name)
     .bind(&payload.last_name)
     .bind(&payload.phone)
+    .bind(&status)
     .bind(payload.department_id)
-    .execute(&pool)
+    .execute(&mut *tx)
     .await;
 
     match result {
-        Ok(_) => Ok(Json(RegisterResponse {
-            user_id,
-            message: "User registered successfully".to_string(),
-        })),
+        Ok(_) => {
+            send_verification_email(&mut tx, user_id, &payload.email).await?;
+            tx.commit()
+                .await
+                .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            outbox::wake();
+            let message = if pending_approval {
+                "User registered; check your email to verify the account, then wait for an administrator to approve it"
+            } else {
+                "User registered; check your email to verify the account"
+            };
+            Ok(Json(RegisterResponse {
+                user_id,
+                message: message.to_string(),
+                pending_approval,
+            }))
+        }
         Err(sqlx::Error::Database(db_err)) => {
             if db_err.is_unique_violation() {
                 Err((StatusCode::CONFLICT, "Student ID or Email already exists".to_string()))
@@ -350,6 +1505,99 @@ pub async fn register_handler(
     }
 }
 
+/// Mark the address in a verification link as verified. Only succeeds while
+/// the account still has that address.
+pub async fn verify_email_handler(
+    State(pool): State<PgPool>,
+    Json(payload): Json<VerifyEmailInput>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let (user_id, email) = email_verification::verify_token(&payload.token)?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE users SET email_verified_at = COALESCE(email_verified_at, NOW()), updated_at = NOW()
+        WHERE id = $1 AND LOWER(email) = $2 AND deleted_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(&email)
+    .execute(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if result.rows_affected() == 0 {
+        return Err((StatusCode::BAD_REQUEST, "Invalid or expired verification link".to_string()));
+    }
+
+    Ok(Json(serde_json::json!({ "message": "Email verified successfully" })))
+}
+
+/// Send another verification link. Answers the same whether or not the
+/// address belongs to an unverified account, so it can't be used to probe.
+pub async fn resend_verification_handler(
+    State(pool): State<PgPool>,
+    Json(payload): Json<ResendVerificationInput>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let email = payload.email.trim().to_lowercase();
+
+    #[derive(sqlx::FromRow)]
+    struct PendingRow {
+        id: Uuid,
+        email: String,
+    }
+
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    // Claiming the cooldown and reading the row in one UPDATE means
+    // concurrent requests serialize on the row lock: only the first sees the
+    // cooldown expired, so a burst sends at most one mail.
+    let pending = sqlx::query_as::<_, PendingRow>(
+        r#"
+        UPDATE users SET email_verification_sent_at = NOW()
+        WHERE LOWER(email) = $1
+          AND email_verified_at IS NULL
+          AND deleted_at IS NULL
+          AND (email_verification_sent_at IS NULL
+               OR email_verification_sent_at < NOW() - make_interval(secs => $2))
+        RETURNING id, email
+        "#,
+    )
+    .bind(&email)
+    .bind(email_verification::RESEND_COOLDOWN_SECS as f64)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    if let Some(user) = pending {
+        send_verification_email(&mut tx, user.id, &user.email).await?;
+        tx.commit()
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        outbox::wake();
+    }
+
+    Ok(Json(serde_json::json!({
+        "message": "If this email belongs to an unverified account, a new verification link has been sent."
+    })))
+}
+
+/// Public signing keys (RFC 7517) so other services can verify our tokens.
+/// Verifiers may cache the set for five minutes.
+pub async fn jwks_handler() -> Result<Response, (StatusCode, String)> {
+    let jwks = keys::jwks()?;
+    Ok(([(CACHE_CONTROL, "public, max-age=300")], Json(jwks)).into_response())
+}
+
+/// The active password rules, so forms can show them before submitting.
+pub async fn password_policy_handler() -> Json<password_policy::PasswordPolicy> {
+    Json(password_policy::PasswordPolicy::from_env())
+}
+
+/// Lifetime of a password-reset link.
+const RESET_TOKEN_MINUTES: i64 = 30;
+
 pub async fn forgot_password_handler(
     State(pool): State<PgPool>,
     Json(payload): Json<ForgotPasswordInput>,
@@ -368,7 +1616,7 @@ pub async fn forgot_password_handler(
     };
 
     let token = Uuid::new_v4().to_string();
-    let expires_at = Utc::now() + Duration::minutes(30);
+    let expires_at = Utc::now() + Duration::minutes(RESET_TOKEN_MINUTES);
 
     sqlx::query("INSERT INTO password_reset_tokens (user_id, token, expires_at) VALUES ($1, $2, $3)")
         .bind(user.id)
@@ -378,59 +1626,13 @@ pub async fn forgot_password_handler(
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    let resend_api_key = std::env::var("RESEND_API_KEY").unwrap_or_default();
-    let frontend_url = std::env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:5173".to_string());
-
-    if resend_api_key.is_empty() {
-        tracing::warn!(
-            "RESEND_API_KEY is not set — skipping password reset email for {}",
-            email
-        );
-    } else {
-        let reset_link = format!("{}/reset-password?token={}", frontend_url, token);
-        let client = reqwest::Client::new();
-
-        let html_content = format!(
-            r#"<h2>คุณได้ขอรีเซ็ตรหัสผ่าน</h2>
-            <p>กรุณาคลิกที่ลิงก์ด้านล่างเพื่อตั้งรหัสผ่านใหม่สำหรับบัญชี Trackivity ของคุณ:</p>
-            <p><a href="{}">เปลี่ยนรหัสผ่าน</a></p>
-            <p>ลิงก์นี้จะหมดอายุภายใน 30 นาที หากคุณไม่ได้ส่งคำขอนี้ หรือเป็นความผิดพลาด คุณสามารถเพิกเฉยต่ออีเมลฉบับนี้ได้เลย</p>"#,
-            reset_link
-        );
-
-        let payload = serde_json::json!({
-            "from": "Trackivity <admin@utrackivity.com>",
-            "to": [&email],
-            "subject": "Trackivity - คำขอตั้งรหัสผ่านใหม่",
-            "html": html_content
-        });
-
-        match client
-            .post("https://api.resend.com/emails")
-            .bearer_auth(&resend_api_key)
-            .json(&payload)
-            .send()
-            .await
-        {
-            Ok(resp) => {
-                let status = resp.status();
-                if status.is_success() {
-                    tracing::info!("Password reset email queued for {}", email);
-                } else {
-                    let body = resp.text().await.unwrap_or_default();
-                    tracing::error!(
-                        "Resend API returned {} for {}: {}",
-                        status,
-                        email,
-                        body
-                    );
-                }
-            }
-            Err(e) => {
-                tracing::error!("Resend network error for {}: {}", email, e);
-            }
-        }
-    }
+    let reset_link = format!("{}/reset-password?token={}", frontend_url(), token);
+    outbox::enqueue(
+        &pool,
+        &email,
+        EmailTemplate::PasswordReset { link: &reset_link, expires_minutes: RESET_TOKEN_MINUTES },
+    )
+    .await?;
 
     Ok((StatusCode::OK, Json(serde_json::json!({ "message": "If this email exists, a password reset link has been sent." }))).into_response())
 }
@@ -460,6 +1662,14 @@ pub async fn reset_password_handler(
         return Err((StatusCode::BAD_REQUEST, "Token has expired".to_string()));
     }
 
+    let (student_id, email): (String, String) = sqlx::query_as("SELECT student_id, email FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::BAD_REQUEST, "Invalid or expired token".to_string()))?;
+    password_policy::enforce(&payload.new_password, &[&student_id, &email]).await?;
+
     let salt = SaltString::generate(&mut OsRng);
     let argon2 = Argon2::default();
     let password_hash = argon2.hash_password(payload.new_password.as_bytes(), &salt)
@@ -496,6 +1706,7 @@ pub async fn reset_password_handler(
     tx.commit()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    session::invalidate_user_sessions(user_id);
 
     Ok((StatusCode::OK, Json(serde_json::json!({ "message": "Password successfully reset" }))).into_response())
 }
@@ -615,3 +1826,4 @@ mod me_handler_tests {
         assert_eq!(r.department_name, None);
     }
 }
+
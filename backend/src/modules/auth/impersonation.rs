@@ -0,0 +1,44 @@
+//! Super-admin "act as user" sessions.
+//!
+//! An impersonation session is an ordinary session of the target user
+//! whose tokens also carry `Claims.impersonator_id`, so support staff see
+//! exactly what the student sees. It never carries admin claims, lasts at
+//! most `IMPERSONATION_MINUTES`, and handlers that would change the
+//! student's account or act on their behalf call `forbid_while_impersonating`.
+
+use axum::http::StatusCode;
+use super::models::Claims;
+
+pub const IMPERSONATION_MINUTES: i64 = 60;
+pub const LOGIN_METHOD: &str = "impersonation";
+
+pub fn forbid_while_impersonating(claims: &Claims) -> Result<(), (StatusCode, String)> {
+    if claims.impersonator_id.is_some() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "This action is not available while impersonating a user".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn only_impersonated_sessions_are_blocked() {
+        let mut claims: Claims = serde_json::from_value(serde_json::json!({
+            "sub": Uuid::nil(), "session_id": "s", "exp": 0, "iat": 0,
+            "student_id": "6501", "email": "s@example.com",
+            "first_name": "S", "last_name": "T", "department_id": null,
+            "is_admin": false, "admin_level": null, "organization_id": null,
+        }))
+        .unwrap();
+        assert!(forbid_while_impersonating(&claims).is_ok());
+
+        claims.impersonator_id = Some(Uuid::new_v4());
+        assert!(forbid_while_impersonating(&claims).is_err());
+    }
+}
@@ -0,0 +1,299 @@
+//! Signing keys for every JWT the backend issues.
+//!
+//! Two independent keyrings: `KeyUse::Session` signs access tokens, 2FA
+//! challenges and email-verification links; `KeyUse::Qr` signs check-in QR
+//! codes, so a leaked QR key can't mint sessions. Each ring is a directory
+//! of Ed25519 keys named by their `kid`:
+//!
+//! - `<kid>.pem` — PKCS#8 private key (`openssl genpkey -algorithm ed25519`);
+//!   signs if it is the active key, verifies either way.
+//! - `<kid>.pub.pem` — public key of a retired key; verifies only.
+//!
+//! The active key is `*_ACTIVE_KID`, or the greatest private `kid` when
+//! unset, so date-named files (`2026-10.pem`) rotate by adding a file.
+//! Tokens carry the `kid` header and are checked against the key it names,
+//! so a retired key keeps verifying until its tokens have expired.
+//!
+//! Without a key directory the ring falls back to HS256 with its secret —
+//! `JWT_SECRET` for sessions, `QR_JWT_SECRET` for QR codes (development).
+//! While the secret is set, HS256 tokens without a `kid` are also still
+//! accepted, which lets a deployment move to EdDSA without logging anyone
+//! out. The QR ring never borrows the session secret, and startup fails if
+//! both rings are given the same key material.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ed25519_dalek::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use jsonwebtoken::errors::{Error as JwtError, ErrorKind};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy)]
+pub enum KeyUse {
+    Session,
+    Qr,
+}
+
+impl KeyUse {
+    /// Key directory, active-kid and HS256 secret variables of the ring.
+    fn env(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            KeyUse::Session => ("JWT_KEYS_DIR", "JWT_ACTIVE_KID", "JWT_SECRET"),
+            KeyUse::Qr => ("QR_KEYS_DIR", "QR_ACTIVE_KID", "QR_JWT_SECRET"),
+        }
+    }
+}
+
+pub struct Keyring {
+    active: Option<(String, EncodingKey)>,
+    verifiers: HashMap<String, DecodingKey>,
+    legacy: Option<(EncodingKey, DecodingKey)>,
+    public: Vec<Jwk>,
+}
+
+fn env_opt(name: &str) -> Option<String> {
+    std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+fn public_jwk(kid: &str, key: &VerifyingKey) -> Jwk {
+    Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(KeyAlgorithm::EdDSA),
+            key_id: Some(kid.to_string()),
+            ..Default::default()
+        },
+        algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: URL_SAFE_NO_PAD.encode(key.as_bytes()),
+        }),
+    }
+}
+
+impl Keyring {
+    /// Build a ring from a key directory and/or a legacy HS256 secret.
+    pub fn load(dir: Option<&Path>, active_kid: Option<&str>, legacy_secret: Option<&str>) -> Result<Self, String> {
+        let mut private: Vec<(String, Vec<u8>)> = Vec::new();
+        let mut verifiers = HashMap::new();
+        let mut public = Vec::new();
+
+        if let Some(dir) = dir {
+            let entries = std::fs::read_dir(dir)
+                .map_err(|e| format!("Failed to read key directory {}: {}", dir.display(), e))?;
+            for entry in entries {
+                let path = entry.map_err(|e| e.to_string())?.path();
+                let Some(name) = path.file_name().and_then(|n| n.to_str()) else { continue };
+                let pem = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                let (kid, verifying) = if let Some(kid) = name.strip_suffix(".pub.pem") {
+                    let key = VerifyingKey::from_public_key_pem(&pem)
+                        .map_err(|e| format!("{} is not an Ed25519 public key: {}", name, e))?;
+                    (kid.to_string(), key)
+                } else if let Some(kid) = name.strip_suffix(".pem") {
+                    let key = SigningKey::from_pkcs8_pem(&pem)
+                        .map_err(|e| format!("{} is not an Ed25519 private key: {}", name, e))?;
+                    private.push((kid.to_string(), pem.into_bytes()));
+                    (kid.to_string(), key.verifying_key())
+                } else {
+                    continue;
+                };
+                let jwk = public_jwk(&kid, &verifying);
+                let decoding = DecodingKey::from_jwk(&jwk).map_err(|e| format!("Key {}: {}", kid, e))?;
+                if verifiers.insert(kid.clone(), decoding).is_some() {
+                    return Err(format!("Duplicate key id {} in {}", kid, dir.display()));
+                }
+                public.push(jwk);
+            }
+        }
+        public.sort_by(|a, b| a.common.key_id.cmp(&b.common.key_id));
+
+        let active = match active_kid {
+            Some(kid) => Some(
+                private
+                    .iter()
+                    .find(|(k, _)| k == kid)
+                    .ok_or(format!("Active key {} has no private key file", kid))?,
+            ),
+            None => private.iter().max_by(|a, b| a.0.cmp(&b.0)),
+        }
+        .map(|(kid, pem)| {
+            EncodingKey::from_ed_pem(pem).map(|key| (kid.clone(), key)).map_err(|e| e.to_string())
+        })
+        .transpose()?;
+
+        let legacy = legacy_secret
+            .map(|s| (EncodingKey::from_secret(s.as_bytes()), DecodingKey::from_secret(s.as_bytes())));
+        if active.is_none() && legacy.is_none() {
+            return Err("No signing key: add an Ed25519 key to the key directory or set the HS256 secret".to_string());
+        }
+        Ok(Self { active, verifiers, legacy, public })
+    }
+
+    fn from_env(key_use: KeyUse) -> Result<Self, String> {
+        let (dir_var, kid_var, secret_var) = key_use.env();
+        let dir = env_opt(dir_var);
+        let legacy = env_opt(secret_var);
+        Self::load(dir.as_deref().map(Path::new), env_opt(kid_var).as_deref(), legacy.as_deref())
+            .map_err(|e| format!("{:?} keys ({} / {}): {}", key_use, dir_var, secret_var, e))
+    }
+
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, JwtError> {
+        match (&self.active, &self.legacy) {
+            (Some((kid, key)), _) => {
+                let mut header = Header::new(Algorithm::EdDSA);
+                header.kid = Some(kid.clone());
+                encode(&header, claims, key)
+            }
+            (None, Some((key, _))) => encode(&Header::default(), claims, key),
+            (None, None) => Err(ErrorKind::InvalidKeyFormat.into()),
+        }
+    }
+
+    pub fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<TokenData<T>, JwtError> {
+        let header = decode_header(token)?;
+        match (header.alg, header.kid.as_deref(), &self.legacy) {
+            (Algorithm::EdDSA, Some(kid), _) => {
+                let key = self.verifiers.get(kid).ok_or(JwtError::from(ErrorKind::InvalidSignature))?;
+                decode(token, key, &Validation::new(Algorithm::EdDSA))
+            }
+            (Algorithm::HS256, None, Some((_, key))) => decode(token, key, &Validation::default()),
+            _ => Err(ErrorKind::InvalidAlgorithm.into()),
+        }
+    }
+}
+
+static SESSION_KEYS: OnceLock<Result<Keyring, String>> = OnceLock::new();
+static QR_KEYS: OnceLock<Result<Keyring, String>> = OnceLock::new();
+
+fn ring(key_use: KeyUse) -> Result<&'static Keyring, &'static str> {
+    let cell = match key_use {
+        KeyUse::Session => &SESSION_KEYS,
+        KeyUse::Qr => &QR_KEYS,
+    };
+    cell.get_or_init(|| Keyring::from_env(key_use)).as_ref().map_err(String::as_str)
+}
+
+/// True when the two rings would sign with the same key: the same key
+/// directory or the same HS256 secret.
+fn rings_share_keys(session: (Option<&str>, Option<&str>), qr: (Option<&str>, Option<&str>)) -> bool {
+    let dir = |d: Option<&str>| d.map(|d| d.trim_end_matches('/').to_string());
+    let same_dir = session.0.is_some() && dir(session.0) == dir(qr.0);
+    let same_secret = session.1.is_some() && session.1 == qr.1;
+    same_dir || same_secret
+}
+
+/// Load both rings at startup so a bad key directory fails the boot rather
+/// than the first login.
+pub fn init() -> Result<(), String> {
+    let material = |key_use: KeyUse| {
+        let (dir_var, _, secret_var) = key_use.env();
+        (env_opt(dir_var), env_opt(secret_var))
+    };
+    let (session, qr) = (material(KeyUse::Session), material(KeyUse::Qr));
+    if rings_share_keys(
+        (session.0.as_deref(), session.1.as_deref()),
+        (qr.0.as_deref(), qr.1.as_deref()),
+    ) {
+        return Err("QR tokens must use their own key: set QR_KEYS_DIR or QR_JWT_SECRET to something other than the session keys".to_string());
+    }
+    for key_use in [KeyUse::Session, KeyUse::Qr] {
+        let keys = ring(key_use)?;
+        match &keys.active {
+            Some((kid, _)) => tracing::info!("{:?} tokens signed with EdDSA key {}", key_use, kid),
+            None => tracing::warn!("{:?} tokens signed with legacy HS256 secret; configure a key directory", key_use),
+        }
+    }
+    Ok(())
+}
+
+pub fn sign<T: Serialize>(key_use: KeyUse, claims: &T) -> Result<String, (StatusCode, String)> {
+    ring(key_use)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .sign(claims)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Token creation failed: {}", e)))
+}
+
+pub fn verify<T: DeserializeOwned>(key_use: KeyUse, token: &str) -> Result<TokenData<T>, JwtError> {
+    ring(key_use)
+        .map_err(|_| JwtError::from(ErrorKind::InvalidKeyFormat))?
+        .verify(token)
+}
+
+/// Public halves of every Ed25519 key in both rings.
+pub fn jwks() -> Result<JwkSet, (StatusCode, String)> {
+    let mut keys = Vec::new();
+    for key_use in [KeyUse::Session, KeyUse::Qr] {
+        let ring = ring(key_use).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        keys.extend(ring.public.iter().cloned());
+    }
+    Ok(JwkSet { keys })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::pkcs8::{EncodePrivateKey, EncodePublicKey};
+    use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+
+    #[derive(Debug, Serialize, serde::Deserialize, PartialEq)]
+    struct TestClaims {
+        sub: String,
+        exp: usize,
+    }
+
+    #[test]
+    fn rotated_keys_keep_verifying_by_kid() {
+        let dir = std::env::temp_dir().join(format!("jwt-keys-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = SigningKey::from_bytes(&[1; 32]);
+        let new = SigningKey::from_bytes(&[2; 32]);
+        std::fs::write(dir.join("2026-01.pem"), old.to_pkcs8_pem(LineEnding::LF).unwrap().as_bytes()).unwrap();
+        std::fs::write(dir.join("2026-10.pem"), new.to_pkcs8_pem(LineEnding::LF).unwrap().as_bytes()).unwrap();
+
+        let claims = TestClaims { sub: "u".into(), exp: 4_000_000_000 };
+        let before = Keyring::load(Some(&dir), Some("2026-01"), None).unwrap();
+        let old_token = before.sign(&claims).unwrap();
+        assert_eq!(decode_header(&old_token).unwrap().kid.as_deref(), Some("2026-01"));
+
+        // Rotate: newest key becomes active, the old one is kept public-only.
+        std::fs::remove_file(dir.join("2026-01.pem")).unwrap();
+        std::fs::write(
+            dir.join("2026-01.pub.pem"),
+            old.verifying_key().to_public_key_pem(LineEnding::LF).unwrap(),
+        )
+        .unwrap();
+        let after = Keyring::load(Some(&dir), None, Some("legacy")).unwrap();
+        let new_token = after.sign(&claims).unwrap();
+        assert_eq!(decode_header(&new_token).unwrap().kid.as_deref(), Some("2026-10"));
+        assert_eq!(after.verify::<TestClaims>(&old_token).unwrap().claims, claims);
+        assert_eq!(after.verify::<TestClaims>(&new_token).unwrap().claims, claims);
+        assert_eq!(after.public.len(), 2);
+
+        // Legacy HS256 tokens are accepted only without a kid.
+        let legacy = encode(&Header::default(), &claims, &EncodingKey::from_secret(b"legacy")).unwrap();
+        assert!(after.verify::<TestClaims>(&legacy).is_ok());
+        let forged = Header { kid: Some("2026-10".into()), ..Header::default() };
+        let forged = encode(&forged, &claims, &EncodingKey::from_secret(b"legacy")).unwrap();
+        assert!(after.verify::<TestClaims>(&forged).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn qr_ring_may_not_reuse_session_keys() {
+        assert!(rings_share_keys((None, Some("s")), (None, Some("s"))));
+        assert!(rings_share_keys((Some("/keys/a/"), None), (Some("/keys/a"), Some("q"))));
+        assert!(!rings_share_keys((Some("/keys/a"), Some("s")), (Some("/keys/b"), Some("q"))));
+        assert!(!rings_share_keys((None, Some("s")), (Some("/keys/qr"), None)));
+    }
+}
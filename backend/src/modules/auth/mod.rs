@@ -1,5 +1,17 @@
+pub mod client_ip;
+pub mod csrf;
+pub mod email_verification;
 pub mod handlers;
+pub mod impersonation;
+pub mod keys;
 pub mod models;
+pub mod oidc;
+pub mod password_policy;
+pub mod permissions;
+pub mod session;
+pub mod throttle;
+pub mod tokens;
+pub mod totp;
 
 pub use handlers::*;
 pub use models::*;
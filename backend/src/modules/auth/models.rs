@@ -27,6 +27,7 @@ pub struct RegisterInput {
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
     pub token: String,
+    pub access_expires_at: DateTime<Utc>,
     pub user: UserResponse,
 }
 
@@ -34,6 +35,9 @@ pub struct AuthResponse {
 pub struct RegisterResponse {
     pub user_id: Uuid,
     pub message: String,
+    /// The department's organization vets registrations; the account stays
+    /// `pending` until one of its admins approves it.
+    pub pending_approval: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -52,6 +56,12 @@ pub struct UserResponse {
     pub department_name: Option<String>,
     pub session_id: String,
     pub expires_at: DateTime<Utc>,
+    /// Set when org policy requires 2FA and the admin hasn't enrolled yet;
+    /// admin privileges are withheld until `/auth/2fa/verify` succeeds.
+    pub two_factor_setup_required: bool,
+    /// Set while a super admin is acting as this user, so the UI can show
+    /// a banner and a "stop impersonating" button.
+    pub impersonator_id: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -68,6 +78,16 @@ pub struct Claims {
     pub is_admin: bool,
     pub admin_level: Option<AdminLevel>,
     pub organization_id: Option<Uuid>,
+    /// Keys from `permissions::Permission`; empty for non-admins.
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// The super admin behind an impersonation session; `sub` is the
+    /// impersonated user. Absent on ordinary sessions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonator_id: Option<Uuid>,
+    /// Set when the request authenticated with a personal API key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_id: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -80,3 +100,96 @@ pub struct ResetPasswordInput {
     pub token: String,
     pub new_password: String,
 }
+
+/// One row of `GET /auth/sessions` — a device the user is signed in on.
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SessionInfo {
+    pub id: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub login_method: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_accessed: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub is_current: bool,
+}
+
+/// Returned by `/auth/login` instead of a session when the account has 2FA.
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallengeResponse {
+    pub two_factor_required: bool,
+    pub challenge_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TwoFactorChallengeClaims {
+    pub sub: String,
+    pub purpose: String,
+    pub remember_me: bool,
+    /// How the first factor was passed (`password` or `oidc`), recorded as
+    /// the session's `login_method` once the code is accepted.
+    pub login_method: String,
+    pub exp: usize,
+    pub iat: usize,
+}
+
+/// Payload of the signed link mailed after registration. `email` pins the
+/// link to the address it was sent to.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailVerificationClaims {
+    pub sub: String,
+    pub email: String,
+    pub purpose: String,
+    pub exp: usize,
+    pub iat: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailInput {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResendVerificationInput {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorLoginInput {
+    pub challenge_token: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeInput {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorSetupResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Recovery codes are shown exactly once, right after enrollment.
+#[derive(Debug, Serialize)]
+pub struct TwoFactorEnabledResponse {
+    pub recovery_codes: Vec<String>,
+    pub token: String,
+    pub access_expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcLoginQuery {
+    /// Frontend path to land on after sign-in, e.g. `/student/activities`.
+    pub return_to: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
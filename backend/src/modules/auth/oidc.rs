@@ -0,0 +1,370 @@
+//! OpenID Connect single sign-on (authorization code + PKCE).
+//!
+//! Enabled by setting `OIDC_ISSUER`, `OIDC_CLIENT_ID` and
+//! `OIDC_REDIRECT_URI`; everything else is read from the issuer's
+//! `/.well-known/openid-configuration`, so any compliant IdP works —
+//! including a local mock IdP over plain http during development.
+//!
+//! Discovery and JWKS documents are cached in-process for
+//! `PROVIDER_CACHE_TTL`; an ID token signed with an unknown `kid` forces one
+//! refetch so key rotation at the IdP doesn't need a restart.
+
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+const PROVIDER_CACHE_TTL: Duration = Duration::from_secs(3600);
+const DEFAULT_SCOPES: &str = "openid email profile";
+const DEFAULT_STUDENT_ID_CLAIM: &str = "student_id";
+const DEFAULT_DEPARTMENT_CLAIM: &str = "department";
+
+#[derive(Debug, Clone)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub redirect_uri: String,
+    pub scopes: String,
+    /// ID-token claim holding the student number, e.g. `student_id`.
+    pub student_id_claim: String,
+    /// ID-token claim matched against `departments.code` or `name` when
+    /// provisioning a new account.
+    pub department_claim: String,
+    /// Create a `users` row on first sign-in when no account matches.
+    pub auto_provision: bool,
+}
+
+impl OidcConfig {
+    /// `None` when SSO isn't configured for this deployment.
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+        Some(Self {
+            issuer: var("OIDC_ISSUER")?.trim_end_matches('/').to_string(),
+            client_id: var("OIDC_CLIENT_ID")?,
+            client_secret: var("OIDC_CLIENT_SECRET"),
+            redirect_uri: var("OIDC_REDIRECT_URI")?,
+            scopes: var("OIDC_SCOPES").unwrap_or_else(|| DEFAULT_SCOPES.to_string()),
+            student_id_claim: var("OIDC_STUDENT_ID_CLAIM").unwrap_or_else(|| DEFAULT_STUDENT_ID_CLAIM.to_string()),
+            department_claim: var("OIDC_DEPARTMENT_CLAIM").unwrap_or_else(|| DEFAULT_DEPARTMENT_CLAIM.to_string()),
+            auto_provision: var("OIDC_AUTO_PROVISION").as_deref() == Some("true"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Discovery {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+#[derive(Clone)]
+struct Provider {
+    discovery: Discovery,
+    jwks: JwkSet,
+    fetched_at: Instant,
+}
+
+fn provider_cache() -> &'static Mutex<Option<Provider>> {
+    static CACHE: OnceLock<Mutex<Option<Provider>>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(None))
+}
+
+fn bad_gateway(context: &str, e: impl std::fmt::Display) -> (StatusCode, String) {
+    (StatusCode::BAD_GATEWAY, format!("{}: {}", context, e))
+}
+
+async fn fetch_provider(config: &OidcConfig) -> Result<Provider, (StatusCode, String)> {
+    let client = reqwest::Client::new();
+    let discovery: Discovery = client
+        .get(format!("{}/.well-known/openid-configuration", config.issuer))
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| bad_gateway("OIDC discovery failed", e))?
+        .json()
+        .await
+        .map_err(|e| bad_gateway("Invalid OIDC discovery document", e))?;
+
+    if discovery.issuer.trim_end_matches('/') != config.issuer {
+        return Err((
+            StatusCode::BAD_GATEWAY,
+            format!("OIDC issuer mismatch: expected {}, got {}", config.issuer, discovery.issuer),
+        ));
+    }
+
+    let jwks: JwkSet = client
+        .get(&discovery.jwks_uri)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| bad_gateway("OIDC JWKS fetch failed", e))?
+        .json()
+        .await
+        .map_err(|e| bad_gateway("Invalid OIDC JWKS", e))?;
+
+    Ok(Provider { discovery, jwks, fetched_at: Instant::now() })
+}
+
+async fn provider(config: &OidcConfig, force_refresh: bool) -> Result<Provider, (StatusCode, String)> {
+    if !force_refresh {
+        let cached = provider_cache().lock().unwrap().clone();
+        if let Some(p) = cached.filter(|p| p.fetched_at.elapsed() < PROVIDER_CACHE_TTL) {
+            return Ok(p);
+        }
+    }
+    let fresh = fetch_provider(config).await?;
+    *provider_cache().lock().unwrap() = Some(fresh.clone());
+    Ok(fresh)
+}
+
+pub async fn discovery(config: &OidcConfig) -> Result<Discovery, (StatusCode, String)> {
+    Ok(provider(config, false).await?.discovery)
+}
+
+/// Random URL-safe string for `state`, `nonce` and the PKCE verifier.
+pub fn random_token() -> String {
+    let bytes: [u8; 32] = rand::random();
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// RFC 7636 `S256` code challenge.
+pub fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+pub fn authorization_url(
+    config: &OidcConfig,
+    discovery: &Discovery,
+    state: &str,
+    nonce: &str,
+    code_verifier: &str,
+) -> Result<String, (StatusCode, String)> {
+    let challenge = pkce_challenge(code_verifier);
+    reqwest::Url::parse_with_params(
+        &discovery.authorization_endpoint,
+        &[
+            ("response_type", "code"),
+            ("client_id", config.client_id.as_str()),
+            ("redirect_uri", config.redirect_uri.as_str()),
+            ("scope", config.scopes.as_str()),
+            ("state", state),
+            ("nonce", nonce),
+            ("code_challenge", challenge.as_str()),
+            ("code_challenge_method", "S256"),
+        ],
+    )
+    .map(|u| u.to_string())
+    .map_err(|e| bad_gateway("Invalid authorization endpoint", e))
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Exchange the authorization code and return the verified ID-token claims.
+pub async fn exchange_code(
+    config: &OidcConfig,
+    code: &str,
+    code_verifier: &str,
+    nonce: &str,
+) -> Result<serde_json::Map<String, serde_json::Value>, (StatusCode, String)> {
+    let discovery = discovery(config).await?;
+
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code),
+        ("redirect_uri", config.redirect_uri.as_str()),
+        ("client_id", config.client_id.as_str()),
+        ("code_verifier", code_verifier),
+    ];
+    if let Some(secret) = config.client_secret.as_deref() {
+        form.push(("client_secret", secret));
+    }
+
+    let tokens: TokenResponse = reqwest::Client::new()
+        .post(&discovery.token_endpoint)
+        .form(&form)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| bad_gateway("OIDC token exchange failed", e))?
+        .json()
+        .await
+        .map_err(|e| bad_gateway("Invalid OIDC token response", e))?;
+
+    verify_id_token(config, &tokens.id_token, nonce).await
+}
+
+async fn verify_id_token(
+    config: &OidcConfig,
+    id_token: &str,
+    nonce: &str,
+) -> Result<serde_json::Map<String, serde_json::Value>, (StatusCode, String)> {
+    let invalid = |e: &dyn std::fmt::Display| (StatusCode::UNAUTHORIZED, format!("Invalid ID token: {}", e));
+    let header = decode_header(id_token).map_err(|e| invalid(&e))?;
+    if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+        return Err(invalid(&"symmetric signature"));
+    }
+
+    // IdPs with a single signing key may omit `kid`.
+    let find_key = |jwks: &JwkSet| match header.kid.as_deref() {
+        Some(kid) => jwks.find(kid).cloned(),
+        None if jwks.keys.len() == 1 => jwks.keys.first().cloned(),
+        None => None,
+    };
+    let mut provider = provider(config, false).await?;
+    if find_key(&provider.jwks).is_none() {
+        provider = self::provider(config, true).await?;
+    }
+    let jwk = find_key(&provider.jwks).ok_or_else(|| invalid(&"unknown signing key"))?;
+    let key = DecodingKey::from_jwk(&jwk).map_err(|e| invalid(&e))?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[provider.discovery.issuer.as_str()]);
+    validation.set_audience(&[config.client_id.as_str()]);
+    validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+    let claims = decode::<serde_json::Map<String, serde_json::Value>>(id_token, &key, &validation)
+        .map_err(|e| invalid(&e))?
+        .claims;
+    if claims.get("nonce").and_then(|v| v.as_str()) != Some(nonce) {
+        return Err(invalid(&"nonce mismatch"));
+    }
+    Ok(claims)
+}
+
+/// The parts of an ID token we use to find or create a `users` row.
+#[derive(Debug, Default, PartialEq)]
+pub struct Identity {
+    pub subject: String,
+    pub email: Option<String>,
+    pub student_id: Option<String>,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub department: Option<String>,
+}
+
+fn claim_string(claims: &serde_json::Map<String, serde_json::Value>, name: &str) -> Option<String> {
+    match claims.get(name)? {
+        serde_json::Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+pub fn identity_from_claims(
+    config: &OidcConfig,
+    claims: &serde_json::Map<String, serde_json::Value>,
+) -> Option<Identity> {
+    // Only an email the IdP vouches for may link onto an existing local
+    // account; a missing `email_verified` counts as unverified. Some IdPs
+    // send the flag as a string.
+    let email_verified = match claims.get("email_verified") {
+        Some(serde_json::Value::Bool(b)) => *b,
+        Some(serde_json::Value::String(s)) => s.eq_ignore_ascii_case("true"),
+        _ => false,
+    };
+
+    let (mut first_name, mut last_name) = (claim_string(claims, "given_name"), claim_string(claims, "family_name"));
+    if first_name.is_none() {
+        if let Some(name) = claim_string(claims, "name") {
+            let mut parts = name.splitn(2, ' ');
+            first_name = parts.next().map(str::to_string);
+            last_name = last_name.or_else(|| parts.next().map(|s| s.trim().to_string()));
+        }
+    }
+
+    Some(Identity {
+        subject: claim_string(claims, "sub")?,
+        email: claim_string(claims, "email")
+            .filter(|_| email_verified)
+            .map(|e| e.to_lowercase()),
+        student_id: claim_string(claims, &config.student_id_claim),
+        first_name,
+        last_name,
+        department: claim_string(claims, &config.department_claim),
+    })
+}
+
+/// Only same-site paths are accepted as post-login destinations, so the
+/// login link can't be turned into an open redirect.
+pub fn sanitize_return_to(path: Option<&str>) -> String {
+    match path {
+        Some(p) if p.starts_with('/') && !p.starts_with("//") && !p.contains('\\') => p.to_string(),
+        _ => "/".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> OidcConfig {
+        OidcConfig {
+            issuer: "http://localhost:8080/default".into(),
+            client_id: "trackivity".into(),
+            client_secret: None,
+            redirect_uri: "http://localhost:3000/auth/oidc/callback".into(),
+            scopes: DEFAULT_SCOPES.into(),
+            student_id_claim: "student_number".into(),
+            department_claim: DEFAULT_DEPARTMENT_CLAIM.into(),
+            auto_provision: false,
+        }
+    }
+
+    #[test]
+    fn pkce_challenge_matches_rfc7636_example() {
+        assert_eq!(
+            pkce_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk"),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM",
+        );
+    }
+
+    #[test]
+    fn identity_uses_configured_claims_and_ignores_unverified_email() {
+        let claims = serde_json::json!({
+            "sub": "abc",
+            "email": "Student@Uni.ac.th",
+            "email_verified": false,
+            "student_number": 6400123,
+            "name": "Somchai Jaidee",
+        });
+        let identity = identity_from_claims(&config(), claims.as_object().unwrap()).unwrap();
+        assert_eq!(identity.subject, "abc");
+        assert_eq!(identity.email, None);
+        assert_eq!(identity.student_id.as_deref(), Some("6400123"));
+        assert_eq!(identity.first_name.as_deref(), Some("Somchai"));
+        assert_eq!(identity.last_name.as_deref(), Some("Jaidee"));
+    }
+
+    #[test]
+    fn email_is_only_used_when_the_idp_marks_it_verified() {
+        let email_of = |claims: serde_json::Value| {
+            identity_from_claims(&config(), claims.as_object().unwrap()).unwrap().email
+        };
+        assert_eq!(email_of(serde_json::json!({ "sub": "abc", "email": "a@uni.ac.th" })), None);
+        assert_eq!(
+            email_of(serde_json::json!({ "sub": "abc", "email": "A@uni.ac.th", "email_verified": true })).as_deref(),
+            Some("a@uni.ac.th"),
+        );
+        assert_eq!(
+            email_of(serde_json::json!({ "sub": "abc", "email": "a@uni.ac.th", "email_verified": "true" })).as_deref(),
+            Some("a@uni.ac.th"),
+        );
+    }
+
+    #[test]
+    fn return_to_rejects_offsite_targets() {
+        assert_eq!(sanitize_return_to(Some("/student/activities")), "/student/activities");
+        assert_eq!(sanitize_return_to(Some("//evil.example")), "/");
+        assert_eq!(sanitize_return_to(Some("https://evil.example")), "/");
+        assert_eq!(sanitize_return_to(None), "/");
+    }
+}
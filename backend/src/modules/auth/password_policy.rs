@@ -0,0 +1,170 @@
+//! Password policy shared by every path that sets a password: registration,
+//! self-service change, reset-by-link, admin reset and admin creation.
+//!
+//! Configuration (all optional):
+//! - `PASSWORD_MIN_LENGTH` — minimum length in characters (default 8).
+//! - `PASSWORD_MIN_CHAR_CLASSES` — how many of lowercase, uppercase, digit
+//!   and symbol must appear (1–4, default 1).
+//! - `BREACHED_PASSWORDS_DIR` — offline breached-password list in the
+//!   Pwned Passwords range layout: one file per 5-hex-digit SHA-1 prefix
+//!   (`ABCDE` or `ABCDE.txt`) holding `SUFFIX:COUNT` lines. Only the file
+//!   for the candidate's prefix is read.
+//!
+//! Violations are answered as 422 with a JSON body the frontend can map to
+//! field messages.
+
+use std::path::{Path, PathBuf};
+use axum::http::StatusCode;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+
+/// Argon2 cost grows with input; nobody needs a longer password.
+const MAX_LENGTH: usize = 128;
+pub const VIOLATION_CODE: &str = "password_policy";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub min_char_classes: usize,
+    pub breached_check: bool,
+    #[serde(skip)]
+    breached_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum Violation {
+    TooShort { min_length: usize },
+    TooLong { max_length: usize },
+    SameAsIdentifier,
+    TooFewCharClasses { min_char_classes: usize },
+    Breached,
+}
+
+impl PasswordPolicy {
+    pub fn from_env() -> Self {
+        let read = |name: &str, default: usize| {
+            std::env::var(name).ok().and_then(|v| v.trim().parse().ok()).unwrap_or(default)
+        };
+        let breached_dir = std::env::var("BREACHED_PASSWORDS_DIR")
+            .ok()
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
+        Self {
+            min_length: read("PASSWORD_MIN_LENGTH", 8).max(1),
+            max_length: MAX_LENGTH,
+            min_char_classes: read("PASSWORD_MIN_CHAR_CLASSES", 1).clamp(1, 4),
+            breached_check: breached_dir.is_some(),
+            breached_dir,
+        }
+    }
+
+    /// Rules that need no I/O. `identifiers` are the account's student ID and
+    /// email; the password may not equal either, ignoring case.
+    pub fn check(&self, password: &str, identifiers: &[&str]) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let length = password.chars().count();
+        if length < self.min_length {
+            violations.push(Violation::TooShort { min_length: self.min_length });
+        }
+        if length > self.max_length {
+            violations.push(Violation::TooLong { max_length: self.max_length });
+        }
+        let normalized = password.trim().to_lowercase();
+        if identifiers.iter().any(|id| !id.trim().is_empty() && id.trim().to_lowercase() == normalized) {
+            violations.push(Violation::SameAsIdentifier);
+        }
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ];
+        if classes.iter().filter(|&&present| present).count() < self.min_char_classes {
+            violations.push(Violation::TooFewCharClasses { min_char_classes: self.min_char_classes });
+        }
+        violations
+    }
+}
+
+/// Look the password up in the local range files. A missing prefix file
+/// means "not listed"; an unreadable one is logged and also treated as
+/// not listed so a broken mount can't block every password change.
+pub async fn is_breached(dir: &Path, password: &str) -> bool {
+    let digest = Sha1::digest(password.as_bytes());
+    let hex: String = digest.iter().map(|b| format!("{:02X}", b)).collect();
+    let (prefix, suffix) = hex.split_at(5);
+
+    for name in [format!("{}.txt", prefix), prefix.to_string()] {
+        match tokio::fs::read_to_string(dir.join(&name)).await {
+            Ok(contents) => {
+                return contents.lines().any(|line| {
+                    line.split(':').next().is_some_and(|s| s.trim().eq_ignore_ascii_case(suffix))
+                });
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => {
+                tracing::error!("Failed to read breached-password range {}: {}", name, e);
+                return false;
+            }
+        }
+    }
+    false
+}
+
+/// Enforce the configured policy, answering 422 with every violation.
+pub async fn enforce(password: &str, identifiers: &[&str]) -> Result<(), (StatusCode, String)> {
+    let policy = PasswordPolicy::from_env();
+    let mut violations = policy.check(password, identifiers);
+    if let Some(dir) = &policy.breached_dir {
+        if violations.is_empty() && is_breached(dir, password).await {
+            violations.push(Violation::Breached);
+        }
+    }
+    if violations.is_empty() {
+        return Ok(());
+    }
+    let body = serde_json::json!({
+        "code": VIOLATION_CODE,
+        "message": "Password does not meet the password policy",
+        "violations": violations,
+    });
+    Err((StatusCode::UNPROCESSABLE_ENTITY, body.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(min_length: usize, min_char_classes: usize) -> PasswordPolicy {
+        PasswordPolicy { min_length, max_length: MAX_LENGTH, min_char_classes, breached_check: false, breached_dir: None }
+    }
+
+    #[test]
+    fn check_reports_every_violation() {
+        assert_eq!(policy(8, 1).check("correct horse", &["6401", "a@b.com"]), vec![]);
+        assert_eq!(
+            policy(8, 3).check("6401", &["6401", "a@b.com"]),
+            vec![
+                Violation::TooShort { min_length: 8 },
+                Violation::SameAsIdentifier,
+                Violation::TooFewCharClasses { min_char_classes: 3 },
+            ]
+        );
+        assert_eq!(policy(1, 1).check("A@B.com", &["6401", "a@b.com"]), vec![Violation::SameAsIdentifier]);
+        assert_eq!(policy(8, 4).check("Tr0ub4dor&3", &[]), vec![]);
+    }
+
+    #[tokio::test]
+    async fn breached_lookup_reads_only_the_prefix_file() {
+        let dir = std::env::temp_dir().join(format!("pwned-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // SHA-1("password") = 5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8
+        std::fs::write(dir.join("5BAA6.txt"), "003D68EB55068C33ACE09247EE4C639306B:3\r\n1E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\r\n").unwrap();
+        assert!(is_breached(&dir, "password").await);
+        assert!(!is_breached(&dir, "not in the list").await);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,269 @@
+//! Admin permission catalogue.
+//!
+//! `admin_roles.permissions` holds keys from `Permission::ALL`. Handlers call
+//! `require_permission` before their organization-scope checks, so the two
+//! compose: the permission says *what* an admin may do, `organization_id`
+//! says *where*. Super admins hold every permission implicitly.
+//!
+//! Permissions travel in the access token like `admin_level`, so a change
+//! takes effect at the admin's next refresh (at most `ACCESS_TOKEN_MINUTES`).
+
+use axum::http::StatusCode;
+use serde::Serialize;
+use crate::models::AdminLevel;
+use super::models::Claims;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    ActivitiesView,
+    ActivitiesCreate,
+    ActivitiesUpdate,
+    ActivitiesDelete,
+    ParticipationsManualComplete,
+    UsersView,
+    UsersUpdate,
+    UsersResetPassword,
+    UsersUnlockLogin,
+    UsersDelete,
+    UsersApprove,
+    QrScan,
+    ScannersManage,
+    DepartmentsView,
+    DepartmentsManage,
+    OrganizationsView,
+    OrganizationsManage,
+    OrganizationsRequirements,
+    AuditView,
+    AdminsView,
+    DashboardView,
+}
+
+impl Permission {
+    pub const ALL: [Permission; 21] = [
+        Permission::ActivitiesView,
+        Permission::ActivitiesCreate,
+        Permission::ActivitiesUpdate,
+        Permission::ActivitiesDelete,
+        Permission::ParticipationsManualComplete,
+        Permission::UsersView,
+        Permission::UsersUpdate,
+        Permission::UsersResetPassword,
+        Permission::UsersUnlockLogin,
+        Permission::UsersDelete,
+        Permission::UsersApprove,
+        Permission::QrScan,
+        Permission::ScannersManage,
+        Permission::DepartmentsView,
+        Permission::DepartmentsManage,
+        Permission::OrganizationsView,
+        Permission::OrganizationsManage,
+        Permission::OrganizationsRequirements,
+        Permission::AuditView,
+        Permission::AdminsView,
+        Permission::DashboardView,
+    ];
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Permission::ActivitiesView => "activities.view",
+            Permission::ActivitiesCreate => "activities.create",
+            Permission::ActivitiesUpdate => "activities.update",
+            Permission::ActivitiesDelete => "activities.delete",
+            Permission::ParticipationsManualComplete => "participations.manual_complete",
+            Permission::UsersView => "users.view",
+            Permission::UsersUpdate => "users.update",
+            Permission::UsersResetPassword => "users.reset_password",
+            Permission::UsersUnlockLogin => "users.unlock_login",
+            Permission::UsersDelete => "users.delete",
+            Permission::UsersApprove => "users.approve",
+            Permission::QrScan => "qr.scan",
+            Permission::ScannersManage => "scanners.manage",
+            Permission::DepartmentsView => "departments.view",
+            Permission::DepartmentsManage => "departments.manage",
+            Permission::OrganizationsView => "organizations.view",
+            Permission::OrganizationsManage => "organizations.manage",
+            Permission::OrganizationsRequirements => "organizations.requirements",
+            Permission::AuditView => "audit.view",
+            Permission::AdminsView => "admins.view",
+            Permission::DashboardView => "dashboard.view",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Permission::ActivitiesView => "ดูกิจกรรมทั้งหมดของหน่วยงาน รวมฉบับร่าง",
+            Permission::ActivitiesCreate => "สร้างกิจกรรม",
+            Permission::ActivitiesUpdate => "แก้ไขกิจกรรม",
+            Permission::ActivitiesDelete => "ลบกิจกรรม",
+            Permission::ParticipationsManualComplete => "บันทึกการเข้าร่วมกิจกรรมด้วยตนเอง",
+            Permission::UsersView => "ดูข้อมูลผู้ใช้",
+            Permission::UsersUpdate => "แก้ไขข้อมูลผู้ใช้",
+            Permission::UsersResetPassword => "รีเซ็ตรหัสผ่านผู้ใช้",
+            Permission::UsersUnlockLogin => "ปลดล็อกการเข้าสู่ระบบ",
+            Permission::UsersDelete => "ลบผู้ใช้",
+            Permission::UsersApprove => "อนุมัติ/ปฏิเสธการสมัครสมาชิก",
+            Permission::QrScan => "สแกน QR เช็คอิน/เช็คเอาท์",
+            Permission::ScannersManage => "มอบสิทธิ์ผู้สแกน QR ให้นักศึกษา/เจ้าหน้าที่",
+            Permission::DepartmentsView => "ดูสาขา/ภาควิชา",
+            Permission::DepartmentsManage => "จัดการสาขา/ภาควิชา",
+            Permission::OrganizationsView => "ดูหน่วยงาน",
+            Permission::OrganizationsManage => "จัดการหน่วยงาน",
+            Permission::OrganizationsRequirements => "กำหนดชั่วโมงกิจกรรมที่ต้องเก็บ",
+            Permission::AuditView => "ดูบันทึกการดำเนินการของผู้ดูแล",
+            Permission::AdminsView => "ดูรายชื่อผู้ดูแลและรายการสิทธิ์",
+            Permission::DashboardView => "ดูสถิติภาพรวมบนแดชบอร์ด",
+        }
+    }
+
+    /// Permissions reserved for super admins; they can't be granted to an
+    /// organization-scoped admin because the handler has no org to scope by.
+    pub fn super_admin_only(self) -> bool {
+        matches!(self, Permission::OrganizationsManage)
+    }
+
+    pub fn from_key(key: &str) -> Option<Permission> {
+        Permission::ALL.into_iter().find(|p| p.key() == key)
+    }
+
+    /// What a newly created admin of `level` gets when no explicit list is
+    /// supplied. Matches what each level could do before permissions were
+    /// enforced; regular admins could never modify users, and the audit
+    /// trail is for the organization admin overseeing them.
+    pub fn defaults_for(level: &AdminLevel) -> Vec<Permission> {
+        Permission::ALL
+            .into_iter()
+            .filter(|p| match level {
+                AdminLevel::SuperAdmin => true,
+                AdminLevel::OrganizationAdmin => !p.super_admin_only(),
+                AdminLevel::RegularAdmin => !p.super_admin_only()
+                    && !matches!(
+                        p,
+                        Permission::UsersUpdate
+                            | Permission::UsersResetPassword
+                            | Permission::UsersUnlockLogin
+                            | Permission::UsersDelete
+                            | Permission::UsersApprove
+                            | Permission::AuditView
+                            | Permission::AdminsView
+                    ),
+            })
+            .collect()
+    }
+}
+
+/// One entry of `GET /admin/permissions`.
+#[derive(Debug, Serialize)]
+pub struct PermissionInfo {
+    pub key: &'static str,
+    pub description: &'static str,
+    pub super_admin_only: bool,
+    pub default_levels: Vec<AdminLevel>,
+}
+
+pub fn catalogue() -> Vec<PermissionInfo> {
+    let levels = [AdminLevel::SuperAdmin, AdminLevel::OrganizationAdmin, AdminLevel::RegularAdmin];
+    Permission::ALL
+        .into_iter()
+        .map(|p| PermissionInfo {
+            key: p.key(),
+            description: p.description(),
+            super_admin_only: p.super_admin_only(),
+            default_levels: levels
+                .iter()
+                .filter(|l| Permission::defaults_for(l).contains(&p))
+                .cloned()
+                .collect(),
+        })
+        .collect()
+}
+
+/// Reject the request unless the caller is an admin holding `permission`.
+pub fn require_permission(claims: &Claims, permission: Permission) -> Result<(), (StatusCode, String)> {
+    if !claims.is_admin {
+        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
+    }
+    if matches!(claims.admin_level, Some(AdminLevel::SuperAdmin)) {
+        return Ok(());
+    }
+    if !permission.super_admin_only() && claims.permissions.iter().any(|p| p == permission.key()) {
+        return Ok(());
+    }
+    Err((StatusCode::FORBIDDEN, format!("Missing permission: {}", permission.key())))
+}
+
+/// Validate a permission list supplied for an admin of `level`, falling
+/// back to the level's defaults when none is given.
+pub fn resolve_for_level(
+    level: &AdminLevel,
+    requested: Option<Vec<String>>,
+) -> Result<Vec<String>, (StatusCode, String)> {
+    let Some(requested) = requested else {
+        return Ok(Permission::defaults_for(level).into_iter().map(|p| p.key().to_string()).collect());
+    };
+    let mut keys = Vec::with_capacity(requested.len());
+    for key in requested {
+        let permission = Permission::from_key(&key)
+            .ok_or((StatusCode::BAD_REQUEST, format!("Unknown permission: {}", key)))?;
+        if permission.super_admin_only() && *level != AdminLevel::SuperAdmin {
+            return Err((StatusCode::BAD_REQUEST, format!("{} is reserved for super admins", key)));
+        }
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn admin_claims(level: AdminLevel, permissions: &[&str]) -> Claims {
+        Claims {
+            sub: uuid::Uuid::nil().to_string(),
+            session_id: "s".into(),
+            exp: 0,
+            iat: 0,
+            student_id: "A1".into(),
+            email: "a@example.com".into(),
+            first_name: "A".into(),
+            last_name: "B".into(),
+            department_id: None,
+            is_admin: true,
+            admin_level: Some(level),
+            organization_id: None,
+            permissions: permissions.iter().map(|p| p.to_string()).collect(),
+            impersonator_id: None,
+            api_key_id: None,
+        }
+    }
+
+    #[test]
+    fn keys_round_trip() {
+        for p in Permission::ALL {
+            assert_eq!(Permission::from_key(p.key()), Some(p));
+        }
+    }
+
+    #[test]
+    fn guard_checks_granted_keys_and_super_admin_only() {
+        let org_admin = admin_claims(AdminLevel::OrganizationAdmin, &["qr.scan", "organizations.manage"]);
+        assert!(require_permission(&org_admin, Permission::QrScan).is_ok());
+        assert!(require_permission(&org_admin, Permission::UsersDelete).is_err());
+        assert!(require_permission(&org_admin, Permission::OrganizationsManage).is_err());
+
+        let super_admin = admin_claims(AdminLevel::SuperAdmin, &[]);
+        assert!(require_permission(&super_admin, Permission::OrganizationsManage).is_ok());
+    }
+
+    #[test]
+    fn resolve_rejects_unknown_and_reserved_keys() {
+        assert!(resolve_for_level(&AdminLevel::RegularAdmin, Some(vec!["nope".into()])).is_err());
+        assert!(resolve_for_level(&AdminLevel::OrganizationAdmin, Some(vec!["organizations.manage".into()])).is_err());
+        let defaults = resolve_for_level(&AdminLevel::RegularAdmin, None).unwrap();
+        assert!(defaults.contains(&"qr.scan".to_string()));
+        assert!(!defaults.contains(&"users.delete".to_string()));
+        assert!(!defaults.contains(&"audit.view".to_string()));
+        assert!(!defaults.contains(&"users.approve".to_string()));
+    }
+}
@@ -0,0 +1,155 @@
+//! Server-side session checks layered on top of the JWT.
+//!
+//! A valid signature only proves we issued the token; it says nothing about
+//! whether the user has since logged out or reset their password. Every
+//! authenticated request therefore confirms that `Claims.session_id` still
+//! points at an active, unexpired `sessions` row.
+//!
+//! The lookup result is cached in-process for `SESSION_CACHE_TTL` so a page
+//! that fires a dozen API calls costs one lookup rather than twelve. Local
+//! revocations (logout, password reset) evict the cache immediately; a
+//! revocation on another replica is picked up once the entry goes stale.
+
+use axum::http::StatusCode;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+const SESSION_CACHE_TTL: Duration = Duration::from_secs(30);
+/// Upper bound before stale entries are swept, so a burst of one-off
+/// sessions can't grow the map forever.
+const SESSION_CACHE_SWEEP_AT: usize = 10_000;
+
+#[derive(Debug, Clone, Copy)]
+struct CachedSession {
+    user_id: Uuid,
+    valid: bool,
+    checked_at: Instant,
+}
+
+#[derive(Default)]
+struct SessionCache {
+    entries: HashMap<String, CachedSession>,
+}
+
+impl SessionCache {
+    fn get(&self, session_id: &str, now: Instant) -> Option<CachedSession> {
+        self.entries
+            .get(session_id)
+            .filter(|c| now.duration_since(c.checked_at) < SESSION_CACHE_TTL)
+            .copied()
+    }
+
+    fn put(&mut self, session_id: &str, entry: CachedSession) {
+        if self.entries.len() >= SESSION_CACHE_SWEEP_AT {
+            let now = entry.checked_at;
+            self.entries
+                .retain(|_, c| now.duration_since(c.checked_at) < SESSION_CACHE_TTL);
+        }
+        self.entries.insert(session_id.to_string(), entry);
+    }
+
+    fn remove(&mut self, session_id: &str) {
+        self.entries.remove(session_id);
+    }
+
+    fn remove_user(&mut self, user_id: Uuid) {
+        self.entries.retain(|_, c| c.user_id != user_id);
+    }
+}
+
+fn cache() -> &'static Mutex<SessionCache> {
+    static CACHE: OnceLock<Mutex<SessionCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(SessionCache::default()))
+}
+
+/// Confirm that `session_id` is an active, unexpired session owned by
+/// `user_id`. Returns 401 when the session was revoked or has lapsed.
+pub async fn ensure_session_active(
+    pool: &PgPool,
+    session_id: &str,
+    user_id: Uuid,
+) -> Result<(), (StatusCode, String)> {
+    let now = Instant::now();
+    let cached = cache().lock().unwrap().get(session_id, now);
+
+    let valid = match cached {
+        Some(c) => c.valid && c.user_id == user_id,
+        None => {
+            // Validating and touching `last_accessed` in one statement keeps
+            // the "active devices" list fresh to within one cache TTL.
+            let owner: Option<Uuid> = sqlx::query_scalar(
+                r#"
+                UPDATE sessions SET last_accessed = NOW()
+                WHERE id = $1 AND is_active = TRUE AND expires_at > NOW()
+                RETURNING user_id
+                "#,
+            )
+            .bind(session_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+            let valid = owner == Some(user_id);
+            cache().lock().unwrap().put(
+                session_id,
+                CachedSession { user_id, valid, checked_at: now },
+            );
+            valid
+        }
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err((StatusCode::UNAUTHORIZED, "Session has been revoked or expired".to_string()))
+    }
+}
+
+/// Drop a single session from the cache after it is deactivated.
+pub fn invalidate_session(session_id: &str) {
+    cache().lock().unwrap().remove(session_id);
+}
+
+/// Drop every cached session of a user, e.g. after a password reset.
+pub fn invalidate_user_sessions(user_id: Uuid) {
+    cache().lock().unwrap().remove_user(user_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(user_id: Uuid, checked_at: Instant) -> CachedSession {
+        CachedSession { user_id, valid: true, checked_at }
+    }
+
+    #[test]
+    fn cached_entry_expires_after_ttl() {
+        let mut c = SessionCache::default();
+        let start = Instant::now();
+        c.put("s1", entry(Uuid::nil(), start));
+
+        assert!(c.get("s1", start).is_some());
+        assert!(c.get("s1", start + SESSION_CACHE_TTL).is_none());
+    }
+
+    #[test]
+    fn remove_user_only_drops_that_users_sessions() {
+        let mut c = SessionCache::default();
+        let now = Instant::now();
+        let alice = Uuid::new_v4();
+        let bob = Uuid::new_v4();
+        c.put("a1", entry(alice, now));
+        c.put("a2", entry(alice, now));
+        c.put("b1", entry(bob, now));
+
+        c.remove_user(alice);
+
+        assert!(c.get("a1", now).is_none());
+        assert!(c.get("a2", now).is_none());
+        assert!(c.get("b1", now).is_some());
+    }
+}
@@ -0,0 +1,134 @@
+//! Brute-force protection for `login_handler`.
+//!
+//! Failures are counted per account and per client IP. Once a counter passes
+//! its free allowance, each further failure locks the key for an
+//! exponentially growing period (1 min, 2 min, 4 min … capped at 1 h).
+//! Counters reset after an hour without failures, and the account counter
+//! is cleared on a successful login.
+
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub const SCOPE_ACCOUNT: &str = "account";
+pub const SCOPE_IP: &str = "ip";
+
+/// Failures allowed before lockouts start. The IP allowance is generous
+/// because a whole computer lab can sit behind one campus NAT address.
+const ACCOUNT_FREE_ATTEMPTS: i32 = 5;
+const IP_FREE_ATTEMPTS: i32 = 30;
+const BASE_LOCKOUT_SECS: i64 = 60;
+const MAX_LOCKOUT_SECS: i64 = 60 * 60;
+
+fn free_attempts(scope: &str) -> i32 {
+    if scope == SCOPE_IP { IP_FREE_ATTEMPTS } else { ACCOUNT_FREE_ATTEMPTS }
+}
+
+/// Lockout length after the `failed_count`-th consecutive failure, or None
+/// while the key is still within its free allowance.
+fn lockout_duration(scope: &str, failed_count: i32) -> Option<Duration> {
+    let over = failed_count - free_attempts(scope);
+    if over < 0 {
+        return None;
+    }
+    let secs = BASE_LOCKOUT_SECS
+        .saturating_mul(1i64 << over.min(16))
+        .min(MAX_LOCKOUT_SECS);
+    Some(Duration::seconds(secs))
+}
+
+/// Latest `locked_until` still in the future across the given keys.
+pub async fn locked_until(
+    pool: &PgPool,
+    keys: &[(&str, &str)],
+) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    let (scopes, values): (Vec<String>, Vec<String>) = keys
+        .iter()
+        .map(|(s, k)| (s.to_string(), k.to_string()))
+        .unzip();
+    sqlx::query_scalar(
+        r#"
+        SELECT MAX(t.locked_until)
+        FROM login_throttles t
+        JOIN UNNEST($1::text[], $2::text[]) AS k(scope, key)
+          ON t.scope = k.scope AND t.key = k.key
+        WHERE t.locked_until > NOW()
+        "#,
+    )
+    .bind(&scopes)
+    .bind(&values)
+    .fetch_one(pool)
+    .await
+}
+
+/// Count one failure against `key` and lock it if it is over its allowance.
+pub async fn record_failure(pool: &PgPool, scope: &str, key: &str) -> Result<(), sqlx::Error> {
+    let failed_count: i32 = sqlx::query_scalar(
+        r#"
+        INSERT INTO login_throttles (scope, key, failed_count, last_failed_at)
+        VALUES ($1, $2, 1, NOW())
+        ON CONFLICT (scope, key) DO UPDATE SET
+            failed_count = CASE
+                WHEN login_throttles.last_failed_at < NOW() - INTERVAL '1 hour' THEN 1
+                ELSE login_throttles.failed_count + 1
+            END,
+            last_failed_at = NOW()
+        RETURNING failed_count
+        "#,
+    )
+    .bind(scope)
+    .bind(key)
+    .fetch_one(pool)
+    .await?;
+
+    if let Some(lock) = lockout_duration(scope, failed_count) {
+        sqlx::query("UPDATE login_throttles SET locked_until = $3 WHERE scope = $1 AND key = $2")
+            .bind(scope)
+            .bind(key)
+            .bind(Utc::now() + lock)
+            .execute(pool)
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn clear(pool: &PgPool, scope: &str, key: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM login_throttles WHERE scope = $1 AND key = $2")
+        .bind(scope)
+        .bind(key)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Admin unlock: wipe the account counter so the user can log in again.
+pub async fn clear_account(pool: &PgPool, user_id: Uuid) -> Result<(), sqlx::Error> {
+    clear(pool, SCOPE_ACCOUNT, &user_id.to_string()).await
+}
+
+pub fn lockout_message(until: DateTime<Utc>) -> String {
+    let minutes = ((until - Utc::now()).num_seconds().max(1) + 59) / 60;
+    format!(
+        "เข้าสู่ระบบผิดหลายครั้งเกินไป บัญชีถูกล็อกชั่วคราว กรุณาลองใหม่ใน {m} นาที / \
+         Too many failed login attempts. Please try again in {m} minute(s).",
+        m = minutes
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_lockout_starts_after_free_attempts_and_doubles() {
+        assert_eq!(lockout_duration(SCOPE_ACCOUNT, ACCOUNT_FREE_ATTEMPTS - 1), None);
+        assert_eq!(lockout_duration(SCOPE_ACCOUNT, ACCOUNT_FREE_ATTEMPTS), Some(Duration::seconds(60)));
+        assert_eq!(lockout_duration(SCOPE_ACCOUNT, ACCOUNT_FREE_ATTEMPTS + 2), Some(Duration::seconds(240)));
+    }
+
+    #[test]
+    fn lockout_is_capped() {
+        assert_eq!(lockout_duration(SCOPE_ACCOUNT, 1000), Some(Duration::seconds(MAX_LOCKOUT_SECS)));
+        assert_eq!(lockout_duration(SCOPE_IP, ACCOUNT_FREE_ATTEMPTS), None);
+    }
+}
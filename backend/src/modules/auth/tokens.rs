@@ -0,0 +1,96 @@
+//! Access / refresh token primitives.
+//!
+//! Access tokens are short-lived JWTs (`ACCESS_TOKEN_MINUTES`, default 15)
+//! carried in the `session_token` cookie or a Bearer header. Refresh tokens
+//! are opaque random strings; only their SHA-256 is stored, in
+//! `session_refresh_tokens`, so a database leak can't be replayed.
+
+use axum::http::StatusCode;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use super::keys::{self, KeyUse};
+use sha2::{Digest, Sha256};
+use crate::models::{AdminRole, User};
+use super::models::Claims;
+
+pub const ACCESS_COOKIE: &str = "session_token";
+pub const REFRESH_COOKIE: &str = "refresh_token";
+const DEFAULT_ACCESS_TOKEN_MINUTES: i64 = 15;
+
+pub fn access_token_ttl() -> Duration {
+    std::env::var("ACCESS_TOKEN_MINUTES")
+        .ok()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|m| *m > 0)
+        .map(Duration::minutes)
+        .unwrap_or_else(|| Duration::minutes(DEFAULT_ACCESS_TOKEN_MINUTES))
+}
+
+/// Access tokens never outlive the session they belong to.
+pub fn access_token_expiry(now: DateTime<Utc>, session_expires_at: DateTime<Utc>) -> DateTime<Utc> {
+    (now + access_token_ttl()).min(session_expires_at)
+}
+
+pub fn claims_for_user(
+    user: &User,
+    admin_role: Option<&AdminRole>,
+    session_id: &str,
+    expires_at: DateTime<Utc>,
+) -> Claims {
+    Claims {
+        sub: user.id.to_string(),
+        session_id: session_id.to_string(),
+        exp: expires_at.timestamp() as usize,
+        iat: Utc::now().timestamp() as usize,
+        student_id: user.student_id.clone(),
+        email: user.email.clone(),
+        first_name: user.first_name.clone(),
+        last_name: user.last_name.clone(),
+        department_id: user.department_id,
+        is_admin: admin_role.is_some(),
+        admin_level: admin_role.map(|r| r.admin_level.clone()),
+        organization_id: admin_role.and_then(|r| r.organization_id),
+        permissions: admin_role.map(|r| r.permissions.clone()).unwrap_or_default(),
+        impersonator_id: None,
+        api_key_id: None,
+    }
+}
+
+pub fn sign_access_token(claims: &Claims) -> Result<String, (StatusCode, String)> {
+    keys::sign(KeyUse::Session, claims)
+}
+
+/// Returns `(token, hash)`. Hand the token to the client, persist the hash.
+pub fn generate_refresh_token() -> (String, String) {
+    let bytes: [u8; 32] = rand::random();
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+    let hash = hash_refresh_token(&token);
+    (token, hash)
+}
+
+pub fn hash_refresh_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refresh_token_hash_is_stable_hex() {
+        let (token, hash) = generate_refresh_token();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_refresh_token(&token));
+        assert_ne!(generate_refresh_token().0, token);
+    }
+
+    #[test]
+    fn access_token_is_capped_by_session_expiry() {
+        let now = Utc::now();
+        let session_end = now + Duration::minutes(1);
+        assert_eq!(access_token_expiry(now, session_end), session_end);
+    }
+}
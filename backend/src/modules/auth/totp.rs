@@ -0,0 +1,300 @@
+//! RFC 6238 TOTP for admin two-factor authentication.
+//!
+//! Codes are 6 digits, HMAC-SHA1, 30-second steps — the defaults every
+//! authenticator app (Google Authenticator, Microsoft Authenticator, Authy)
+//! assumes when scanning an `otpauth://` URI. One step of clock drift is
+//! tolerated either way, and the last accepted step is persisted so a code
+//! can't be replayed inside its window.
+
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use super::keys::{self, KeyUse};
+use sha1::Sha1;
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::models::{AdminLevel, AdminRole};
+use super::models::TwoFactorChallengeClaims;
+use super::tokens::hash_refresh_token as hash_secret_code;
+
+const STEP_SECS: i64 = 30;
+const DIGITS: u32 = 6;
+const ALLOWED_DRIFT_STEPS: i64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const CHALLENGE_PURPOSE: &str = "2fa";
+const CHALLENGE_TTL_MINUTES: i64 = 5;
+
+pub fn base32_encode(data: &[u8]) -> String {
+    let mut out = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for &byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            out.push(BASE32_ALPHABET[((buffer >> (bits - 5)) & 31) as usize] as char);
+            bits -= 5;
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    out
+}
+
+pub fn base32_decode(input: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for c in input.chars().filter(|c| *c != '=' && !c.is_whitespace()) {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|&a| a as char == c.to_ascii_uppercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            out.push((buffer >> (bits - 8)) as u8);
+            bits -= 8;
+        }
+    }
+    Some(out)
+}
+
+/// 160-bit secret, base32-encoded as authenticator apps expect.
+pub fn generate_secret() -> String {
+    let bytes: [u8; 20] = rand::random();
+    base32_encode(&bytes)
+}
+
+/// HOTP value (RFC 4226) for one counter step.
+fn hotp(secret: &[u8], counter: u64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&counter.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    binary % 10u32.pow(DIGITS)
+}
+
+/// Returns the matching time step when `code` is valid at `unix_time`
+/// (± the allowed drift), so the caller can reject reuse of that step.
+pub fn verify_code(secret_b32: &str, code: &str, unix_time: i64) -> Option<i64> {
+    let secret = base32_decode(secret_b32)?;
+    let code = code.trim().replace(' ', "");
+    if code.len() != DIGITS as usize {
+        return None;
+    }
+    let expected: u32 = code.parse().ok()?;
+    let current = unix_time / STEP_SECS;
+    (current - ALLOWED_DRIFT_STEPS..=current + ALLOWED_DRIFT_STEPS)
+        .filter(|step| *step >= 0)
+        .find(|step| hotp(&secret, *step as u64) == expected)
+}
+
+pub fn otpauth_uri(secret_b32: &str, account: &str) -> String {
+    let issuer = "Trackivity";
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret_b32}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={STEP_SECS}",
+        account = account.replace(' ', "%20"),
+    )
+}
+
+/// Recovery codes look like `abcd-efgh-ijkl`; only their SHA-256 is stored.
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let bytes: [u8; 8] = rand::random();
+            let raw = base32_encode(&bytes).to_lowercase();
+            format!("{}-{}-{}", &raw[0..4], &raw[4..8], &raw[8..12])
+        })
+        .collect()
+}
+
+pub fn hash_recovery_code(code: &str) -> String {
+    hash_secret_code(&code.trim().to_lowercase())
+}
+
+pub async fn is_enabled(pool: &PgPool, user_id: Uuid) -> Result<bool, (StatusCode, String)> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL)",
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Whether policy requires this admin to have 2FA. Organization admins
+/// follow their organization's `require_admin_2fa`; super admins, who have
+/// no organization, follow `REQUIRE_SUPER_ADMIN_2FA`.
+pub async fn is_required(pool: &PgPool, role: &AdminRole) -> Result<bool, (StatusCode, String)> {
+    if matches!(role.admin_level, AdminLevel::SuperAdmin) {
+        return Ok(std::env::var("REQUIRE_SUPER_ADMIN_2FA").unwrap_or_default() == "true");
+    }
+    let Some(org_id) = role.organization_id else {
+        return Ok(false);
+    };
+    sqlx::query_scalar::<_, bool>("SELECT require_admin_2fa FROM organizations WHERE id = $1")
+        .bind(org_id)
+        .fetch_optional(pool)
+        .await
+        .map(|v| v.unwrap_or(false))
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// The admin role a new access token may carry. An admin whose policy
+/// requires 2FA but who hasn't enrolled yet gets a plain session (no admin
+/// claims) plus `setup_required = true`, enough to reach `/auth/2fa/setup`.
+pub async fn effective_admin_role(
+    pool: &PgPool,
+    user_id: Uuid,
+    admin_role: Option<AdminRole>,
+) -> Result<(Option<AdminRole>, bool), (StatusCode, String)> {
+    let Some(role) = admin_role else {
+        return Ok((None, false));
+    };
+    if is_required(pool, &role).await? && !is_enabled(pool, user_id).await? {
+        return Ok((None, true));
+    }
+    Ok((Some(role), false))
+}
+
+/// Short-lived token proving the password step succeeded; exchanged at
+/// `/auth/2fa/login` together with a code for the real session.
+pub fn sign_challenge(
+    user_id: Uuid,
+    remember_me: bool,
+    login_method: &str,
+) -> Result<(String, chrono::DateTime<Utc>), (StatusCode, String)> {
+    let expires_at = Utc::now() + Duration::minutes(CHALLENGE_TTL_MINUTES);
+    let claims = TwoFactorChallengeClaims {
+        sub: user_id.to_string(),
+        purpose: CHALLENGE_PURPOSE.to_string(),
+        remember_me,
+        login_method: login_method.to_string(),
+        exp: expires_at.timestamp() as usize,
+        iat: Utc::now().timestamp() as usize,
+    };
+    let token = keys::sign(KeyUse::Session, &claims)?;
+    Ok((token, expires_at))
+}
+
+pub fn verify_challenge(token: &str) -> Result<TwoFactorChallengeClaims, (StatusCode, String)> {
+    let invalid = || (StatusCode::UNAUTHORIZED, "Invalid or expired two-factor challenge".to_string());
+    let claims = keys::verify::<TwoFactorChallengeClaims>(KeyUse::Session, token)
+        .map_err(|_| invalid())?
+        .claims;
+    if claims.purpose != CHALLENGE_PURPOSE {
+        return Err(invalid());
+    }
+    Ok(claims)
+}
+
+/// Check a TOTP code or a recovery code for `user_id` and consume it.
+/// Returns false for a wrong, reused or already-spent code.
+pub async fn verify_and_consume(
+    pool: &PgPool,
+    user_id: Uuid,
+    code: Option<&str>,
+    recovery_code: Option<&str>,
+) -> Result<bool, (StatusCode, String)> {
+    if let Some(code) = code {
+        #[derive(sqlx::FromRow)]
+        struct TotpRow {
+            secret: String,
+            last_used_step: Option<i64>,
+        }
+        let Some(row) = sqlx::query_as::<_, TotpRow>(
+            "SELECT secret, last_used_step FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        else {
+            return Ok(false);
+        };
+
+        let Some(step) = verify_code(&row.secret, code, chrono::Utc::now().timestamp()) else {
+            return Ok(false);
+        };
+        if row.last_used_step.is_some_and(|last| step <= last) {
+            return Ok(false);
+        }
+        // Conditional update so two concurrent requests can't both spend
+        // the same step.
+        let updated = sqlx::query(
+            "UPDATE user_totp SET last_used_step = $2 WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)",
+        )
+        .bind(user_id)
+        .bind(step)
+        .execute(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        return Ok(updated.rows_affected() == 1);
+    }
+
+    if let Some(recovery_code) = recovery_code {
+        let updated = sqlx::query(
+            "UPDATE user_recovery_codes SET used_at = NOW() WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL",
+        )
+        .bind(user_id)
+        .bind(hash_recovery_code(recovery_code))
+        .execute(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        return Ok(updated.rows_affected() == 1);
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// RFC 6238 Appendix B, SHA1 column (8 digits there, last 6 here).
+    #[test]
+    fn matches_rfc6238_test_vectors() {
+        let secret = base32_encode(b"12345678901234567890");
+        assert_eq!(verify_code(&secret, "287082", 59), Some(1));
+        assert_eq!(verify_code(&secret, "081804", 1111111109), Some(1111111109 / 30));
+        assert_eq!(verify_code(&secret, "005924", 1234567890), Some(1234567890 / 30));
+        assert_eq!(verify_code(&secret, "000000", 59), None);
+    }
+
+    #[test]
+    fn base32_round_trips() {
+        let data = b"\x00\x01\xfehello world";
+        assert_eq!(base32_decode(&base32_encode(data)).unwrap(), data);
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+    }
+
+    #[test]
+    fn recovery_codes_are_unique_and_normalised_before_hashing() {
+        let codes = generate_recovery_codes();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert_eq!(
+            hash_recovery_code(&codes[0]),
+            hash_recovery_code(&format!(" {} ", codes[0].to_uppercase())),
+        );
+        assert_ne!(codes[0], codes[1]);
+    }
+
+    #[test]
+    fn challenge_carries_the_first_factor() {
+        std::env::set_var("JWT_SECRET", "test-secret");
+        let user_id = Uuid::new_v4();
+        let (token, _) = sign_challenge(user_id, true, "oidc").unwrap();
+        let claims = verify_challenge(&token).unwrap();
+        assert_eq!(claims.sub, user_id.to_string());
+        assert_eq!(claims.login_method, "oidc");
+        assert!(claims.remember_me);
+    }
+}
@@ -1,7 +1,9 @@
 use axum::{Json, extract::{State, Path}, http::{StatusCode, HeaderMap}};
 use sqlx::PgPool;
 use crate::models::AdminLevel;
+use crate::modules::audit::service::{self as audit, AuditEntry};
 use crate::modules::auth::get_claims_from_headers;
+use crate::modules::auth::permissions::{require_permission, Permission};
 use super::models::{DepartmentFull, CreateDepartmentInput, UpdateDepartmentInput};
 use uuid::Uuid;
 
@@ -42,10 +44,8 @@ pub async fn list_departments(
     State(pool): State<PgPool>,
     headers: HeaderMap,
 ) -> Result<Json<Vec<DepartmentFull>>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
-    }
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::DepartmentsView)?;
 
     let scope_org_id: Option<Uuid> = match claims.admin_level {
         Some(AdminLevel::SuperAdmin) => None,
@@ -91,10 +91,8 @@ pub async fn create_department(
     headers: HeaderMap,
     Json(payload): Json<CreateDepartmentInput>,
 ) -> Result<Json<DepartmentFull>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
-    }
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::DepartmentsManage)?;
 
     // Org / regular admin can only create departments inside their own
     // organization. Super admin can target any.
@@ -114,6 +112,10 @@ pub async fn create_department(
     let dept_id = Uuid::new_v4();
     let status = payload.status.unwrap_or(true);
 
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
     sqlx::query(r#"
         INSERT INTO departments (id, name, code, description, organization_id, status, created_at, updated_at)
         VALUES ($1, $2, $3, $4, $5, $6, NOW(), NOW())
@@ -124,7 +126,7 @@ pub async fn create_department(
     .bind(&payload.description)
     .bind(payload.organization_id)
     .bind(status)
-    .execute(&pool)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
         if let sqlx::Error::Database(ref db_err) = e {
@@ -135,6 +137,21 @@ pub async fn create_department(
         (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create department: {}", e))
     })?;
 
+    let after = audit::snapshot(&mut *tx, "departments", dept_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("department.create", "department", dept_id)
+            .organization(Some(payload.organization_id))
+            .after(after),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
     let dept = sqlx::query_as::<_, DepartmentFull>(r#"
         SELECT d.*, o.name AS organization_name,
             (SELECT COUNT(*) FROM users u WHERE u.department_id = d.id AND u.deleted_at IS NULL) AS students_count,
@@ -156,9 +173,15 @@ pub async fn update_department(
     Path(dept_id): Path<Uuid>,
     Json(payload): Json<UpdateDepartmentInput>,
 ) -> Result<Json<DepartmentFull>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::DepartmentsManage)?;
     assert_admin_can_manage_department(&pool, &claims, dept_id).await?;
 
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "departments", dept_id).await?;
+
     sqlx::query(r#"
         UPDATE departments SET
             name = COALESCE($2, name),
@@ -173,10 +196,26 @@ pub async fn update_department(
     .bind(payload.code)
     .bind(payload.description)
     .bind(payload.status)
-    .execute(&pool)
+    .execute(&mut *tx)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update department: {}", e)))?;
 
+    let after = audit::snapshot(&mut *tx, "departments", dept_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("department.update", "department", dept_id)
+            .organization(audit::uuid_field(&after, "organization_id"))
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
     let dept = sqlx::query_as::<_, DepartmentFull>(r#"
         SELECT d.*, o.name AS organization_name,
             (SELECT COUNT(*) FROM users u WHERE u.department_id = d.id AND u.deleted_at IS NULL) AS students_count,
@@ -198,15 +237,35 @@ pub async fn delete_department(
     headers: HeaderMap,
     Path(dept_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::DepartmentsManage)?;
     assert_admin_can_manage_department(&pool, &claims, dept_id).await?;
 
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "departments", dept_id).await?;
+
     sqlx::query("DELETE FROM departments WHERE id = $1")
         .bind(dept_id)
-        .execute(&pool)
+        .execute(&mut *tx)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete department: {}", e)))?;
 
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("department.delete", "department", dept_id)
+            .organization(audit::uuid_field(&before, "organization_id"))
+            .before(before),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
     Ok(Json(serde_json::json!({ "message": "Department deleted successfully" })))
 }
 
@@ -215,15 +274,37 @@ pub async fn toggle_department_status(
     headers: HeaderMap,
     Path(dept_id): Path<Uuid>,
 ) -> Result<Json<DepartmentFull>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::DepartmentsManage)?;
     assert_admin_can_manage_department(&pool, &claims, dept_id).await?;
 
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "departments", dept_id).await?;
+
     sqlx::query("UPDATE departments SET status = NOT status, updated_at = NOW() WHERE id = $1")
         .bind(dept_id)
-        .execute(&pool)
+        .execute(&mut *tx)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to toggle status: {}", e)))?;
 
+    let after = audit::snapshot(&mut *tx, "departments", dept_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("department.toggle_status", "department", dept_id)
+            .organization(audit::uuid_field(&after, "organization_id"))
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
     let dept = sqlx::query_as::<_, DepartmentFull>(r#"
         SELECT d.*, o.name AS organization_name,
             (SELECT COUNT(*) FROM users u WHERE u.department_id = d.id AND u.deleted_at IS NULL) AS students_count,
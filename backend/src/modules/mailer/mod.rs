@@ -0,0 +1,6 @@
+pub mod outbox;
+pub mod templates;
+pub mod transport;
+
+pub use templates::EmailTemplate;
+pub use transport::Mailer;
@@ -0,0 +1,195 @@
+//! Persisted outbox on top of `email_queue`.
+//!
+//! Handlers `enqueue` a rendered template and return; a single background
+//! worker claims due rows, hands them to the configured `Mailer` and
+//! reschedules failures with exponential backoff until `max_attempts`, so
+//! a provider hiccup delays a password-reset email instead of losing it.
+//!
+//! The worker doesn't poll: it sleeps until the earliest pending row is due
+//! and otherwise waits for an enqueue, so an idle queue sends no queries
+//! and Neon can suspend the database.
+
+use std::time::Duration;
+use axum::http::StatusCode;
+use sqlx::{PgExecutor, PgPool};
+use tokio::sync::Notify;
+use uuid::Uuid;
+use super::templates::EmailTemplate;
+use super::transport::{Mailer, OutgoingEmail};
+
+const MAX_ATTEMPTS: i32 = 5;
+const BATCH_SIZE: i64 = 10;
+/// Pause before looking again when the queue itself can't be read.
+const ERROR_BACKOFF: Duration = Duration::from_secs(60);
+/// A claimed row is hidden from other workers this long; if the process dies
+/// mid-send the row becomes due again afterwards.
+const CLAIM_LEASE_SECS: i64 = 300;
+
+/// Wakes the worker when a new email is queued.
+static WAKE: Notify = Notify::const_new();
+
+#[derive(sqlx::FromRow)]
+struct QueuedEmail {
+    id: Uuid,
+    to_email: String,
+    subject: String,
+    body_text: String,
+    body_html: Option<String>,
+    attempts: Option<i32>,
+    max_attempts: Option<i32>,
+}
+
+pub async fn enqueue<'e>(
+    executor: impl PgExecutor<'e>,
+    to: &str,
+    template: EmailTemplate<'_>,
+) -> Result<Uuid, (StatusCode, String)> {
+    let rendered = template.render();
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO email_queue (to_email, subject, body_text, body_html, max_attempts, metadata)
+        VALUES ($1, $2, $3, $4, $5, jsonb_build_object('template', $6::text))
+        RETURNING id
+        "#,
+    )
+    .bind(to)
+    .bind(&rendered.subject)
+    .bind(&rendered.text)
+    .bind(&rendered.html)
+    .bind(MAX_ATTEMPTS)
+    .bind(template.name())
+    .fetch_one(executor)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to queue email: {}", e)))?;
+
+    WAKE.notify_one();
+    Ok(id)
+}
+
+/// Wake the worker. `enqueue` already does this, but when it ran inside a
+/// transaction the worker may look before the row is committed; call this
+/// again once the transaction has committed.
+pub fn wake() {
+    WAKE.notify_one();
+}
+
+/// Seconds to wait before retrying after the `attempt`-th failure:
+/// 1 min, 5 min, 25 min, then hourly.
+pub fn retry_delay_secs(attempt: i32) -> i64 {
+    let exponent = attempt.clamp(1, 4) as u32 - 1;
+    (60 * 5_i64.pow(exponent)).min(3600)
+}
+
+/// Background worker; runs for the lifetime of the process.
+pub async fn run(pool: PgPool, mailer: Mailer) {
+    tracing::info!("Email outbox worker started (backend: {})", mailer.name());
+    loop {
+        let next_due = match deliver_due(&pool, &mailer).await {
+            // A full batch probably means more rows are already due.
+            Ok(n) if n as i64 == BATCH_SIZE => continue,
+            Ok(_) => next_due_in(&pool).await,
+            Err(e) => Err(e),
+        };
+        let next_due = next_due.unwrap_or_else(|e| {
+            tracing::error!("Email outbox poll failed: {}", e);
+            Some(ERROR_BACKOFF)
+        });
+        match next_due {
+            Some(duration) => tokio::select! {
+                _ = tokio::time::sleep(duration) => {}
+                _ = WAKE.notified() => {}
+            },
+            None => WAKE.notified().await,
+        }
+    }
+}
+
+/// How long until the earliest pending row (a retry or an expired claim)
+/// is due; `None` when nothing is pending.
+async fn next_due_in(pool: &PgPool) -> Result<Option<Duration>, sqlx::Error> {
+    let secs: Option<f64> = sqlx::query_scalar(
+        "SELECT EXTRACT(EPOCH FROM MIN(scheduled_for) - NOW())::float8 FROM email_queue WHERE status = 'pending'",
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(secs.map(|s| Duration::from_secs_f64(s.max(0.0))))
+}
+
+async fn deliver_due(pool: &PgPool, mailer: &Mailer) -> Result<usize, sqlx::Error> {
+    let batch = sqlx::query_as::<_, QueuedEmail>(
+        r#"
+        UPDATE email_queue
+        SET attempts = COALESCE(attempts, 0) + 1,
+            scheduled_for = NOW() + make_interval(secs => $2),
+            updated_at = NOW()
+        WHERE id IN (
+            SELECT id FROM email_queue
+            WHERE status = 'pending' AND scheduled_for <= NOW()
+            ORDER BY priority DESC, scheduled_for
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, to_email, subject, body_text, body_html, attempts, max_attempts
+        "#,
+    )
+    .bind(BATCH_SIZE)
+    .bind(CLAIM_LEASE_SECS as f64)
+    .fetch_all(pool)
+    .await?;
+
+    for row in &batch {
+        let email = OutgoingEmail {
+            to: row.to_email.clone(),
+            subject: row.subject.clone(),
+            text: row.body_text.clone(),
+            html: row.body_html.clone(),
+        };
+        let attempts = row.attempts.unwrap_or(1);
+        match mailer.send(&email).await {
+            Ok(()) => {
+                tracing::info!("Email \"{}\" sent to {}", row.subject, row.to_email);
+                sqlx::query(
+                    "UPDATE email_queue SET status = 'sent', sent_at = NOW(), error_message = NULL, updated_at = NOW() WHERE id = $1",
+                )
+                .bind(row.id)
+                .execute(pool)
+                .await?;
+            }
+            Err(e) if attempts >= row.max_attempts.unwrap_or(MAX_ATTEMPTS) => {
+                tracing::error!("Giving up on email {} to {} after {} attempts: {}", row.id, row.to_email, attempts, e);
+                sqlx::query(
+                    "UPDATE email_queue SET status = 'failed', error_message = $2, updated_at = NOW() WHERE id = $1",
+                )
+                .bind(row.id)
+                .bind(&e)
+                .execute(pool)
+                .await?;
+            }
+            Err(e) => {
+                let delay = retry_delay_secs(attempts);
+                tracing::warn!("Email {} to {} failed (attempt {}), retrying in {}s: {}", row.id, row.to_email, attempts, delay, e);
+                sqlx::query(
+                    "UPDATE email_queue SET scheduled_for = NOW() + make_interval(secs => $2), error_message = $3, updated_at = NOW() WHERE id = $1",
+                )
+                .bind(row.id)
+                .bind(delay as f64)
+                .bind(&e)
+                .execute(pool)
+                .await?;
+            }
+        }
+    }
+
+    Ok(batch.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_backs_off_and_caps_at_an_hour() {
+        let delays: Vec<i64> = (1..=6).map(retry_delay_secs).collect();
+        assert_eq!(delays, vec![60, 300, 1500, 3600, 3600, 3600]);
+    }
+}
@@ -0,0 +1,56 @@
+//! Subjects and bodies of every transactional email, rendered once at
+//! enqueue time so the outbox row holds exactly what will be sent.
+
+pub enum EmailTemplate<'a> {
+    PasswordReset { link: &'a str, expires_minutes: i64 },
+    VerifyEmail { link: &'a str, expires_hours: i64 },
+}
+
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+impl EmailTemplate<'_> {
+    /// Stored in `email_queue.metadata` to tell queued emails apart.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EmailTemplate::PasswordReset { .. } => "password_reset",
+            EmailTemplate::VerifyEmail { .. } => "verify_email",
+        }
+    }
+
+    pub fn render(&self) -> RenderedEmail {
+        match self {
+            EmailTemplate::PasswordReset { link, expires_minutes } => RenderedEmail {
+                subject: "Trackivity - คำขอตั้งรหัสผ่านใหม่".to_string(),
+                html: format!(
+                    r#"<h2>คุณได้ขอรีเซ็ตรหัสผ่าน</h2>
+            <p>กรุณาคลิกที่ลิงก์ด้านล่างเพื่อตั้งรหัสผ่านใหม่สำหรับบัญชี Trackivity ของคุณ:</p>
+            <p><a href="{}">เปลี่ยนรหัสผ่าน</a></p>
+            <p>ลิงก์นี้จะหมดอายุภายใน {} นาที หากคุณไม่ได้ส่งคำขอนี้ หรือเป็นความผิดพลาด คุณสามารถเพิกเฉยต่ออีเมลฉบับนี้ได้เลย</p>"#,
+                    link, expires_minutes
+                ),
+                text: format!(
+                    "คุณได้ขอรีเซ็ตรหัสผ่าน\n\nเปิดลิงก์นี้เพื่อตั้งรหัสผ่านใหม่สำหรับบัญชี Trackivity ของคุณ:\n{}\n\nลิงก์นี้จะหมดอายุภายใน {} นาที หากคุณไม่ได้ส่งคำขอนี้ คุณสามารถเพิกเฉยต่ออีเมลฉบับนี้ได้เลย\n",
+                    link, expires_minutes
+                ),
+            },
+            EmailTemplate::VerifyEmail { link, expires_hours } => RenderedEmail {
+                subject: "Trackivity - ยืนยันอีเมลของคุณ".to_string(),
+                html: format!(
+                    r#"<h2>ยืนยันอีเมลสำหรับบัญชี Trackivity</h2>
+            <p>กรุณาคลิกที่ลิงก์ด้านล่างเพื่อยืนยันอีเมลและเปิดใช้งานบัญชีของคุณ:</p>
+            <p><a href="{}">ยืนยันอีเมล</a></p>
+            <p>ลิงก์นี้จะหมดอายุภายใน {} ชั่วโมง หากคุณไม่ได้สมัครสมาชิก คุณสามารถเพิกเฉยต่ออีเมลฉบับนี้ได้เลย</p>"#,
+                    link, expires_hours
+                ),
+                text: format!(
+                    "ยืนยันอีเมลสำหรับบัญชี Trackivity\n\nเปิดลิงก์นี้เพื่อยืนยันอีเมลและเปิดใช้งานบัญชีของคุณ:\n{}\n\nลิงก์นี้จะหมดอายุภายใน {} ชั่วโมง หากคุณไม่ได้สมัครสมาชิก คุณสามารถเพิกเฉยต่ออีเมลฉบับนี้ได้เลย\n",
+                    link, expires_hours
+                ),
+            },
+        }
+    }
+}
@@ -0,0 +1,155 @@
+//! Where outgoing email actually goes, chosen once at startup by
+//! `MAIL_BACKEND`: `resend` (HTTP API), `smtp` (university relay, MailHog),
+//! `file` (one `.eml` per message, for development) or `log` (recipient and
+//! subject only — the body carries live reset/verification links).
+
+use std::time::Duration;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncFileTransport, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+const DEFAULT_FROM: &str = "Trackivity <admin@utrackivity.com>";
+
+pub struct OutgoingEmail {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    pub html: Option<String>,
+}
+
+pub enum Mailer {
+    Resend { api_key: String, from: String, client: reqwest::Client },
+    Smtp { transport: AsyncSmtpTransport<Tokio1Executor>, from: Mailbox },
+    File { transport: AsyncFileTransport<Tokio1Executor>, from: Mailbox },
+    Log,
+}
+
+fn env_opt(name: &str) -> Option<String> {
+    std::env::var(name).ok().map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+impl Mailer {
+    /// Without `MAIL_BACKEND`, Resend is used when `RESEND_API_KEY` is set;
+    /// otherwise startup fails rather than quietly dropping every email.
+    pub fn from_env() -> Result<Self, String> {
+        let from = env_opt("MAIL_FROM").unwrap_or_else(|| DEFAULT_FROM.to_string());
+        let backend = match env_opt("MAIL_BACKEND") {
+            Some(backend) => backend,
+            None if env_opt("RESEND_API_KEY").is_some() => "resend".to_string(),
+            None => {
+                return Err("No mail backend configured: set RESEND_API_KEY, or MAIL_BACKEND \
+                            to smtp, file or log"
+                    .to_string())
+            }
+        };
+
+        match backend.to_lowercase().as_str() {
+            "resend" => {
+                let api_key = env_opt("RESEND_API_KEY")
+                    .ok_or("MAIL_BACKEND=resend requires RESEND_API_KEY")?;
+                let client = reqwest::Client::builder()
+                    .timeout(Duration::from_secs(15))
+                    .build()
+                    .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+                Ok(Mailer::Resend { api_key, from, client })
+            }
+            "smtp" => {
+                let host = env_opt("SMTP_HOST").ok_or("MAIL_BACKEND=smtp requires SMTP_HOST")?;
+                let security = env_opt("SMTP_SECURITY").unwrap_or_else(|| "starttls".to_string());
+                let mut builder = match security.to_lowercase().as_str() {
+                    "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host),
+                    "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host),
+                    "none" => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host)),
+                    other => return Err(format!("Unknown SMTP_SECURITY '{}' (use tls, starttls or none)", other)),
+                }
+                .map_err(|e| format!("Invalid SMTP_HOST: {}", e))?
+                .timeout(Some(Duration::from_secs(30)));
+                if let Some(port) = env_opt("SMTP_PORT") {
+                    builder = builder.port(port.parse().map_err(|_| "SMTP_PORT must be a number")?);
+                }
+                if let Some(username) = env_opt("SMTP_USERNAME") {
+                    let password = env_opt("SMTP_PASSWORD").unwrap_or_default();
+                    builder = builder.credentials(Credentials::new(username, password));
+                }
+                Ok(Mailer::Smtp { transport: builder.build(), from: parse_mailbox(&from)? })
+            }
+            "file" => {
+                let dir = env_opt("MAIL_FILE_DIR").unwrap_or_else(|| "mail-outbox".to_string());
+                std::fs::create_dir_all(&dir)
+                    .map_err(|e| format!("Failed to create MAIL_FILE_DIR {}: {}", dir, e))?;
+                Ok(Mailer::File { transport: AsyncFileTransport::new(dir), from: parse_mailbox(&from)? })
+            }
+            "log" => {
+                tracing::warn!("MAIL_BACKEND=log: emails are not delivered, only their recipient and subject are logged");
+                Ok(Mailer::Log)
+            }
+            other => Err(format!("Unknown MAIL_BACKEND '{}' (use resend, smtp, file or log)", other)),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mailer::Resend { .. } => "resend",
+            Mailer::Smtp { .. } => "smtp",
+            Mailer::File { .. } => "file",
+            Mailer::Log => "log",
+        }
+    }
+
+    /// Deliver one email. An `Err` is worth retrying later.
+    pub async fn send(&self, email: &OutgoingEmail) -> Result<(), String> {
+        match self {
+            Mailer::Resend { api_key, from, client } => {
+                let payload = serde_json::json!({
+                    "from": from,
+                    "to": [email.to],
+                    "subject": email.subject,
+                    "text": email.text,
+                    "html": email.html,
+                });
+                let resp = client
+                    .post("https://api.resend.com/emails")
+                    .bearer_auth(api_key)
+                    .json(&payload)
+                    .send()
+                    .await
+                    .map_err(|e| format!("Resend network error: {}", e))?;
+                let status = resp.status();
+                if status.is_success() {
+                    Ok(())
+                } else {
+                    let body = resp.text().await.unwrap_or_default();
+                    Err(format!("Resend API returned {}: {}", status, body))
+                }
+            }
+            Mailer::Smtp { transport, from } => {
+                let message = build_message(from, email)?;
+                transport.send(message).await.map(|_| ()).map_err(|e| format!("SMTP error: {}", e))
+            }
+            Mailer::File { transport, from } => {
+                let message = build_message(from, email)?;
+                transport.send(message).await.map(|_| ()).map_err(|e| format!("File transport error: {}", e))
+            }
+            Mailer::Log => {
+                tracing::info!("[mail:log] to={} subject=\"{}\" (body not logged)", email.to, email.subject);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn parse_mailbox(address: &str) -> Result<Mailbox, String> {
+    address.parse().map_err(|e| format!("Invalid address '{}': {}", address, e))
+}
+
+fn build_message(from: &Mailbox, email: &OutgoingEmail) -> Result<Message, String> {
+    let builder = Message::builder()
+        .from(from.clone())
+        .to(parse_mailbox(&email.to)?)
+        .subject(email.subject.clone());
+    match &email.html {
+        Some(html) => builder.multipart(MultiPart::alternative_plain_html(email.text.clone(), html.clone())),
+        None => builder.body(email.text.clone()),
+    }
+    .map_err(|e| format!("Failed to build message: {}", e))
+}
@@ -6,3 +6,6 @@ pub mod departments;
 pub mod admins;
 pub mod qr;
 pub mod notifications;
+pub mod audit;
+pub mod api_keys;
+pub mod mailer;
@@ -2,6 +2,7 @@ use axum::{Json, extract::{State, Path}, http::{StatusCode, HeaderMap}};
 use sqlx::PgPool;
 use uuid::Uuid;
 use crate::modules::auth::get_claims_from_headers;
+use crate::modules::auth::impersonation::forbid_while_impersonating;
 
 #[derive(serde::Deserialize)]
 pub struct PushSubscriptionPayload {
@@ -31,7 +32,8 @@ pub async fn subscribe_push(
     headers: HeaderMap,
     Json(payload): Json<PushSubscriptionPayload>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    forbid_while_impersonating(&claims)?;
     let user_id = Uuid::parse_str(&claims.sub)
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
 
@@ -56,7 +58,7 @@ pub async fn list_notifications(
     State(pool): State<PgPool>,
     headers: HeaderMap,
 ) -> Result<Json<Vec<NotificationPublic>>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
     let user_id = Uuid::parse_str(&claims.sub)
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
 
@@ -101,7 +103,7 @@ pub async fn mark_read(
     headers: HeaderMap,
     Path(id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
     let user_id = Uuid::parse_str(&claims.sub)
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
 
@@ -123,7 +125,7 @@ pub async fn mark_all_read(
     State(pool): State<PgPool>,
     headers: HeaderMap,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
     let user_id = Uuid::parse_str(&claims.sub)
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
 
@@ -0,0 +1,201 @@
+//! Requirement compliance across an organization's students: completed
+//! faculty/university hours against `org_activity_requirements` and the
+//! shortfall, for faculty admins preparing graduation audits.
+//!
+//! "Students" are active, non-admin users whose department belongs to the
+//! organization. Hours use the same effective-credit rule as the student's
+//! own progress view, and everything is aggregated in SQL.
+
+use axum::{Json, extract::{Path, Query, State}, http::{HeaderMap, StatusCode}};
+use sqlx::PgPool;
+use uuid::Uuid;
+use crate::models::AdminLevel;
+use crate::modules::activities::credit;
+use crate::modules::auth::get_claims_from_headers;
+use crate::modules::auth::permissions::{require_permission, Permission};
+use super::handlers::{DEFAULT_REQUIRED_FACULTY_HOURS, DEFAULT_REQUIRED_UNIVERSITY_HOURS};
+use super::models::{
+    ComplianceFilter, ComplianceReport, ComplianceReportQuery, ComplianceRow, ComplianceSort, SortOrder,
+};
+
+/// Per-student hours and shortfall. Binds: $1 organization, $2 department
+/// filter, $3 cohort prefix, $4/$5 required faculty/university hours.
+fn scoped_rows_sql() -> String {
+    format!(
+        r#"
+        WITH students AS (
+            SELECT u.id, u.student_id, u.prefix, u.first_name, u.last_name,
+                   u.department_id, d.name AS department_name
+            FROM users u
+            JOIN departments d ON u.department_id = d.id
+            WHERE d.organization_id = $1
+              AND u.deleted_at IS NULL
+              AND u.status = 'active'::user_status
+              AND NOT EXISTS (SELECT 1 FROM admin_roles ar WHERE ar.user_id = u.id)
+              AND ($2::uuid IS NULL OR u.department_id = $2)
+              AND ($3::text IS NULL OR u.student_id LIKE $3 || '%')
+        ),
+        hours AS (
+            SELECT p.user_id,
+                   SUM(CASE WHEN a.activity_level = 'faculty' THEN {credited_hours} ELSE 0 END) AS faculty_hours,
+                   SUM(CASE WHEN a.activity_level = 'university' THEN {credited_hours} ELSE 0 END) AS university_hours
+            FROM participations p
+            JOIN activities a ON p.activity_id = a.id
+            WHERE p.user_id IN (SELECT id FROM students)
+              AND p.status IN ('checked_out', 'completed')
+            GROUP BY p.user_id
+        ),
+        scoped AS (
+            SELECT s.id AS user_id, s.student_id, s.prefix, s.first_name, s.last_name,
+                   s.department_id, s.department_name,
+                   COALESCE(h.faculty_hours, 0)::float8 AS faculty_hours,
+                   COALESCE(h.university_hours, 0)::float8 AS university_hours,
+                   GREATEST($4 - COALESCE(h.faculty_hours, 0), 0)::float8 AS faculty_shortfall,
+                   GREATEST($5 - COALESCE(h.university_hours, 0), 0)::float8 AS university_shortfall
+            FROM students s
+            LEFT JOIN hours h ON h.user_id = s.id
+        )
+        "#,
+        credited_hours = credit::EFFECTIVE_HOURS_SQL,
+    )
+}
+
+/// The student-ID prefix to filter on; blank means no filter. Only letters
+/// and digits, so it can't smuggle `LIKE` wildcards.
+fn cohort_prefix(raw: Option<&str>) -> Result<Option<&str>, (StatusCode, String)> {
+    let cohort = raw.map(str::trim).filter(|s| !s.is_empty());
+    if cohort.is_some_and(|c| !c.chars().all(|ch| ch.is_ascii_alphanumeric())) {
+        return Err((StatusCode::BAD_REQUEST, "cohort must be letters or digits".to_string()));
+    }
+    Ok(cohort)
+}
+
+pub async fn compliance_report(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(organization_id): Path<Uuid>,
+    Query(params): Query<ComplianceReportQuery>,
+) -> Result<Json<ComplianceReport>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::UsersView)?;
+    if !matches!(claims.admin_level, Some(AdminLevel::SuperAdmin))
+        && claims.organization_id != Some(organization_id)
+    {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Cannot view another organization's report".to_string(),
+        ));
+    }
+
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(50).clamp(1, 200);
+    let offset = (page - 1) * per_page;
+
+    let cohort = cohort_prefix(params.cohort.as_deref())?;
+
+    let (required_faculty_hours, required_university_hours) = sqlx::query_as::<_, (i32, i32)>(
+        "SELECT required_faculty_hours, required_university_hours FROM org_activity_requirements WHERE organization_id = $1",
+    )
+    .bind(organization_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .unwrap_or((DEFAULT_REQUIRED_FACULTY_HOURS, DEFAULT_REQUIRED_UNIVERSITY_HOURS));
+
+    let status_clause = match params.status {
+        ComplianceFilter::All => "TRUE",
+        ComplianceFilter::Behind => "(faculty_shortfall > 0 OR university_shortfall > 0)",
+        ComplianceFilter::Met => "(faculty_shortfall = 0 AND university_shortfall = 0)",
+    };
+    let sort_column = match params.sort {
+        ComplianceSort::StudentId => "student_id",
+        ComplianceSort::Name => "first_name || ' ' || last_name",
+        ComplianceSort::FacultyShortfall => "faculty_shortfall",
+        ComplianceSort::UniversityShortfall => "university_shortfall",
+        ComplianceSort::TotalShortfall => "faculty_shortfall + university_shortfall",
+    };
+    let direction = match params.order {
+        SortOrder::Asc => "ASC",
+        SortOrder::Desc => "DESC",
+    };
+
+    let (total, students_in_scope, behind) = sqlx::query_as::<_, (i64, i64, i64)>(&format!(
+        r#"
+        {scoped}
+        SELECT COUNT(*) FILTER (WHERE {status_clause}),
+               COUNT(*),
+               COUNT(*) FILTER (WHERE faculty_shortfall > 0 OR university_shortfall > 0)
+        FROM scoped
+        "#,
+        scoped = scoped_rows_sql(),
+    ))
+    .bind(organization_id)
+    .bind(params.department_id)
+    .bind(cohort)
+    .bind(required_faculty_hours)
+    .bind(required_university_hours)
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to build report: {}", e)))?;
+
+    let students = sqlx::query_as::<_, ComplianceRow>(&format!(
+        r#"
+        {scoped}
+        SELECT * FROM scoped
+        WHERE {status_clause}
+        ORDER BY {sort_column} {direction}, student_id
+        LIMIT $6 OFFSET $7
+        "#,
+        scoped = scoped_rows_sql(),
+    ))
+    .bind(organization_id)
+    .bind(params.department_id)
+    .bind(cohort)
+    .bind(required_faculty_hours)
+    .bind(required_university_hours)
+    .bind(per_page)
+    .bind(offset)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to build report: {}", e)))?;
+
+    Ok(Json(ComplianceReport {
+        organization_id,
+        required_faculty_hours,
+        required_university_hours,
+        total,
+        students_in_scope,
+        behind,
+        page,
+        per_page,
+        students,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cohort_is_a_plain_prefix() {
+        assert_eq!(cohort_prefix(None).unwrap(), None);
+        assert_eq!(cohort_prefix(Some("  ")).unwrap(), None);
+        assert_eq!(cohort_prefix(Some(" 64 ")).unwrap(), Some("64"));
+        assert!(cohort_prefix(Some("6%")).is_err());
+        assert!(cohort_prefix(Some("6_")).is_err());
+    }
+
+    #[test]
+    fn query_defaults_to_everyone_by_largest_shortfall() {
+        let q: ComplianceReportQuery = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(q.status, ComplianceFilter::All);
+        assert!(matches!(q.sort, ComplianceSort::TotalShortfall));
+        assert!(matches!(q.order, SortOrder::Desc));
+
+        let q: ComplianceReportQuery =
+            serde_json::from_value(serde_json::json!({ "status": "behind", "sort": "name", "order": "asc" })).unwrap();
+        assert_eq!(q.status, ComplianceFilter::Behind);
+        assert!(matches!(q.sort, ComplianceSort::Name));
+        assert!(matches!(q.order, SortOrder::Asc));
+    }
+}
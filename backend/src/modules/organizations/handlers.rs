@@ -1,24 +1,18 @@
 use axum::{Json, extract::{State, Path}, http::{StatusCode, HeaderMap}};
 use sqlx::PgPool;
 use crate::models::{AdminLevel, Organization, OrganizationType};
+use crate::modules::audit::service::{self as audit, AuditEntry};
 use crate::modules::auth::get_claims_from_headers;
+use crate::modules::auth::permissions::{require_permission, Permission};
 use super::models::{Department, OrganizationsResponse, GroupedOrganizations, CreateOrganizationInput, UpdateOrganizationInput};
 use uuid::Uuid;
 
-fn require_super_admin(
-    claims: &crate::modules::auth::models::Claims,
-) -> Result<(), (StatusCode, String)> {
-    if !matches!(claims.admin_level, Some(AdminLevel::SuperAdmin)) {
-        return Err((StatusCode::FORBIDDEN, "Super admin access required".to_string()));
-    }
-    Ok(())
-}
-
 pub async fn get_all_organizations(
     State(pool): State<PgPool>,
 ) -> Result<Json<OrganizationsResponse>, (StatusCode, String)> {
     let organizations = sqlx::query_as::<_, Organization>(r#"
-        SELECT id, name, code, description, organization_type, status, created_at, updated_at
+        SELECT id, name, code, description, organization_type, status, require_admin_2fa, require_registration_approval,
+               no_show_block_threshold, no_show_window_days, no_show_block_days, created_at, updated_at
         FROM organizations
         WHERE status = TRUE
         ORDER BY name ASC
@@ -46,13 +40,12 @@ pub async fn list_all_organizations_admin(
     State(pool): State<PgPool>,
     headers: HeaderMap,
 ) -> Result<Json<Vec<Organization>>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
-    }
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::OrganizationsView)?;
 
     let organizations = sqlx::query_as::<_, Organization>(r#"
-        SELECT id, name, code, description, organization_type, status, created_at, updated_at
+        SELECT id, name, code, description, organization_type, status, require_admin_2fa, require_registration_approval,
+               no_show_block_threshold, no_show_window_days, no_show_block_days, created_at, updated_at
         FROM organizations
         ORDER BY name ASC
     "#)
@@ -68,12 +61,16 @@ pub async fn create_organization(
     headers: HeaderMap,
     Json(payload): Json<CreateOrganizationInput>,
 ) -> Result<Json<Organization>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    require_super_admin(&claims)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::OrganizationsManage)?;
 
     let org_id = Uuid::new_v4();
     let status = payload.status.unwrap_or(true);
 
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
     sqlx::query(r#"
         INSERT INTO organizations (id, name, code, description, organization_type, status, created_at, updated_at)
         VALUES ($1, $2, $3, $4, $5, $6, NOW(), NOW())
@@ -84,7 +81,7 @@ pub async fn create_organization(
     .bind(&payload.description)
     .bind(&payload.organization_type)
     .bind(status)
-    .execute(&pool)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
         if let sqlx::Error::Database(ref db_err) = e {
@@ -95,6 +92,21 @@ pub async fn create_organization(
         (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to create organization: {}", e))
     })?;
 
+    let after = audit::snapshot(&mut *tx, "organizations", org_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("organization.create", "organization", org_id)
+            .organization(Some(org_id))
+            .after(after),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
     let org = sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE id = $1")
         .bind(org_id)
         .fetch_one(&pool)
@@ -110,8 +122,20 @@ pub async fn update_organization(
     Path(org_id): Path<Uuid>,
     Json(payload): Json<UpdateOrganizationInput>,
 ) -> Result<Json<Organization>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    require_super_admin(&claims)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::OrganizationsManage)?;
+
+    if payload.no_show_block_threshold.is_some_and(|v| v < 0)
+        || payload.no_show_window_days.is_some_and(|v| v < 1)
+        || payload.no_show_block_days.is_some_and(|v| v < 1)
+    {
+        return Err((StatusCode::BAD_REQUEST, "Invalid no-show policy".to_string()));
+    }
+
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "organizations", org_id).await?;
 
     sqlx::query(r#"
         UPDATE organizations SET
@@ -120,6 +144,11 @@ pub async fn update_organization(
             description = COALESCE($4, description),
             organization_type = COALESCE($5, organization_type),
             status = COALESCE($6, status),
+            require_admin_2fa = COALESCE($7, require_admin_2fa),
+            require_registration_approval = COALESCE($8, require_registration_approval),
+            no_show_block_threshold = COALESCE($9, no_show_block_threshold),
+            no_show_window_days = COALESCE($10, no_show_window_days),
+            no_show_block_days = COALESCE($11, no_show_block_days),
             updated_at = NOW()
         WHERE id = $1
     "#)
@@ -129,10 +158,31 @@ pub async fn update_organization(
     .bind(payload.description)
     .bind(payload.organization_type)
     .bind(payload.status)
-    .execute(&pool)
+    .bind(payload.require_admin_2fa)
+    .bind(payload.require_registration_approval)
+    .bind(payload.no_show_block_threshold)
+    .bind(payload.no_show_window_days)
+    .bind(payload.no_show_block_days)
+    .execute(&mut *tx)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update organization: {}", e)))?;
 
+    let after = audit::snapshot(&mut *tx, "organizations", org_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("organization.update", "organization", org_id)
+            .organization(Some(org_id))
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
     let org = sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE id = $1")
         .bind(org_id)
         .fetch_optional(&pool)
@@ -148,15 +198,34 @@ pub async fn delete_organization(
     headers: HeaderMap,
     Path(org_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    require_super_admin(&claims)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::OrganizationsManage)?;
+
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "organizations", org_id).await?;
 
     sqlx::query("DELETE FROM organizations WHERE id = $1")
         .bind(org_id)
-        .execute(&pool)
+        .execute(&mut *tx)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to delete organization: {}", e)))?;
 
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("organization.delete", "organization", org_id)
+            .organization(Some(org_id))
+            .before(before),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
     Ok(Json(serde_json::json!({ "message": "Organization deleted successfully" })))
 }
 
@@ -165,15 +234,36 @@ pub async fn toggle_organization_status(
     headers: HeaderMap,
     Path(org_id): Path<Uuid>,
 ) -> Result<Json<Organization>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    require_super_admin(&claims)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::OrganizationsManage)?;
+
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "organizations", org_id).await?;
 
     sqlx::query("UPDATE organizations SET status = NOT status, updated_at = NOW() WHERE id = $1")
         .bind(org_id)
-        .execute(&pool)
+        .execute(&mut *tx)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to toggle status: {}", e)))?;
 
+    let after = audit::snapshot(&mut *tx, "organizations", org_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("organization.toggle_status", "organization", org_id)
+            .organization(Some(org_id))
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
     let org = sqlx::query_as::<_, Organization>("SELECT * FROM organizations WHERE id = $1")
         .bind(org_id)
         .fetch_optional(&pool)
@@ -202,6 +292,10 @@ pub async fn get_departments(
     Ok(Json(departments))
 }
 
+/// Hours required when an organization hasn't configured its own.
+pub const DEFAULT_REQUIRED_FACULTY_HOURS: i32 = 6;
+pub const DEFAULT_REQUIRED_UNIVERSITY_HOURS: i32 = 12;
+
 pub async fn get_activity_requirements(
     State(pool): State<PgPool>,
     Path(organization_id): Path<Uuid>,
@@ -223,8 +317,8 @@ pub async fn get_activity_requirements(
         Ok(Json(super::models::OrgActivityRequirements {
             id: Uuid::nil(),
             organization_id,
-            required_faculty_hours: 6, // Default values can be adjusted here if needed
-            required_university_hours: 12, // Default values can be adjusted here if needed
+            required_faculty_hours: DEFAULT_REQUIRED_FACULTY_HOURS,
+            required_university_hours: DEFAULT_REQUIRED_UNIVERSITY_HOURS,
             created_at: None,
             updated_at: None,
             created_by: Uuid::nil(),
@@ -238,10 +332,8 @@ pub async fn update_activity_requirements(
     Path(organization_id): Path<Uuid>,
     Json(payload): Json<super::models::UpdateOrgActivityRequirementsInput>,
 ) -> Result<Json<super::models::OrgActivityRequirements>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
-    }
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::OrganizationsRequirements)?;
     // Super admin can edit any org's requirements; organization / regular
     // admin can only edit their own org's. Without this an org admin from
     // Faculty A could rewrite Faculty B's required hours by knowing the URL.
@@ -254,6 +346,17 @@ pub async fn update_activity_requirements(
         ));
     }
 
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = sqlx::query_scalar::<_, serde_json::Value>(
+        "SELECT to_jsonb(r) FROM org_activity_requirements r WHERE organization_id = $1",
+    )
+    .bind(organization_id)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
     // Upsert the requirements
     let reqs = sqlx::query_as::<_, super::models::OrgActivityRequirements>(r#"
         INSERT INTO org_activity_requirements (organization_id, required_faculty_hours, required_university_hours, created_by)
@@ -268,9 +371,24 @@ pub async fn update_activity_requirements(
     .bind(payload.required_faculty_hours)
     .bind(payload.required_university_hours)
     .bind(Uuid::parse_str(&claims.sub).unwrap_or_default())
-    .fetch_one(&pool)
+    .fetch_one(&mut *tx)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to update requirements: {}", e)))?;
 
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("organization.update_requirements", "org_requirements", reqs.id)
+            .organization(Some(organization_id))
+            .before(before)
+            .after(serde_json::to_value(&reqs).ok()),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
     Ok(Json(reqs))
 }
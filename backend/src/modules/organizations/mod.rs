@@ -1,3 +1,4 @@
+pub mod compliance;
 pub mod handlers;
 pub mod models;
 
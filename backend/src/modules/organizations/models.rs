@@ -44,6 +44,16 @@ pub struct UpdateOrganizationInput {
     pub description: Option<String>,
     pub organization_type: Option<OrganizationType>,
     pub status: Option<bool>,
+    /// Require every admin of this organization to enrol in TOTP 2FA.
+    pub require_admin_2fa: Option<bool>,
+    /// Hold new self-registrations as `pending` until an admin approves them.
+    pub require_registration_approval: Option<bool>,
+    /// Block registration after this many no-shows within
+    /// `no_show_window_days`; 0 turns the penalty off.
+    pub no_show_block_threshold: Option<i32>,
+    pub no_show_window_days: Option<i32>,
+    /// How long the block lasts after the latest no-show.
+    pub no_show_block_days: Option<i32>,
 }
 
 #[derive(Debug, Serialize, FromRow)]
@@ -76,3 +86,79 @@ pub struct UpdateOrgActivityRequirementsInput {
     pub required_faculty_hours: i32,
     pub required_university_hours: i32,
 }
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ComplianceFilter {
+    #[default]
+    All,
+    /// Short of at least one requirement.
+    Behind,
+    Met,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ComplianceSort {
+    StudentId,
+    Name,
+    FacultyShortfall,
+    UniversityShortfall,
+    #[default]
+    TotalShortfall,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ComplianceReportQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub department_id: Option<Uuid>,
+    /// Admission year as it leads the student ID, e.g. `64` for 64xxxxxxxx.
+    pub cohort: Option<String>,
+    #[serde(default)]
+    pub status: ComplianceFilter,
+    #[serde(default)]
+    pub sort: ComplianceSort,
+    #[serde(default)]
+    pub order: SortOrder,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ComplianceRow {
+    pub user_id: Uuid,
+    pub student_id: String,
+    pub prefix: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub department_id: Option<Uuid>,
+    pub department_name: Option<String>,
+    pub faculty_hours: f64,
+    pub university_hours: f64,
+    pub faculty_shortfall: f64,
+    pub university_shortfall: f64,
+}
+
+/// `GET /organizations/{id}/compliance`. `students` is the requested page
+/// and `total` the size of the filtered list for paging; `students_in_scope`
+/// and `behind` summarise everyone matching the department/cohort filters
+/// regardless of `status`.
+#[derive(Debug, Serialize)]
+pub struct ComplianceReport {
+    pub organization_id: Uuid,
+    pub required_faculty_hours: i32,
+    pub required_university_hours: i32,
+    pub total: i64,
+    pub students_in_scope: i64,
+    pub behind: i64,
+    pub page: i64,
+    pub per_page: i64,
+    pub students: Vec<ComplianceRow>,
+}
@@ -1,16 +1,22 @@
 use axum::{
-    extract::{State, Path},
+    extract::{Query, State, Path},
     http::{HeaderMap, StatusCode},
     response::Json,
 };
 use sqlx::PgPool;
 use chrono::Utc;
 use uuid::Uuid;
-use jsonwebtoken::{encode, decode, EncodingKey, DecodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 
+use crate::modules::activities::{credit, handlers::assert_admin_can_manage_activity};
+use crate::modules::audit::service::{self as audit, AuditEntry};
 use crate::modules::auth::handlers::get_claims_from_headers;
-use super::models::QRGenerateResponse;
+use crate::modules::auth::impersonation::forbid_while_impersonating;
+use crate::modules::auth::keys::{self, KeyUse};
+use crate::modules::auth::permissions::{require_permission, Permission};
+use crate::models::AdminLevel;
+use crate::modules::auth::models::Claims;
+use super::models::{QRGenerateResponse, CreateScannerGrantInput, ListScannerGrantsQuery, ScannerGrant, RosterEntry, SessionRosterEntry};
 use crate::modules::notifications::service::{NotificationService, NotificationType};
 
 // ─── QR Token Claims (embedded in QR code) ────────────────────────────────────
@@ -46,6 +52,20 @@ pub struct ScanQRData {
     pub participation_status: String,
     pub checked_in_at: Option<chrono::DateTime<Utc>>,
     pub checked_out_at: Option<chrono::DateTime<Utc>>,
+    /// Present for multi-session activities.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session: Option<ScanSessionProgress>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScanSessionProgress {
+    pub session_id: Uuid,
+    pub session_title: Option<String>,
+    pub checked_in_at: Option<chrono::DateTime<Utc>>,
+    pub checked_out_at: Option<chrono::DateTime<Utc>>,
+    /// Sessions checked in and out so far.
+    pub sessions_attended: i64,
+    pub sessions_required: i64,
 }
 
 #[derive(Debug, Serialize)]
@@ -59,9 +79,11 @@ pub struct ScanQRError {
 
 pub async fn generate_qr_handler(
     headers: HeaderMap,
+    State(pool): State<PgPool>,
 ) -> Result<Json<QRGenerateResponse>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)
+    let claims = get_claims_from_headers(&pool, &headers).await
         .map_err(|e| (StatusCode::UNAUTHORIZED, format!("Unauthorized: {}", e.1)))?;
+    forbid_while_impersonating(&claims)?;
 
     let now_ts = Utc::now().timestamp();
     let expires_ts = now_ts + (3 * 60); // 3 minutes
@@ -76,14 +98,7 @@ pub async fn generate_qr_handler(
         jti: jti.clone(),
     };
 
-    let secret = std::env::var("JWT_SECRET")
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "JWT_SECRET not configured".to_string()))?;
-
-    let token = encode(
-        &Header::default(),
-        &qr_claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    ).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to generate QR token: {}", e)))?;
+    let token = keys::sign(KeyUse::Qr, &qr_claims)?;
 
     Ok(Json(QRGenerateResponse {
         id: jti,
@@ -93,6 +108,59 @@ pub async fn generate_qr_handler(
     }))
 }
 
+// ─── Scan Access ──────────────────────────────────────────────────────────────
+
+/// Admins need `qr.scan`; anyone else needs a live scanner grant covering
+/// the activity, either directly or through its organizer organization.
+async fn assert_can_scan(
+    pool: &PgPool,
+    claims: &Claims,
+    activity_id: Uuid,
+) -> Result<(), (StatusCode, String)> {
+    if claims.is_admin {
+        return require_permission(claims, Permission::QrScan);
+    }
+    // A student's scanner grant doesn't extend to an admin acting as them.
+    forbid_while_impersonating(claims)?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
+    let granted: bool = sqlx::query_scalar(r#"
+        SELECT EXISTS (
+            SELECT 1
+            FROM scanner_grants g
+            JOIN activities a ON a.id = $2
+            WHERE g.user_id = $1
+              AND g.revoked_at IS NULL
+              AND (g.expires_at IS NULL OR g.expires_at > NOW())
+              AND (g.activity_id = a.id OR g.organization_id = a.organizer_id)
+        )
+    "#)
+    .bind(user_id)
+    .bind(activity_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !granted {
+        return Err((StatusCode::FORBIDDEN, "Scanner access to this activity is required".to_string()));
+    }
+    Ok(())
+}
+
+/// Roster access: scan access, and for admins other than super admins the
+/// activity must also belong to their organization, since the roster lists
+/// students' IDs, names and attendance. Scanner grants are already scoped.
+async fn assert_can_read_roster(
+    pool: &PgPool,
+    claims: &Claims,
+    activity_id: Uuid,
+) -> Result<(), (StatusCode, String)> {
+    assert_can_scan(pool, claims, activity_id).await?;
+    if claims.is_admin {
+        assert_admin_can_manage_activity(pool, claims, activity_id).await?;
+    }
+    Ok(())
+}
+
 // ─── Check-in Handler ─────────────────────────────────────────────────────────
 
 pub async fn checkin_handler(
@@ -101,11 +169,9 @@ pub async fn checkin_handler(
     Path(activity_id): Path<Uuid>,
     Json(payload): Json<ScanQRRequest>,
 ) -> Result<Json<ScanQRResponse>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)
+    let claims = get_claims_from_headers(&pool, &headers).await
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()))?;
-    if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, "Only admins may scan QR".to_string()));
-    }
+    assert_can_scan(&pool, &claims, activity_id).await?;
 
     scan_qr(&pool, activity_id, &payload.qr_data, "checkin").await
 }
@@ -118,11 +184,9 @@ pub async fn checkout_handler(
     Path(activity_id): Path<Uuid>,
     Json(payload): Json<ScanQRRequest>,
 ) -> Result<Json<ScanQRResponse>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)
+    let claims = get_claims_from_headers(&pool, &headers).await
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()))?;
-    if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, "Only admins may scan QR".to_string()));
-    }
+    assert_can_scan(&pool, &claims, activity_id).await?;
 
     scan_qr(&pool, activity_id, &payload.qr_data, "checkout").await
 }
@@ -136,13 +200,7 @@ async fn scan_qr(
     mode: &str,
 ) -> Result<Json<ScanQRResponse>, (StatusCode, String)> {
     // 1. Verify QR JWT token
-    let secret = std::env::var("JWT_SECRET")
-        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "JWT_SECRET not configured".to_string()))?;
-    let token_data = decode::<QRTokenClaims>(
-        qr_data,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &Validation::default(),
-    );
+    let token_data = keys::verify::<QRTokenClaims>(KeyUse::Qr, qr_data);
 
     let qr_claims = match token_data {
         Ok(data) => data.claims,
@@ -262,7 +320,19 @@ async fn scan_qr(
         }
     };
 
-    // 5. Check participation record
+    // 5. Multi-session activities record attendance per session instead.
+    let has_sessions: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM activity_sessions WHERE activity_id = $1)"
+    )
+    .bind(activity_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if has_sessions {
+        return scan_session(pool, activity_id, &activity_title, student_id, user_name, user.student_id, mode).await;
+    }
+
+    // 6. Check participation record
     #[derive(sqlx::FromRow)]
     struct ParticipationRow {
         id: Uuid,
@@ -322,6 +392,7 @@ async fn scan_qr(
                             participation_status: "checked_in".to_string(),
                             checked_in_at: Some(now),
                             checked_out_at: None,
+                            session: None,
                         }),
                         error: None,
                     }))
@@ -336,6 +407,7 @@ async fn scan_qr(
                             participation_status: p.status,
                             checked_in_at: p.checked_in_at,
                             checked_out_at: p.checked_out_at,
+                            session: None,
                         }),
                         error: Some(ScanQRError {
                             code: "ALREADY_CHECKED_IN".to_string(),
@@ -354,6 +426,7 @@ async fn scan_qr(
                             participation_status: p.status,
                             checked_in_at: p.checked_in_at,
                             checked_out_at: p.checked_out_at,
+                            session: None,
                         }),
                         error: Some(ScanQRError {
                             code: "ALREADY_COMPLETED".to_string(),
@@ -392,6 +465,7 @@ async fn scan_qr(
                             participation_status: "checked_in".to_string(),
                             checked_in_at: Some(now),
                             checked_out_at: None,
+                            session: None,
                         }),
                         error: None,
                     }))
@@ -427,6 +501,7 @@ async fn scan_qr(
                             participation_status: p.status,
                             checked_in_at: p.checked_in_at,
                             checked_out_at: p.checked_out_at,
+                            session: None,
                         }),
                         error: Some(ScanQRError {
                             code: code.to_string(),
@@ -445,6 +520,7 @@ async fn scan_qr(
                     .execute(pool)
                     .await
                     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+                    credit_participant(pool, activity_id, student_id).await?;
 
                     // 🔔 Notify user that they checked out
                     let _ = NotificationService::send(
@@ -465,6 +541,7 @@ async fn scan_qr(
                             participation_status: "checked_out".to_string(),
                             checked_in_at: p.checked_in_at,
                             checked_out_at: Some(now),
+                            session: None,
                         }),
                         error: None,
                     }))
@@ -474,3 +551,617 @@ async fn scan_qr(
         _ => Err((StatusCode::BAD_REQUEST, "Invalid mode".to_string())),
     }
 }
+
+async fn credit_participant(pool: &PgPool, activity_id: Uuid, user_id: Uuid) -> Result<(), (StatusCode, String)> {
+    let mut conn = pool.acquire()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    credit::recompute(&mut conn, activity_id, Some(&[user_id])).await
+}
+
+// ─── Multi-session Scan ───────────────────────────────────────────────────────
+
+/// Check-in opens this long before a session starts and check-out stays
+/// open this long after it ends.
+const SESSION_SCAN_GRACE_MINUTES: i32 = 30;
+
+/// A session whose scan window is open now, with this student's attendance.
+#[derive(Debug, Clone, sqlx::FromRow)]
+struct SessionCandidate {
+    id: Uuid,
+    title: Option<String>,
+    starts_at: chrono::DateTime<Utc>,
+    checked_in: bool,
+    checked_out: bool,
+}
+
+/// Scan windows overlap for back-to-back sessions, so pick by mode: check-out
+/// closes the earliest session the student is still checked in to; check-in
+/// opens the session starting closest to now that they haven't entered yet.
+/// Failing that, the closest session, so the response can say why nothing
+/// changed.
+fn pick_session<'a>(
+    candidates: &'a [SessionCandidate],
+    mode: &str,
+    now: chrono::DateTime<Utc>,
+) -> Option<&'a SessionCandidate> {
+    let distance = |c: &&SessionCandidate| (c.starts_at - now).num_seconds().abs();
+    let preferred = if mode == "checkout" {
+        candidates.iter().filter(|c| c.checked_in && !c.checked_out).min_by_key(|c| c.starts_at)
+    } else {
+        candidates.iter().filter(|c| !c.checked_in).min_by_key(distance)
+    };
+    preferred.or_else(|| candidates.iter().min_by_key(distance))
+}
+
+/// A participation completes once it has checked out of `required`
+/// sessions. An activity with nothing to attend can't be completed this way.
+fn sessions_complete(attended: i64, required: i64) -> bool {
+    required > 0 && attended >= required
+}
+
+fn scan_error(message: String, code: &str, detail: &str, category: &str, data: Option<ScanQRData>) -> Json<ScanQRResponse> {
+    Json(ScanQRResponse {
+        success: false,
+        message,
+        data,
+        error: Some(ScanQRError {
+            code: code.to_string(),
+            message: detail.to_string(),
+            category: category.to_string(),
+        }),
+    })
+}
+
+/// Check-in / check-out against an open session (see `pick_session`). The
+/// participation is created on first check-in (walk-in) and becomes
+/// `completed` once the student has attended `required_sessions` (default:
+/// all) sessions.
+async fn scan_session(
+    pool: &PgPool,
+    activity_id: Uuid,
+    activity_title: &str,
+    user_id: Uuid,
+    user_name: String,
+    student_id: String,
+    mode: &str,
+) -> Result<Json<ScanQRResponse>, (StatusCode, String)> {
+    let candidates = sqlx::query_as::<_, SessionCandidate>(r#"
+        SELECT
+            s.id, s.title,
+            (s.session_date + s.start_time) AT TIME ZONE 'Asia/Bangkok' AS starts_at,
+            sa.participation_id IS NOT NULL AS checked_in,
+            sa.checked_out_at IS NOT NULL AS checked_out
+        FROM activity_sessions s
+        LEFT JOIN participations p ON p.activity_id = s.activity_id AND p.user_id = $3
+        LEFT JOIN session_attendance sa ON sa.session_id = s.id AND sa.participation_id = p.id
+        WHERE s.activity_id = $1
+          AND NOW() >= ((s.session_date + s.start_time) AT TIME ZONE 'Asia/Bangkok') - make_interval(mins => $2)
+          AND NOW() <= ((s.session_date + s.end_time) AT TIME ZONE 'Asia/Bangkok') + make_interval(mins => $2)
+    "#)
+    .bind(activity_id)
+    .bind(SESSION_SCAN_GRACE_MINUTES)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let Some(session) = pick_session(&candidates, mode, Utc::now()).cloned() else {
+        return Ok(scan_error(
+            "ขณะนี้ไม่มีรอบกิจกรรมที่เปิดให้สแกน".to_string(),
+            "NO_ACTIVE_SESSION",
+            "No session is running now",
+            "error",
+            None,
+        ));
+    };
+    let session_label = session.title.clone().unwrap_or_else(|| "รอบนี้".to_string());
+
+    if mode == "checkin" {
+        // Walk-ins get a participation on their first scan; a registered
+        // student moves to checked_in. Later sessions leave it alone.
+        sqlx::query(r#"
+            INSERT INTO participations (user_id, activity_id, status, registered_at, checked_in_at)
+            VALUES ($1, $2, 'checked_in'::participation_status, NOW(), NOW())
+            ON CONFLICT (user_id, activity_id) DO UPDATE
+            SET status = 'checked_in'::participation_status, checked_in_at = NOW()
+            WHERE participations.status = 'registered'::participation_status
+        "#)
+        .bind(user_id)
+        .bind(activity_id)
+        .execute(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    } else if mode != "checkout" {
+        return Err((StatusCode::BAD_REQUEST, "Invalid mode".to_string()));
+    }
+
+    #[derive(sqlx::FromRow)]
+    struct ParticipationRow {
+        id: Uuid,
+        status: String,
+        checked_in_at: Option<chrono::DateTime<Utc>>,
+        checked_out_at: Option<chrono::DateTime<Utc>>,
+    }
+
+    let participation = sqlx::query_as::<_, ParticipationRow>(
+        "SELECT id, status::text AS status, checked_in_at, checked_out_at FROM participations WHERE user_id = $1 AND activity_id = $2"
+    )
+    .bind(user_id)
+    .bind(activity_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let Some(participation) = participation else {
+        return Ok(scan_error(
+            format!("{} ยังไม่ได้เช็คอิน", user_name),
+            "NOT_CHECKED_IN",
+            "Not checked in yet",
+            "error",
+            None,
+        ));
+    };
+
+    let changed = if mode == "checkin" {
+        sqlx::query(r#"
+            INSERT INTO session_attendance (session_id, participation_id)
+            VALUES ($1, $2)
+            ON CONFLICT (session_id, participation_id) DO NOTHING
+        "#)
+    } else {
+        sqlx::query(r#"
+            UPDATE session_attendance SET checked_out_at = NOW()
+            WHERE session_id = $1 AND participation_id = $2 AND checked_out_at IS NULL
+        "#)
+    }
+    .bind(session.id)
+    .bind(participation.id)
+    .execute(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .rows_affected() > 0;
+
+    #[derive(sqlx::FromRow)]
+    struct ProgressRow {
+        checked_in_at: Option<chrono::DateTime<Utc>>,
+        checked_out_at: Option<chrono::DateTime<Utc>>,
+        sessions_attended: i64,
+        sessions_required: i64,
+    }
+
+    let progress = sqlx::query_as::<_, ProgressRow>(r#"
+        SELECT
+            (SELECT checked_in_at FROM session_attendance WHERE session_id = $1 AND participation_id = $2) AS checked_in_at,
+            (SELECT checked_out_at FROM session_attendance WHERE session_id = $1 AND participation_id = $2) AS checked_out_at,
+            (SELECT COUNT(*) FROM session_attendance WHERE participation_id = $2 AND checked_out_at IS NOT NULL) AS sessions_attended,
+            COALESCE(
+                (SELECT required_sessions FROM activities WHERE id = $3)::bigint,
+                (SELECT COUNT(*) FROM activity_sessions WHERE activity_id = $3)
+            ) AS sessions_required
+    "#)
+    .bind(session.id)
+    .bind(participation.id)
+    .bind(activity_id)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut participation_status = participation.status;
+    let mut participation_checked_out_at = participation.checked_out_at;
+    if mode == "checkout"
+        && changed
+        && sessions_complete(progress.sessions_attended, progress.sessions_required)
+        && participation_status != "completed"
+    {
+        sqlx::query(
+            "UPDATE participations SET status = 'completed'::participation_status, checked_out_at = NOW() WHERE id = $1"
+        )
+        .bind(participation.id)
+        .execute(pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        participation_status = "completed".to_string();
+        participation_checked_out_at = Some(Utc::now());
+        credit_participant(pool, activity_id, user_id).await?;
+    }
+
+    let data = ScanQRData {
+        user_name: user_name.clone(),
+        student_id,
+        participation_status,
+        checked_in_at: participation.checked_in_at,
+        checked_out_at: participation_checked_out_at,
+        session: Some(ScanSessionProgress {
+            session_id: session.id,
+            session_title: session.title,
+            checked_in_at: progress.checked_in_at,
+            checked_out_at: progress.checked_out_at,
+            sessions_attended: progress.sessions_attended,
+            sessions_required: progress.sessions_required,
+        }),
+    };
+
+    if !changed {
+        return Ok(match (mode, progress.checked_in_at, progress.checked_out_at) {
+            ("checkin", _, _) => scan_error(
+                format!("{} เช็คอิน{}ไปแล้ว", user_name, session_label),
+                "ALREADY_CHECKED_IN",
+                "Already checked in to this session",
+                "warning",
+                Some(data),
+            ),
+            (_, None, _) => scan_error(
+                format!("{} ยังไม่ได้เช็คอิน{}", user_name, session_label),
+                "NOT_CHECKED_IN_YET",
+                "Not checked in to this session",
+                "error",
+                Some(data),
+            ),
+            _ => scan_error(
+                format!("{} เช็คเอาท์{}ไปแล้ว", user_name, session_label),
+                "ALREADY_CHECKED_OUT",
+                "Already checked out of this session",
+                "error",
+                Some(data),
+            ),
+        });
+    }
+
+    let (title, body, message) = if mode == "checkin" {
+        (
+            format!("✅ เช็คอินสำเร็จ: {}", activity_title),
+            format!("คุณได้เช็คอิน{}ของกิจกรรม {}", session_label, activity_title),
+            format!("เช็คอิน{}สำเร็จ! ยินดีต้อนรับ {}", session_label, user_name),
+        )
+    } else {
+        (
+            format!("✅ เช็คเอาท์สำเร็จ: {}", activity_title),
+            format!(
+                "คุณได้เช็คเอาท์{}ของกิจกรรม {} (เข้าร่วมแล้ว {}/{} รอบ)",
+                session_label, activity_title, progress.sessions_attended, progress.sessions_required
+            ),
+            format!(
+                "เช็คเอาท์{}สำเร็จ! {} เข้าร่วมแล้ว {}/{} รอบ",
+                session_label, user_name, progress.sessions_attended, progress.sessions_required
+            ),
+        )
+    };
+    let _ = NotificationService::send(
+        pool,
+        user_id,
+        &title,
+        &body,
+        NotificationType::Success,
+        Some(&format!("/student/activities/{}", activity_id)),
+    ).await;
+
+    Ok(Json(ScanQRResponse {
+        success: true,
+        message,
+        data: Some(data),
+        error: None,
+    }))
+}
+
+// ─── Roster ───────────────────────────────────────────────────────────────────
+
+/// Participants of one activity, for the scanning screen. Open to scanner
+/// grant holders and to admins of the organizing organization.
+pub async fn activity_roster_handler(
+    headers: HeaderMap,
+    State(pool): State<PgPool>,
+    Path(activity_id): Path<Uuid>,
+) -> Result<Json<Vec<RosterEntry>>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    assert_can_read_roster(&pool, &claims, activity_id).await?;
+
+    let roster = sqlx::query_as::<_, RosterEntry>(r#"
+        SELECT p.id AS participation_id, u.id AS user_id, u.student_id, u.first_name, u.last_name,
+               p.status::text AS status, p.registered_at, p.checked_in_at, p.checked_out_at
+        FROM participations p
+        JOIN users u ON u.id = p.user_id
+        WHERE p.activity_id = $1
+        ORDER BY u.student_id
+    "#)
+    .bind(activity_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(roster))
+}
+
+/// Every participant of the activity with their attendance for one session.
+pub async fn session_roster_handler(
+    headers: HeaderMap,
+    State(pool): State<PgPool>,
+    Path((activity_id, session_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Vec<SessionRosterEntry>>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    assert_can_read_roster(&pool, &claims, activity_id).await?;
+
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM activity_sessions WHERE id = $1 AND activity_id = $2)"
+    )
+    .bind(session_id)
+    .bind(activity_id)
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, "Session not found".to_string()));
+    }
+
+    let roster = sqlx::query_as::<_, SessionRosterEntry>(r#"
+        SELECT p.id AS participation_id, u.id AS user_id, u.student_id, u.first_name, u.last_name,
+               p.status::text AS status, sa.checked_in_at, sa.checked_out_at
+        FROM participations p
+        JOIN users u ON u.id = p.user_id
+        LEFT JOIN session_attendance sa ON sa.participation_id = p.id AND sa.session_id = $2
+        WHERE p.activity_id = $1
+        ORDER BY u.student_id
+    "#)
+    .bind(activity_id)
+    .bind(session_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(roster))
+}
+
+// ─── Scanner Grants ───────────────────────────────────────────────────────────
+
+const SCANNER_GRANT_SELECT: &str = r#"
+    SELECT g.id, g.user_id, u.student_id, (u.first_name || ' ' || u.last_name) AS user_name,
+           g.activity_id, a.title AS activity_title,
+           g.organization_id, o.name AS organization_name,
+           g.expires_at, g.granted_by, g.created_at
+    FROM scanner_grants g
+    JOIN users u ON u.id = g.user_id
+    LEFT JOIN activities a ON a.id = g.activity_id
+    LEFT JOIN organizations o ON o.id = g.organization_id
+"#;
+
+/// Organization that owns the grant's scope, for admin scope checks.
+async fn scope_org_of(
+    pool: &PgPool,
+    activity_id: Option<Uuid>,
+    organization_id: Option<Uuid>,
+) -> Result<Uuid, (StatusCode, String)> {
+    match (activity_id, organization_id) {
+        (Some(activity_id), None) => sqlx::query_scalar("SELECT organizer_id FROM activities WHERE id = $1")
+            .bind(activity_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .ok_or((StatusCode::NOT_FOUND, "Activity not found".to_string())),
+        (None, Some(organization_id)) => Ok(organization_id),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            "Specify exactly one of activity_id or organization_id".to_string(),
+        )),
+    }
+}
+
+fn assert_admin_in_org(claims: &Claims, org_id: Uuid) -> Result<(), (StatusCode, String)> {
+    if matches!(claims.admin_level, Some(AdminLevel::SuperAdmin)) || claims.organization_id == Some(org_id) {
+        return Ok(());
+    }
+    Err((StatusCode::FORBIDDEN, "Cannot manage scanners of another organization".to_string()))
+}
+
+pub async fn create_scanner_grant(
+    headers: HeaderMap,
+    State(pool): State<PgPool>,
+    Json(payload): Json<CreateScannerGrantInput>,
+) -> Result<Json<ScannerGrant>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ScannersManage)?;
+    let scope_org = scope_org_of(&pool, payload.activity_id, payload.organization_id).await?;
+    assert_admin_in_org(&claims, scope_org)?;
+
+    if payload.organization_id.is_some() && payload.expires_at.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "Organization-wide scanner access needs an expiry date".to_string()));
+    }
+    if payload.expires_at.is_some_and(|t| t <= Utc::now()) {
+        return Err((StatusCode::BAD_REQUEST, "expires_at must be in the future".to_string()));
+    }
+
+    let user_id: Uuid = sqlx::query_scalar(
+        "SELECT id FROM users WHERE student_id = $1 AND deleted_at IS NULL AND status = 'active'",
+    )
+    .bind(payload.student_id.trim())
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))?;
+
+    let granted_by = Uuid::parse_str(&claims.sub).ok();
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let grant_id: Uuid = sqlx::query_scalar(r#"
+        INSERT INTO scanner_grants (user_id, activity_id, organization_id, expires_at, granted_by)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+    "#)
+    .bind(user_id)
+    .bind(payload.activity_id)
+    .bind(payload.organization_id)
+    .bind(payload.expires_at)
+    .bind(granted_by)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to grant scanner access: {}", e)))?;
+
+    let after = audit::snapshot(&mut *tx, "scanner_grants", grant_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("scanner_grant.create", "scanner_grant", grant_id)
+            .organization(Some(scope_org))
+            .after(after),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
+    let grant = sqlx::query_as::<_, ScannerGrant>(&format!("{} WHERE g.id = $1", SCANNER_GRANT_SELECT))
+        .bind(grant_id)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(grant))
+}
+
+/// Active grants, optionally filtered to one activity or organization.
+/// Non-super admins only see grants scoped to their own organization.
+pub async fn list_scanner_grants(
+    headers: HeaderMap,
+    State(pool): State<PgPool>,
+    Query(query): Query<ListScannerGrantsQuery>,
+) -> Result<Json<Vec<ScannerGrant>>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ScannersManage)?;
+    let scope_org_id: Option<Uuid> = match claims.admin_level {
+        Some(AdminLevel::SuperAdmin) => None,
+        _ => claims.organization_id,
+    };
+
+    let grants = sqlx::query_as::<_, ScannerGrant>(&format!(r#"
+        {}
+        WHERE g.revoked_at IS NULL
+          AND (g.expires_at IS NULL OR g.expires_at > NOW())
+          AND ($1::uuid IS NULL OR g.activity_id = $1)
+          AND ($2::uuid IS NULL OR g.organization_id = $2)
+          AND ($3::uuid IS NULL OR COALESCE(g.organization_id, a.organizer_id) = $3)
+        ORDER BY g.created_at DESC
+    "#, SCANNER_GRANT_SELECT))
+    .bind(query.activity_id)
+    .bind(query.organization_id)
+    .bind(scope_org_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(grants))
+}
+
+pub async fn revoke_scanner_grant(
+    headers: HeaderMap,
+    State(pool): State<PgPool>,
+    Path(grant_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::ScannersManage)?;
+
+    let (activity_id, organization_id): (Option<Uuid>, Option<Uuid>) = sqlx::query_as(
+        "SELECT activity_id, organization_id FROM scanner_grants WHERE id = $1 AND revoked_at IS NULL",
+    )
+    .bind(grant_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::NOT_FOUND, "Scanner grant not found".to_string()))?;
+    let scope_org = scope_org_of(&pool, activity_id, organization_id).await?;
+    assert_admin_in_org(&claims, scope_org)?;
+
+    let mut tx = pool.begin()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "scanner_grants", grant_id).await?;
+
+    sqlx::query("UPDATE scanner_grants SET revoked_at = NOW() WHERE id = $1")
+        .bind(grant_id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let after = audit::snapshot(&mut *tx, "scanner_grants", grant_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("scanner_grant.revoke", "scanner_grant", grant_id)
+            .organization(Some(scope_org))
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
+    tx.commit()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
+    Ok(Json(serde_json::json!({ "success": true })))
+}
+
+/// The caller's own live grants, so the app can show the scanner screen.
+pub async fn my_scanner_grants(
+    headers: HeaderMap,
+    State(pool): State<PgPool>,
+) -> Result<Json<Vec<ScannerGrant>>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
+
+    let grants = sqlx::query_as::<_, ScannerGrant>(&format!(r#"
+        {}
+        WHERE g.user_id = $1
+          AND g.revoked_at IS NULL
+          AND (g.expires_at IS NULL OR g.expires_at > NOW())
+        ORDER BY g.created_at DESC
+    "#, SCANNER_GRANT_SELECT))
+    .bind(user_id)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(grants))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(hour: u32, minute: u32) -> chrono::DateTime<Utc> {
+        use chrono::TimeZone;
+        Utc.with_ymd_and_hms(2026, 10, 17, hour, minute, 0).unwrap()
+    }
+
+    fn candidate(start_hour: u32, checked_in: bool, checked_out: bool) -> SessionCandidate {
+        SessionCandidate { id: Uuid::new_v4(), title: None, starts_at: at(start_hour, 0), checked_in, checked_out }
+    }
+
+    #[test]
+    fn back_to_back_sessions_resolve_by_mode() {
+        // 09:00-12:00 then 12:00-15:00; both windows are open at 11:45.
+        let morning_open = [candidate(9, true, false), candidate(12, false, false)];
+        assert_eq!(pick_session(&morning_open, "checkout", at(11, 45)).unwrap().id, morning_open[0].id);
+        assert_eq!(pick_session(&morning_open, "checkin", at(11, 45)).unwrap().id, morning_open[1].id);
+
+        let morning_done = [candidate(9, true, true), candidate(12, false, false)];
+        assert_eq!(pick_session(&morning_done, "checkin", at(11, 40)).unwrap().id, morning_done[1].id);
+        // Nothing open to check out of: the closest session explains why.
+        assert_eq!(pick_session(&morning_done, "checkout", at(12, 20)).unwrap().id, morning_done[1].id);
+
+        // Already in both: check-in reports the closest, check-out the earlier.
+        let both = [candidate(9, true, false), candidate(12, true, false)];
+        assert_eq!(pick_session(&both, "checkin", at(12, 10)).unwrap().id, both[1].id);
+        assert_eq!(pick_session(&both, "checkout", at(12, 10)).unwrap().id, both[0].id);
+
+        assert!(pick_session(&[], "checkin", at(12, 0)).is_none());
+    }
+
+    #[test]
+    fn completion_needs_the_required_number_of_sessions() {
+        assert!(!sessions_complete(1, 2));
+        assert!(sessions_complete(2, 2));
+        assert!(sessions_complete(3, 2));
+        assert!(!sessions_complete(0, 0));
+    }
+}
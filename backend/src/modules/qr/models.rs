@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct QRDataPayload {
@@ -14,3 +17,62 @@ pub struct QRGenerateResponse {
     pub qr_data: String,
     pub expires_at: i64,
 }
+
+/// Grant scanner access to `student_id` for one activity, or for every
+/// activity of one organization (then `expires_at` is required).
+#[derive(Debug, Deserialize)]
+pub struct CreateScannerGrantInput {
+    pub student_id: String,
+    pub activity_id: Option<Uuid>,
+    pub organization_id: Option<Uuid>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListScannerGrantsQuery {
+    pub activity_id: Option<Uuid>,
+    pub organization_id: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ScannerGrant {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub student_id: String,
+    pub user_name: String,
+    pub activity_id: Option<Uuid>,
+    pub activity_title: Option<String>,
+    pub organization_id: Option<Uuid>,
+    pub organization_name: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub granted_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One row of `GET /activities/{id}/roster`.
+#[derive(Debug, Serialize, FromRow)]
+pub struct RosterEntry {
+    pub participation_id: Uuid,
+    pub user_id: Uuid,
+    pub student_id: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub status: String,
+    pub registered_at: Option<DateTime<Utc>>,
+    pub checked_in_at: Option<DateTime<Utc>>,
+    pub checked_out_at: Option<DateTime<Utc>>,
+}
+
+/// One row of `GET /activities/{id}/sessions/{session_id}/roster`; the
+/// check-in times are for that session only.
+#[derive(Debug, Serialize, FromRow)]
+pub struct SessionRosterEntry {
+    pub participation_id: Uuid,
+    pub user_id: Uuid,
+    pub student_id: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub status: String,
+    pub checked_in_at: Option<DateTime<Utc>>,
+    pub checked_out_at: Option<DateTime<Utc>>,
+}
@@ -1,38 +1,38 @@
 use axum::{Json, extract::{Query, State, Path}, http::{StatusCode, HeaderMap}};
 use serde::Deserialize;
 use sqlx::PgPool;
-use crate::models::{AdminLevel, User};
-use crate::modules::auth::get_claims_from_headers;
+use crate::models::{AdminLevel, User, UserStatus};
+use crate::modules::activities::credit;
+use crate::modules::audit::service::{self as audit, AuditEntry};
+use crate::modules::auth::{get_claims_from_headers, password_policy, throttle};
+use crate::modules::api_keys::service::forbid_api_key;
+use crate::modules::auth::impersonation::forbid_while_impersonating;
+use crate::modules::auth::permissions::{require_permission, Permission};
+use crate::modules::notifications::service::{NotificationService, NotificationType};
 use super::models::{
     UserListItem, UserListResponse, UpdateProfileInput, ChangePasswordInput,
-    AdminUpdateUserInput, AdminResetPasswordInput,
+    AdminUpdateUserInput, AdminResetPasswordInput, ListRegistrationsQuery,
+    PendingRegistrationItem, PendingRegistrationListResponse, ReviewRegistrationInput,
+    LevelProgress, ProgressActivity, StudentProgress,
 };
+use crate::modules::organizations::{DEFAULT_REQUIRED_FACULTY_HOURS, DEFAULT_REQUIRED_UNIVERSITY_HOURS};
 use uuid::Uuid;
 use argon2::{Argon2, PasswordHash, PasswordVerifier, password_hash::{rand_core::OsRng, PasswordHasher, SaltString}};
 
 /// Resolve the organization that owns the target user (via department) and
 /// confirm the caller is allowed to write to it. super_admin bypasses scope;
-/// organization_admin must share the org; everyone else is rejected.
+/// any other admin must share the org. Callers check the specific
+/// `users.*` permission first. Returns the target's organization so the
+/// change can be filed under it in the audit log.
 async fn assert_can_manage_user(
     pool: &PgPool,
     claims: &crate::modules::auth::models::Claims,
     target_user_id: Uuid,
-) -> Result<(), (StatusCode, String)> {
+) -> Result<Option<Uuid>, (StatusCode, String)> {
     if !claims.is_admin {
         return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
     }
 
-    if matches!(claims.admin_level, Some(AdminLevel::SuperAdmin)) {
-        return Ok(());
-    }
-
-    if !matches!(claims.admin_level, Some(AdminLevel::OrganizationAdmin)) {
-        return Err((
-            StatusCode::FORBIDDEN,
-            "Only super_admin or organization_admin can modify users".to_string(),
-        ));
-    }
-
     let target_org: Option<Uuid> = sqlx::query_scalar(
         r#"
         SELECT d.organization_id
@@ -47,14 +47,25 @@ async fn assert_can_manage_user(
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
     .ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))?;
 
-    if target_org != claims.organization_id {
+    if matches!(claims.admin_level, Some(AdminLevel::SuperAdmin)) {
+        return Ok(target_org);
+    }
+
+    // Which writes are allowed is decided by the caller's permission check;
+    // here we only confine non-super admins to their own organization.
+    let admin_org = claims.organization_id.ok_or((
+        StatusCode::FORBIDDEN,
+        "Admin is not assigned to any organization".to_string(),
+    ))?;
+
+    if target_org != Some(admin_org) {
         return Err((
             StatusCode::FORBIDDEN,
             "Cannot modify users outside your organization".to_string(),
         ));
     }
 
-    Ok(())
+    Ok(target_org)
 }
 
 #[derive(Debug, Deserialize)]
@@ -72,10 +83,8 @@ pub async fn list_users(
     headers: HeaderMap,
     Query(params): Query<ListUsersQuery>,
 ) -> Result<Json<UserListResponse>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
-    }
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::UsersView)?;
 
     // Super admin sees all users; org / regular admin only sees users
     // whose department belongs to their own organization. The $1::uuid
@@ -162,10 +171,8 @@ pub async fn get_user(
     headers: HeaderMap,
     Path(user_id): Path<Uuid>,
 ) -> Result<Json<UserListItem>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
-    }
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::UsersView)?;
 
     let scope_org_id: Option<Uuid> = match claims.admin_level {
         Some(AdminLevel::SuperAdmin) => None,
@@ -200,7 +207,9 @@ pub async fn update_profile(
     headers: HeaderMap,
     Json(payload): Json<UpdateProfileInput>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    forbid_while_impersonating(&claims)?;
+    forbid_api_key(&claims)?;
     let user_id = Uuid::parse_str(&claims.sub)
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
 
@@ -232,7 +241,9 @@ pub async fn change_password(
     headers: HeaderMap,
     Json(payload): Json<ChangePasswordInput>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    forbid_while_impersonating(&claims)?;
+    forbid_api_key(&claims)?;
     let user_id = Uuid::parse_str(&claims.sub)
         .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
 
@@ -248,6 +259,7 @@ pub async fn change_password(
     if Argon2::default().verify_password(payload.current_password.as_bytes(), &parsed_hash).is_err() {
         return Err((StatusCode::UNAUTHORIZED, "Current password is incorrect".to_string()));
     }
+    password_policy::enforce(&payload.new_password, &[&user.student_id, &user.email]).await?;
 
     let salt = SaltString::generate(&mut OsRng);
     let new_hash = Argon2::default()
@@ -273,13 +285,18 @@ pub async fn admin_update_user(
     Path(user_id): Path<Uuid>,
     Json(payload): Json<AdminUpdateUserInput>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    assert_can_manage_user(&pool, &claims, user_id).await?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::UsersUpdate)?;
+    let target_org = assert_can_manage_user(&pool, &claims, user_id).await?;
 
     if payload.email.is_none() && payload.status.is_none() {
         return Err((StatusCode::BAD_REQUEST, "No fields to update".to_string()));
     }
 
+    let mut tx = pool.begin().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "users", user_id).await?;
+
     let result = sqlx::query(
         r#"
         UPDATE users SET
@@ -292,7 +309,7 @@ pub async fn admin_update_user(
     .bind(user_id)
     .bind(payload.email.as_deref())
     .bind(payload.status.as_ref())
-    .execute(&pool)
+    .execute(&mut *tx)
     .await
     .map_err(|e| {
         if let Some(db_err) = e.as_database_error() {
@@ -307,6 +324,21 @@ pub async fn admin_update_user(
         return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
     }
 
+    let after = audit::snapshot(&mut *tx, "users", user_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("user.update", "user", user_id)
+            .organization(target_org)
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
+    tx.commit().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
     Ok(Json(serde_json::json!({ "message": "User updated successfully" })))
 }
 
@@ -316,15 +348,16 @@ pub async fn admin_reset_password(
     Path(user_id): Path<Uuid>,
     Json(payload): Json<AdminResetPasswordInput>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    assert_can_manage_user(&pool, &claims, user_id).await?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::UsersResetPassword)?;
+    let target_org = assert_can_manage_user(&pool, &claims, user_id).await?;
 
-    if payload.new_password.len() < 8 {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "Password must be at least 8 characters".to_string(),
-        ));
-    }
+    let (student_id, email): (String, String) = sqlx::query_as("SELECT student_id, email FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(&pool)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    password_policy::enforce(&payload.new_password, &[&student_id, &email]).await?;
 
     let salt = SaltString::generate(&mut OsRng);
     let new_hash = Argon2::default()
@@ -332,12 +365,15 @@ pub async fn admin_reset_password(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to hash password: {}", e)))?
         .to_string();
 
+    let mut tx = pool.begin().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
     let result = sqlx::query(
         "UPDATE users SET password_hash = $2, updated_at = NOW() WHERE id = $1 AND deleted_at IS NULL",
     )
     .bind(user_id)
     .bind(new_hash)
-    .execute(&pool)
+    .execute(&mut *tx)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to reset password: {}", e)))?;
 
@@ -345,9 +381,47 @@ pub async fn admin_reset_password(
         return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
     }
 
+    // Snapshots never carry the hash, so the entry itself is the record.
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("user.reset_password", "user", user_id).organization(target_org),
+    )
+    .await?;
+
+    tx.commit().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
     Ok(Json(serde_json::json!({ "message": "Password reset successfully" })))
 }
 
+/// Clear the login lockout counter for a user who tripped brute-force
+/// protection. Per-IP counters are left alone; they decay on their own.
+pub async fn admin_unlock_login(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::UsersUnlockLogin)?;
+    let target_org = assert_can_manage_user(&pool, &claims, user_id).await?;
+
+    throttle::clear_account(&pool, user_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to unlock user: {}", e)))?;
+
+    audit::record(
+        &pool,
+        &claims,
+        &headers,
+        AuditEntry::new("user.unlock_login", "user", user_id).organization(target_org),
+    )
+    .await?;
+
+    Ok(Json(serde_json::json!({ "message": "User login unlocked" })))
+}
+
 /// Soft-delete: sets deleted_at = NOW() and disables any admin role they hold.
 /// Hard DELETE would be blocked by activities.created_by ON DELETE RESTRICT,
 /// and the rest of the codebase already filters by deleted_at IS NULL, so the
@@ -357,8 +431,9 @@ pub async fn admin_delete_user(
     headers: HeaderMap,
     Path(user_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    assert_can_manage_user(&pool, &claims, user_id).await?;
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::UsersDelete)?;
+    let target_org = assert_can_manage_user(&pool, &claims, user_id).await?;
 
     // Don't let an admin delete themselves — too easy to lock yourself out.
     if claims.sub == user_id.to_string() {
@@ -367,6 +442,7 @@ pub async fn admin_delete_user(
 
     let mut tx = pool.begin().await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "users", user_id).await?;
 
     sqlx::query("UPDATE admin_roles SET is_enabled = false WHERE user_id = $1")
         .bind(user_id)
@@ -386,30 +462,36 @@ pub async fn admin_delete_user(
         return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
     }
 
+    let after = audit::snapshot(&mut *tx, "users", user_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        &headers,
+        AuditEntry::new("user.delete", "user", user_id)
+            .organization(target_org)
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
     tx.commit().await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
 
     Ok(Json(serde_json::json!({ "message": "User deleted successfully" })))
 }
 
-/// List a user's participations with activity context — read access mirrors
-/// get_user (any admin level, scoped to their organization for non-super).
-pub async fn admin_get_user_participations(
-    State(pool): State<PgPool>,
-    headers: HeaderMap,
-    Path(user_id): Path<Uuid>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
-    let claims = get_claims_from_headers(&headers)?;
-    if !claims.is_admin {
-        return Err((StatusCode::FORBIDDEN, "Admin access required".to_string()));
-    }
-
+/// 404 unless the target user exists and, for non-super admins, belongs to a
+/// department of the caller's organization.
+async fn ensure_user_visible(
+    pool: &PgPool,
+    claims: &crate::modules::auth::models::Claims,
+    user_id: Uuid,
+) -> Result<(), (StatusCode, String)> {
     let scope_org_id: Option<Uuid> = match claims.admin_level {
         Some(AdminLevel::SuperAdmin) => None,
         _ => claims.organization_id,
     };
 
-    // Confirm the target user is visible to the caller.
     let exists: Option<Uuid> = sqlx::query_scalar(
         r#"
         SELECT u.id
@@ -422,13 +504,27 @@ pub async fn admin_get_user_participations(
     )
     .bind(user_id)
     .bind(scope_org_id)
-    .fetch_optional(&pool)
+    .fetch_optional(pool)
     .await
     .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    if exists.is_none() {
-        return Err((StatusCode::NOT_FOUND, "User not found".to_string()));
+    match exists {
+        Some(_) => Ok(()),
+        None => Err((StatusCode::NOT_FOUND, "User not found".to_string())),
     }
+}
+
+/// List a user's participations with activity context — read access mirrors
+/// get_user (any admin level, scoped to their organization for non-super).
+pub async fn admin_get_user_participations(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::UsersView)?;
+
+    ensure_user_visible(&pool, &claims, user_id).await?;
 
     #[derive(sqlx::FromRow)]
     struct Row {
@@ -444,12 +540,13 @@ pub async fn admin_get_user_participations(
         start_date: chrono::NaiveDate,
         end_date: chrono::NaiveDate,
         hours: i16,
+        credited_hours: f64,
         organizer_name: String,
         activity_type: String,
         activity_level: Option<String>,
     }
 
-    let rows = sqlx::query_as::<_, Row>(
+    let rows = sqlx::query_as::<_, Row>(&format!(
         r#"
         SELECT
             p.id, p.status::text AS status, p.registered_at, p.checked_in_at, p.checked_out_at, p.notes,
@@ -459,6 +556,7 @@ pub async fn admin_get_user_participations(
             a.start_date,
             a.end_date,
             a.hours,
+            {credited_hours} AS credited_hours,
             o.name AS organizer_name,
             a.activity_type::text AS activity_type,
             a.activity_level::text AS activity_level
@@ -468,7 +566,8 @@ pub async fn admin_get_user_participations(
         WHERE p.user_id = $1
         ORDER BY a.start_date DESC, p.registered_at DESC
         "#,
-    )
+        credited_hours = credit::EFFECTIVE_HOURS_SQL,
+    ))
     .bind(user_id)
     .fetch_all(&pool)
     .await
@@ -476,16 +575,16 @@ pub async fn admin_get_user_participations(
 
     // Aggregate stats so the frontend can show a one-look summary card.
     let total = rows.len();
-    let mut total_hours: i32 = 0;
-    let mut faculty_hours: i32 = 0;
-    let mut university_hours: i32 = 0;
+    let mut total_hours: f64 = 0.0;
+    let mut faculty_hours: f64 = 0.0;
+    let mut university_hours: f64 = 0.0;
     let mut completed = 0;
     for r in &rows {
         if r.status == "completed" || r.status == "checked_out" {
-            total_hours += r.hours as i32;
+            total_hours += r.credited_hours;
             match r.activity_level.as_deref() {
-                Some("university") => university_hours += r.hours as i32,
-                Some("faculty") => faculty_hours += r.hours as i32,
+                Some("university") => university_hours += r.credited_hours,
+                Some("faculty") => faculty_hours += r.credited_hours,
                 _ => {}
             }
             completed += 1;
@@ -500,6 +599,7 @@ pub async fn admin_get_user_participations(
             "checked_in_at": r.checked_in_at,
             "checked_out_at": r.checked_out_at,
             "notes": r.notes,
+            "credited_hours": r.credited_hours,
             "activity": {
                 "id": r.activity_id,
                 "title": r.activity_title,
@@ -525,3 +625,331 @@ pub async fn admin_get_user_participations(
         "participations": participations
     })))
 }
+
+/// Hours a student has completed at each level against their organization's
+/// requirement, with the activities that count toward each.
+async fn load_progress(pool: &PgPool, user_id: Uuid) -> Result<StudentProgress, (StatusCode, String)> {
+    let org = sqlx::query_as::<_, ProgressOrg>(
+        r#"
+        SELECT d.organization_id, o.name AS organization_name,
+               r.required_faculty_hours, r.required_university_hours
+        FROM users u
+        LEFT JOIN departments d ON u.department_id = d.id
+        LEFT JOIN organizations o ON d.organization_id = o.id
+        LEFT JOIN org_activity_requirements r ON r.organization_id = d.organization_id
+        WHERE u.id = $1 AND u.deleted_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .ok_or((StatusCode::NOT_FOUND, "User not found".to_string()))?;
+
+    let activities = sqlx::query_as::<_, ProgressActivity>(&format!(
+        r#"
+        SELECT p.id AS participation_id, a.id AS activity_id, a.title, a.start_date,
+               o.name AS organizer_name, a.activity_level::text AS activity_level,
+               {credited_hours} AS credited_hours
+        FROM participations p
+        JOIN activities a ON p.activity_id = a.id
+        JOIN organizations o ON a.organizer_id = o.id
+        WHERE p.user_id = $1
+          AND p.status IN ('checked_out', 'completed')
+          AND a.activity_level IN ('faculty', 'university')
+        ORDER BY a.start_date, p.registered_at
+        "#,
+        credited_hours = credit::EFFECTIVE_HOURS_SQL,
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to fetch participations: {}", e)))?;
+
+    Ok(build_progress(user_id, org, activities))
+}
+
+/// The student's organization and its configured requirement, if any.
+#[derive(sqlx::FromRow)]
+struct ProgressOrg {
+    organization_id: Option<Uuid>,
+    organization_name: Option<String>,
+    required_faculty_hours: Option<i32>,
+    required_university_hours: Option<i32>,
+}
+
+/// Split counted activities by level and measure each against the
+/// organization's requirement, or the defaults when it has none.
+fn build_progress(user_id: Uuid, org: ProgressOrg, activities: Vec<ProgressActivity>) -> StudentProgress {
+    let (university, faculty): (Vec<_>, Vec<_>) =
+        activities.into_iter().partition(|a| a.activity_level == "university");
+    let faculty = LevelProgress::new(
+        org.required_faculty_hours.unwrap_or(DEFAULT_REQUIRED_FACULTY_HOURS),
+        faculty,
+    );
+    let university = LevelProgress::new(
+        org.required_university_hours.unwrap_or(DEFAULT_REQUIRED_UNIVERSITY_HOURS),
+        university,
+    );
+
+    StudentProgress {
+        user_id,
+        organization_id: org.organization_id,
+        organization_name: org.organization_name,
+        requirements_met: faculty.remaining_hours <= 0.0 && university.remaining_hours <= 0.0,
+        faculty,
+        university,
+    }
+}
+
+pub async fn get_my_progress(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+) -> Result<Json<StudentProgress>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    let user_id = Uuid::parse_str(&claims.sub)
+        .map_err(|_| (StatusCode::UNAUTHORIZED, "Invalid user ID".to_string()))?;
+    Ok(Json(load_progress(&pool, user_id).await?))
+}
+
+pub async fn admin_get_user_progress(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<StudentProgress>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::UsersView)?;
+    ensure_user_visible(&pool, &claims, user_id).await?;
+    Ok(Json(load_progress(&pool, user_id).await?))
+}
+
+/// Approval queue: self-registrations still `pending` in the caller's
+/// organization, oldest first.
+pub async fn list_pending_registrations(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Query(params): Query<ListRegistrationsQuery>,
+) -> Result<Json<PendingRegistrationListResponse>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(&pool, &headers).await?;
+    require_permission(&claims, Permission::UsersApprove)?;
+
+    let scope_org_id: Option<Uuid> = match claims.admin_level {
+        Some(AdminLevel::SuperAdmin) => params.organization_id,
+        _ => Some(claims.organization_id.ok_or((
+            StatusCode::FORBIDDEN,
+            "Admin is not assigned to any organization".to_string(),
+        ))?),
+    };
+
+    let page = params.page.unwrap_or(1).max(1);
+    let per_page = params.per_page.unwrap_or(50).clamp(1, 200);
+    let offset = (page - 1) * per_page;
+
+    let total: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM users u
+        LEFT JOIN departments d ON u.department_id = d.id
+        WHERE u.status = 'pending'::user_status
+          AND u.deleted_at IS NULL
+          AND ($1::uuid IS NULL OR d.organization_id = $1)
+        "#,
+    )
+    .bind(scope_org_id)
+    .fetch_one(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to count registrations: {}", e)))?;
+
+    let registrations = sqlx::query_as::<_, PendingRegistrationItem>(
+        r#"
+        SELECT
+            u.id, u.student_id, u.email, u.prefix, u.first_name, u.last_name, u.phone,
+            u.department_id, d.name AS department_name,
+            d.organization_id, o.name AS organization_name,
+            u.email_verified_at, u.created_at
+        FROM users u
+        LEFT JOIN departments d ON u.department_id = d.id
+        LEFT JOIN organizations o ON d.organization_id = o.id
+        WHERE u.status = 'pending'::user_status
+          AND u.deleted_at IS NULL
+          AND ($1::uuid IS NULL OR d.organization_id = $1)
+        ORDER BY u.created_at ASC
+        LIMIT $2 OFFSET $3
+        "#,
+    )
+    .bind(scope_org_id)
+    .bind(per_page)
+    .bind(offset)
+    .fetch_all(&pool)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to fetch registrations: {}", e)))?;
+
+    Ok(Json(PendingRegistrationListResponse { registrations, total }))
+}
+
+pub async fn approve_registration(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+    Json(payload): Json<ReviewRegistrationInput>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    review_registration(&pool, &headers, user_id, true, payload).await
+}
+
+pub async fn reject_registration(
+    State(pool): State<PgPool>,
+    headers: HeaderMap,
+    Path(user_id): Path<Uuid>,
+    Json(payload): Json<ReviewRegistrationInput>,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    review_registration(&pool, &headers, user_id, false, payload).await
+}
+
+/// Move a `pending` registration to `active` or `rejected`, then tell the
+/// student. Reviewing a registration twice is a conflict.
+async fn review_registration(
+    pool: &PgPool,
+    headers: &HeaderMap,
+    user_id: Uuid,
+    approve: bool,
+    payload: ReviewRegistrationInput,
+) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let claims = get_claims_from_headers(pool, headers).await?;
+    require_permission(&claims, Permission::UsersApprove)?;
+    let target_org = assert_can_manage_user(pool, &claims, user_id).await?;
+
+    let reason = payload.reason.as_deref().map(str::trim).filter(|s| !s.is_empty());
+    if !approve && reason.is_none() {
+        return Err((StatusCode::BAD_REQUEST, "A reason is required to reject a registration".to_string()));
+    }
+    let (new_status, action) = if approve {
+        (UserStatus::Active, "user.approve_registration")
+    } else {
+        (UserStatus::Rejected, "user.reject_registration")
+    };
+
+    let mut tx = pool.begin().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let before = audit::snapshot(&mut *tx, "users", user_id).await?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE users SET
+            status = $2,
+            registration_reviewed_at = NOW(),
+            registration_reviewed_by = $3,
+            registration_review_reason = $4,
+            updated_at = NOW()
+        WHERE id = $1 AND status = 'pending'::user_status AND deleted_at IS NULL
+        "#,
+    )
+    .bind(user_id)
+    .bind(&new_status)
+    .bind(Uuid::parse_str(&claims.sub).ok())
+    .bind(reason)
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to review registration: {}", e)))?;
+
+    if result.rows_affected() == 0 {
+        return Err((StatusCode::CONFLICT, "Registration is not pending".to_string()));
+    }
+
+    let after = audit::snapshot(&mut *tx, "users", user_id).await?;
+    audit::record(
+        &mut *tx,
+        &claims,
+        headers,
+        AuditEntry::new(action, "user", user_id)
+            .organization(target_org)
+            .before(before)
+            .after(after),
+    )
+    .await?;
+
+    tx.commit().await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed to commit: {}", e)))?;
+
+    let notified = if approve {
+        NotificationService::send(
+            pool,
+            user_id,
+            "✅ การสมัครสมาชิกได้รับการอนุมัติ",
+            &match reason {
+                Some(r) => format!("บัญชีของคุณได้รับการอนุมัติแล้ว สามารถเข้าร่วมกิจกรรมได้ทันที ({})", r),
+                None => "บัญชีของคุณได้รับการอนุมัติแล้ว สามารถเข้าร่วมกิจกรรมได้ทันที".to_string(),
+            },
+            NotificationType::Success,
+            Some("/student"),
+        ).await
+    } else {
+        NotificationService::send(
+            pool,
+            user_id,
+            "❌ การสมัครสมาชิกไม่ได้รับการอนุมัติ",
+            &format!("เหตุผล: {}", reason.unwrap_or_default()),
+            NotificationType::Warning,
+            None,
+        ).await
+    };
+    if let Err(e) = notified {
+        tracing::error!("Failed to notify user {} about registration review: {}", user_id, e);
+    }
+
+    let message = if approve { "Registration approved" } else { "Registration rejected" };
+    Ok(Json(serde_json::json!({ "message": message })))
+}
+
+#[cfg(test)]
+mod progress_tests {
+    use super::*;
+
+    fn activity(level: &str, hours: f64) -> ProgressActivity {
+        ProgressActivity {
+            participation_id: Uuid::new_v4(),
+            activity_id: Uuid::new_v4(),
+            title: "A".into(),
+            start_date: chrono::NaiveDate::from_ymd_opt(2026, 10, 17).unwrap(),
+            organizer_name: "Org".into(),
+            activity_level: level.into(),
+            credited_hours: hours,
+        }
+    }
+
+    fn org(required: Option<(i32, i32)>) -> ProgressOrg {
+        ProgressOrg {
+            organization_id: None,
+            organization_name: None,
+            required_faculty_hours: required.map(|r| r.0),
+            required_university_hours: required.map(|r| r.1),
+        }
+    }
+
+    #[test]
+    fn hours_are_split_by_level_against_the_defaults() {
+        let progress = build_progress(
+            Uuid::nil(),
+            org(None),
+            vec![activity("faculty", 4.0), activity("university", 3.5), activity("faculty", 1.5)],
+        );
+        assert_eq!(progress.faculty.required_hours, DEFAULT_REQUIRED_FACULTY_HOURS);
+        assert_eq!(progress.university.required_hours, DEFAULT_REQUIRED_UNIVERSITY_HOURS);
+        assert_eq!(progress.faculty.activities.len(), 2);
+        assert_eq!(progress.faculty.completed_hours, 5.5);
+        assert_eq!(progress.faculty.remaining_hours, 0.5);
+        assert_eq!(progress.university.completed_hours, 3.5);
+        assert_eq!(progress.university.remaining_hours, 8.5);
+        assert!(!progress.requirements_met);
+    }
+
+    #[test]
+    fn remaining_clamps_at_zero_once_every_requirement_is_met() {
+        let progress = build_progress(
+            Uuid::nil(),
+            org(Some((2, 0))),
+            vec![activity("faculty", 3.0), activity("university", 1.0)],
+        );
+        assert_eq!(progress.faculty.remaining_hours, 0.0);
+        assert_eq!(progress.university.remaining_hours, 0.0);
+        assert!(progress.requirements_met);
+    }
+}
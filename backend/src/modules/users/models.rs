@@ -53,3 +53,86 @@ pub struct AdminUpdateUserInput {
 pub struct AdminResetPasswordInput {
     pub new_password: String,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct ListRegistrationsQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// Super admins only; other admins always see their own organization.
+    pub organization_id: Option<Uuid>,
+}
+
+/// A self-registration waiting in an organization's approval queue.
+#[derive(Debug, FromRow, Serialize)]
+pub struct PendingRegistrationItem {
+    pub id: Uuid,
+    pub student_id: String,
+    pub email: String,
+    pub prefix: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub phone: Option<String>,
+    pub department_id: Option<Uuid>,
+    pub department_name: Option<String>,
+    pub organization_id: Option<Uuid>,
+    pub organization_name: Option<String>,
+    pub email_verified_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PendingRegistrationListResponse {
+    pub registrations: Vec<PendingRegistrationItem>,
+    pub total: i64,
+}
+
+/// Body of approve/reject. The reason is shown to the student and is
+/// required when rejecting.
+#[derive(Debug, Deserialize, Default)]
+pub struct ReviewRegistrationInput {
+    pub reason: Option<String>,
+}
+
+/// A completed participation counted toward a level's requirement.
+#[derive(Debug, Serialize, FromRow)]
+pub struct ProgressActivity {
+    pub participation_id: Uuid,
+    pub activity_id: Uuid,
+    pub title: String,
+    pub start_date: chrono::NaiveDate,
+    pub organizer_name: String,
+    #[serde(skip)]
+    pub activity_level: String,
+    pub credited_hours: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LevelProgress {
+    pub required_hours: i32,
+    pub completed_hours: f64,
+    pub remaining_hours: f64,
+    pub activities: Vec<ProgressActivity>,
+}
+
+impl LevelProgress {
+    pub fn new(required_hours: i32, activities: Vec<ProgressActivity>) -> Self {
+        let completed_hours: f64 = activities.iter().map(|a| a.credited_hours).sum();
+        Self {
+            required_hours,
+            completed_hours,
+            remaining_hours: (required_hours as f64 - completed_hours).max(0.0),
+            activities,
+        }
+    }
+}
+
+/// `GET /users/me/progress` and `GET /users/{id}/progress`.
+#[derive(Debug, Serialize)]
+pub struct StudentProgress {
+    pub user_id: Uuid,
+    pub organization_id: Option<Uuid>,
+    pub organization_name: Option<String>,
+    pub faculty: LevelProgress,
+    pub university: LevelProgress,
+    pub requirements_met: bool,
+}
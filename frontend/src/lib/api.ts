@@ -16,20 +16,80 @@ export class ApiError extends Error {
     }
 }
 
+type PasswordViolation =
+    | { code: 'too_short'; min_length: number }
+    | { code: 'too_long'; max_length: number }
+    | { code: 'same_as_identifier' }
+    | { code: 'too_few_char_classes'; min_char_classes: number }
+    | { code: 'breached' };
+
+/**
+ * Thai description of a 422 `password_policy` rejection, or null when the
+ * error is something else.
+ */
+export function passwordPolicyMessage(err: unknown): string | null {
+    if (!(err instanceof ApiError) || err.status !== 422) return null;
+    try {
+        const body = JSON.parse(err.message) as { code?: string; violations?: PasswordViolation[] };
+        if (body.code !== 'password_policy' || !body.violations) return null;
+        return body.violations
+            .map((v) => {
+                switch (v.code) {
+                    case 'too_short':
+                        return `รหัสผ่านต้องมีอย่างน้อย ${v.min_length} ตัวอักษร`;
+                    case 'too_long':
+                        return `รหัสผ่านต้องมีไม่เกิน ${v.max_length} ตัวอักษร`;
+                    case 'same_as_identifier':
+                        return 'รหัสผ่านต้องไม่ซ้ำกับรหัสนักศึกษาหรืออีเมล';
+                    case 'too_few_char_classes':
+                        return `รหัสผ่านต้องประกอบด้วยตัวพิมพ์เล็ก ตัวพิมพ์ใหญ่ ตัวเลข หรือสัญลักษณ์อย่างน้อย ${v.min_char_classes} ประเภท`;
+                    case 'breached':
+                        return 'รหัสผ่านนี้เคยรั่วไหลสู่สาธารณะ กรุณาใช้รหัสผ่านอื่น';
+                }
+            })
+            .join('\n');
+    } catch {
+        return null;
+    }
+}
+
+// Access tokens are short-lived; a 401 usually just means the cookie expired.
+// Concurrent 401s share one in-flight refresh so the rotating refresh token
+// is only presented once.
+let refreshing: Promise<boolean> | null = null;
+const NO_REFRESH_PATHS = ['/auth/login', '/auth/2fa/login', '/auth/refresh', '/auth/logout'];
+
+function refreshSession(): Promise<boolean> {
+    refreshing ??= fetch(`${API_BASE}/auth/refresh`, { method: 'POST', credentials: 'include' })
+        // 409 = another tab rotated the token a moment ago; its new cookies are already set
+        .then((r) => r.ok || r.status === 409)
+        .catch(() => false)
+        .finally(() => {
+            refreshing = null;
+        });
+    return refreshing;
+}
+
 export async function request<T>(
     path: string,
     options: RequestInit = {}
 ): Promise<T> {
     const url = `${API_BASE}${path}`;
 
-    const response = await fetch(url, {
-        ...options,
-        credentials: 'include', // Always send cookies
-        headers: {
-            'Content-Type': 'application/json',
-            ...options.headers,
-        },
-    });
+    const send = () =>
+        fetch(url, {
+            ...options,
+            credentials: 'include', // Always send cookies
+            headers: {
+                'Content-Type': 'application/json',
+                ...options.headers,
+            },
+        });
+
+    let response = await send();
+    if (response.status === 401 && !NO_REFRESH_PATHS.includes(path) && (await refreshSession())) {
+        response = await send();
+    }
 
     if (!response.ok) {
         const text = await response.text();
@@ -90,24 +150,64 @@ export interface UserResponse {
     department_name?: string | null;
     session_id: string;
     expires_at: string;
+    /** Admin whose role requires 2FA but who hasn't enrolled yet; admin claims are withheld until then. */
+    two_factor_setup_required?: boolean;
     created_at?: string | null;
     updated_at?: string | null;
 }
 
 export interface AuthResponse {
     token: string;
+    access_expires_at: string;
     user: UserResponse;
 }
 
+/** `/auth/login` answers with this instead of a session when the account has 2FA on. */
+export interface TwoFactorChallenge {
+    two_factor_required: true;
+    challenge_token: string;
+    expires_at: string;
+}
+
+export function isTwoFactorChallenge(r: AuthResponse | TwoFactorChallenge): r is TwoFactorChallenge {
+    return 'two_factor_required' in r && r.two_factor_required;
+}
+
+export interface TwoFactorLoginInput {
+    challenge_token: string;
+    code?: string;
+    recovery_code?: string;
+}
+
+export interface TwoFactorSetup {
+    secret: string;
+    otpauth_uri: string;
+}
+
 export const auth = {
     login: (data: LoginInput) =>
-        request<AuthResponse>('/auth/login', {
+        request<AuthResponse | TwoFactorChallenge>('/auth/login', {
             method: 'POST',
             body: JSON.stringify(data),
         }),
 
+    twoFactorLogin: (data: TwoFactorLoginInput) =>
+        request<AuthResponse>('/auth/2fa/login', {
+            method: 'POST',
+            body: JSON.stringify(data),
+        }),
+
+    twoFactorSetup: () =>
+        request<TwoFactorSetup>('/auth/2fa/setup', { method: 'POST' }),
+
+    twoFactorVerify: (code: string) =>
+        request<{ recovery_codes: string[]; token: string; access_expires_at: string }>('/auth/2fa/verify', {
+            method: 'POST',
+            body: JSON.stringify({ code }),
+        }),
+
     register: (data: RegisterInput) =>
-        request<{ user_id: string; message: string }>('/auth/register', {
+        request<{ user_id: string; message: string; pending_approval: boolean }>('/auth/register', {
             method: 'POST',
             body: JSON.stringify(data),
         }),
@@ -122,6 +222,16 @@ export const auth = {
         body: JSON.stringify({ token, new_password })
     }),
 
+    verifyEmail: (token: string) => request<{ message: string }>('/auth/verify-email', {
+        method: 'POST',
+        body: JSON.stringify({ token })
+    }),
+
+    resendVerification: (email: string) => request<{ message: string }>('/auth/resend-verification', {
+        method: 'POST',
+        body: JSON.stringify({ email })
+    }),
+
     logout: () =>
         request<{ message: string }>('/auth/logout', { method: 'POST' }),
 
@@ -200,6 +310,8 @@ export interface Participation {
     checked_in_at: string | null;
     checked_out_at: string | null;
     notes: string | null;
+    /** Hours actually credited (attendance-based or admin override). */
+    credited_hours?: number;
     activity: {
         id: string;
         title: string;
@@ -253,6 +365,26 @@ export interface ManualCompleteParticipationsResponse {
     results: ManualCompleteParticipationResult[];
 }
 
+export interface ActivityCancellation {
+    id: string;
+    user_id: string;
+    student_id: string;
+    first_name: string;
+    last_name: string;
+    reason: string | null;
+    registered_at: string | null;
+    cancelled_at: string;
+}
+
+export interface WaitlistEntry {
+    user_id: string;
+    student_id: string;
+    first_name: string;
+    last_name: string;
+    position: number;
+    created_at: string;
+}
+
 export const activitiesApi = {
     dashboard: () =>
         request<DashboardResponse>('/activities/dashboard'),
@@ -289,6 +421,34 @@ export const activitiesApi = {
             method: 'POST',
         }),
 
+    cancelRegistration: (id: string, reason?: string) =>
+        request<{ message: string }>(`/activities/${id}/join`, {
+            method: 'DELETE',
+            body: JSON.stringify({ reason: reason || null }),
+        }),
+
+    cancellations: (id: string) =>
+        request<ActivityCancellation[]>(`/activities/${id}/cancellations`),
+
+    joinWaitlist: (id: string) =>
+        request<{ message: string; position: number }>(`/activities/${id}/waitlist`, {
+            method: 'POST',
+        }),
+
+    leaveWaitlist: (id: string) =>
+        request<{ message: string }>(`/activities/${id}/waitlist`, {
+            method: 'DELETE',
+        }),
+
+    waitlist: (id: string) =>
+        request<WaitlistEntry[]>(`/activities/${id}/waitlist`),
+
+    reorderWaitlist: (id: string, userIds: string[]) =>
+        request<WaitlistEntry[]>(`/activities/${id}/waitlist/order`, {
+            method: 'PUT',
+            body: JSON.stringify({ user_ids: userIds }),
+        }),
+
     manualCompleteParticipations: (id: string, data: ManualCompleteParticipationsInput) =>
         request<ManualCompleteParticipationsResponse>(`/activities/${id}/participations/manual-complete`, {
             method: 'POST',
@@ -396,8 +556,58 @@ export const organizationsApi = {
             method: 'PUT',
             body: JSON.stringify(data),
         }),
+
+    complianceReport: (organizationId: string, params?: ComplianceReportParams) => {
+        const qs = new URLSearchParams();
+        if (params?.page) qs.set('page', String(params.page));
+        if (params?.per_page) qs.set('per_page', String(params.per_page));
+        if (params?.department_id) qs.set('department_id', params.department_id);
+        if (params?.cohort) qs.set('cohort', params.cohort);
+        if (params?.status) qs.set('status', params.status);
+        if (params?.sort) qs.set('sort', params.sort);
+        if (params?.order) qs.set('order', params.order);
+        const suffix = qs.toString() ? `?${qs.toString()}` : '';
+        return request<ComplianceReport>(`/organizations/${organizationId}/compliance${suffix}`);
+    },
 };
 
+export interface ComplianceReportParams {
+    page?: number;
+    per_page?: number;
+    department_id?: string;
+    /** Leading digits of the student ID, e.g. `64`. */
+    cohort?: string;
+    status?: 'all' | 'behind' | 'met';
+    sort?: 'student_id' | 'name' | 'faculty_shortfall' | 'university_shortfall' | 'total_shortfall';
+    order?: 'asc' | 'desc';
+}
+
+export interface ComplianceRow {
+    user_id: string;
+    student_id: string;
+    prefix: string;
+    first_name: string;
+    last_name: string;
+    department_id: string | null;
+    department_name: string | null;
+    faculty_hours: number;
+    university_hours: number;
+    faculty_shortfall: number;
+    university_shortfall: number;
+}
+
+export interface ComplianceReport {
+    organization_id: string;
+    required_faculty_hours: number;
+    required_university_hours: number;
+    total: number;
+    students_in_scope: number;
+    behind: number;
+    page: number;
+    per_page: number;
+    students: ComplianceRow[];
+}
+
 // ─── Departments ───────────────────────────────────────────────────────────
 
 export interface CreateDepartmentInput {
@@ -516,8 +726,37 @@ export const usersApi = {
 
     adminParticipations: (id: string) =>
         request<UserParticipationsResponse>(`/users/${id}/participations`),
+
+    myProgress: () => request<StudentProgress>('/users/me/progress'),
+
+    adminProgress: (id: string) => request<StudentProgress>(`/users/${id}/progress`),
 };
 
+export interface ProgressActivity {
+    participation_id: string;
+    activity_id: string;
+    title: string;
+    start_date: string;
+    organizer_name: string;
+    credited_hours: number;
+}
+
+export interface LevelProgress {
+    required_hours: number;
+    completed_hours: number;
+    remaining_hours: number;
+    activities: ProgressActivity[];
+}
+
+export interface StudentProgress {
+    user_id: string;
+    organization_id: string | null;
+    organization_name: string | null;
+    faculty: LevelProgress;
+    university: LevelProgress;
+    requirements_met: boolean;
+}
+
 export interface UserParticipation {
     id: string;
     status: 'registered' | 'checked_in' | 'checked_out' | 'completed' | 'no_show';
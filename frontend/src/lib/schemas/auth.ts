@@ -71,7 +71,7 @@ export const registerSchema = z
 		email: z.string().min(1, 'กรุณาใส่อีเมล').email('รูปแบบอีเมลไม่ถูกต้อง'),
 		password: z
 			.string()
-			.min(6, 'รหัสผ่านต้องมีอย่างน้อย 6 ตัวอักษร')
+			.min(8, 'รหัสผ่านต้องมีอย่างน้อย 8 ตัวอักษร')
 			.regex(/^(?=.*[a-z])(?=.*[A-Z])(?=.*\d)/, 'รหัสผ่านต้องมีตัวพิมพ์เล็ก พิมพ์ใหญ่ และตัวเลข'),
 		confirmPassword: z.string().min(1, 'กรุณายืนยันรหัสผ่าน'),
 		organization_id: z.string().min(1, 'กรุณาเลือกหน่วยงาน'),
@@ -104,7 +104,7 @@ export const adminCreateSchema = z
 		password: z
 			.string()
 			.min(1, 'กรุณาใส่รหัสผ่าน')
-			.min(6, 'รหัสผ่านต้องมีอย่างน้อย 6 ตัวอักษร')
+			.min(8, 'รหัสผ่านต้องมีอย่างน้อย 8 ตัวอักษร')
 			.regex(/^(?=.*[a-z])(?=.*[A-Z])(?=.*\d)/, 'รหัสผ่านต้องมีตัวพิมพ์เล็ก พิมพ์ใหญ่ และตัวเลข'),
 		admin_level: z.nativeEnum(AdminLevel, {
 			message: 'กรุณาเลือกระดับแอดมิน'
@@ -62,6 +62,7 @@ function createAuthStore() {
 
         setUser(u: UserResponse) {
             user = u;
+            loading = false;
         },
 
         clear() {